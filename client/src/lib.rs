@@ -3,6 +3,9 @@ use sauron::prelude::*;
 use sauron::web_sys::Response;
 use serde::{Deserialize, Serialize};
 
+/// API responses as the server sends them, shared with the server and the indexer
+pub use explorer_types as types;
+
 #[macro_use]
 extern crate log;
 
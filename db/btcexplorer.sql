@@ -109,6 +109,31 @@ create unlogged table tx (
     primary key (txhash)
 );
 
+-- `final_outputs` are outputs of the final transactions, indexed by their script.
+-- Inputs are recorded as the spending of the output they refer to
+--
+-- The indexer resumes after the highest block of `final_blocks` and never goes back,
+-- so the database indexed before this table was added keeps it empty:
+-- apply this schema again and reindex from the genesis block to fill it.
+drop table if exists final_outputs;
+create table final_outputs (
+    txhash       bytea,   -- transaction hash
+    vout         int,     -- index of the output in the transaction
+    blockheight  int,     -- block height
+    txindex      int,     -- transaction index in this block
+    scripthash   bytea,   -- SHA-256 of the output script, as in Electrum protocol but not reversed
+    script       bytea,   -- output script
+    address      text,    -- address of the output script, null for non-standard scripts
+    value        bigint,  -- amount in satoshi
+    spent_txhash bytea,   -- transaction spending the output, null while it is unspent
+    spent_vin    int,     -- index of the input spending the output
+    spent_height int,     -- height of the block of the spending transaction
+    primary key (txhash, vout)
+);
+create index idx_final_outputs_scripthash on final_outputs (scripthash, blockheight);
+create index idx_final_outputs_address on final_outputs (address, blockheight);
+create index idx_final_outputs_spent on final_outputs (spent_txhash);
+
 -- `final_addr` are transactions groupped by address
 -- drop table if exists final_addr;
 -- create table final_addr (
@@ -136,3 +161,41 @@ create unlogged table tx (
 -- create index idx_addr_addr on unconfirmed_addr (addr);
 -- create index idx_addr_blockhash on unconfirmed_addr (blockhash, txindex);
 -- create index idx_addr_txhash on unconfirmed_addr (txhash);
+
+-- `api_cache` is the cache of the node replies shared by server replicas
+-- volatile entries describe the tip of the chain and are dropped on a new block
+drop table if exists api_cache;
+create unlogged table api_cache (
+    key         text,        -- kind and parameters of the request
+    value       text,        -- serialized reply
+    volatile    boolean,     -- whether it should be dropped on a new block
+    expires_at  timestamptz, -- end of the entry lifetime
+    primary key (key)
+);
+
+-- `api_keys` are the keys of the API clients with higher rate limits
+-- tiers are defined by `RATE_LIMIT_TIERS` setting of the server
+drop table if exists api_keys;
+create table api_keys (
+    key_hash    text,        -- SHA-256 hex digest of the key, the key itself is not stored
+    owner       text,        -- who the key was issued to
+    tier        text,        -- name of the rate limit tier
+    enabled     boolean default true,
+    created_at  timestamptz default now(),
+    primary key (key_hash)
+);
+
+-- `broadcast_log` is the audit log of the transactions sent with `POST /api/tx`
+drop table if exists broadcast_log;
+create table broadcast_log (
+    id          bigserial,
+    created_at  timestamptz default now(),
+    client      text,        -- `ip:<address>` or `key:<digest>` of the caller
+    network     text,        -- network the transaction was sent to
+    txid        text,        -- null if the transaction could not be decoded by the node
+    size        int,         -- size of the raw transaction, in bytes
+    outcome     text,        -- `sent`, `rejected` or `failed`
+    reason      text,        -- rejection reason or error message
+    primary key (id)
+);
+create index idx_broadcast_log_txid on broadcast_log (txid);
@@ -1,8 +1,9 @@
 use crate::btc;
 use chrono::{DateTime, NaiveDateTime, Utc};
+use sha2::{Digest, Sha256};
 use sqlx::pool::PoolConnection;
-use sqlx::Acquire;
 use sqlx::Postgres;
+use sqlx::{Acquire, Transaction};
 
 pub async fn max_final_height(conn: &mut PoolConnection<Postgres>) -> Result<i32, anyhow::Error> {
     let row: (i32,) = sqlx::query_as("SELECT COALESCE(MAX(blockheight),0) FROM final_blocks")
@@ -11,6 +12,74 @@ pub async fn max_final_height(conn: &mut PoolConnection<Postgres>) -> Result<i32
     Ok(row.0)
 }
 
+/// saves the outputs of the transaction to the scripthash and address index
+async fn persist_outputs(
+    tx: &mut Transaction<'_, Postgres>,
+    t: &btc::BlockTransaction,
+    txb: &[u8],
+    height: u32,
+    txindex: i32,
+    with_index: bool,
+) -> Result<(), anyhow::Error> {
+    let on_conflict = if with_index {
+        "ON CONFLICT (txhash, vout) DO NOTHING"
+    } else {
+        ""
+    };
+    let sql = [
+        "INSERT INTO final_outputs ( \
+            txhash, vout, blockheight, txindex, scripthash, script, address, value
+        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+        on_conflict,
+    ]
+    .join(" ");
+    for out in t.vout.iter() {
+        let script = hex::decode(out.script_pub_key.hex.as_str())?;
+        let scripthash = Sha256::digest(&script).to_vec();
+        sqlx::query(sql.as_str())
+            .bind(txb)
+            .bind(out.n as i32)
+            .bind(height as i32)
+            .bind(txindex)
+            .bind(scripthash)
+            .bind(script)
+            .bind(out.script_pub_key.address())
+            .bind(out.sats())
+            .execute(&mut *tx)
+            .await?;
+    }
+    Ok(())
+}
+
+/// marks the outputs spent by the inputs of the transaction
+async fn persist_spends(
+    tx: &mut Transaction<'_, Postgres>,
+    t: &btc::BlockTransaction,
+    txb: &[u8],
+    height: u32,
+) -> Result<(), anyhow::Error> {
+    for (vin, input) in t.vin.iter().enumerate() {
+        // coinbase input has no previous output
+        let (prev, vout) = match (&input.txid, input.vout) {
+            (Some(prev), Some(vout)) => (hex::decode(prev)?, vout),
+            _ => continue,
+        };
+        sqlx::query(
+            "UPDATE final_outputs \
+                SET spent_txhash = $1, spent_vin = $2, spent_height = $3 \
+                WHERE txhash = $4 AND vout = $5",
+        )
+        .bind(txb)
+        .bind(vin as i32)
+        .bind(height as i32)
+        .bind(prev)
+        .bind(vout as i32)
+        .execute(&mut *tx)
+        .await?;
+    }
+    Ok(())
+}
+
 pub async fn persist(
     conn: &mut PoolConnection<Postgres>,
     block: &btc::BlockInfoCombined,
@@ -39,8 +108,9 @@ pub async fn persist(
     let mut txindex: i32 = 0;
     for tptr in block.info.tx.iter() {
         let t = tptr.clone();
-        let txb = hex::decode(t.hash)?;
-        let total: f32 = t.vout.iter().map(|x| x.value).sum();
+        // `hash` is the witness hash, inputs refer to `txid`
+        let txb = hex::decode(t.txid.as_ref().unwrap_or(&t.hash))?;
+        let total: i64 = t.vout.iter().map(|x| x.sats()).sum();
 
         let on_conflict = if with_index {
             "ON CONFLICT (txhash) DO NOTHING"
@@ -62,6 +132,8 @@ pub async fn persist(
             .bind(0_f64)
             .execute(&mut tx)
             .await?;
+        persist_outputs(&mut tx, &t, &txb, height, txindex, with_index).await?;
+        persist_spends(&mut tx, &t, &txb, height).await?;
         txindex += 1
     }
     let on_conflict = if with_index {
@@ -120,12 +192,103 @@ pub async fn persist(
 
     tx.commit().await?;
 
-    println!(
-        "saved block {}: {} transactions at {} took {:?}",
-        hash,
-        block.stats.txs,
-        block.stats.height,
-        start.elapsed(),
+    tracing::info!(
+        hash = %hash,
+        txs = block.stats.txs,
+        height = block.stats.height,
+        took_ms = start.elapsed().as_millis() as u64,
+        "saved block"
     );
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testdb::TestDb;
+
+    // output of the coinbase of block 9 spent by the second transaction of block 170
+    const COINBASE_9: &str = "0437cd7f8525ceed2324359c2d0ba26006d92d856a9c20fa0241106ee5a597c9";
+    const TX_SPEND_170: &str = "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16";
+
+    fn fixture<T: serde::de::DeserializeOwned>(name: &str) -> T {
+        let path = format!(
+            "{}/../types/tests/fixtures/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            name
+        );
+        let text = std::fs::read_to_string(&path).unwrap();
+        let reply: serde_json::Value = serde_json::from_str(&text).unwrap();
+        serde_json::from_value(reply["result"].clone()).unwrap()
+    }
+
+    // txhash, vout, blockheight, txindex, scripthash, script, value
+    type OutputRow = (Vec<u8>, i32, i32, i32, Vec<u8>, Vec<u8>, i64);
+
+    #[test]
+    fn outputs_and_spends_are_indexed() {
+        let db = match TestDb::create() {
+            Some(x) => x,
+            None => return,
+        };
+        db.seed(&format!(
+            "INSERT INTO final_outputs (txhash, vout, blockheight, txindex, value) \
+                VALUES (decode('{}', 'hex'), 0, 9, 0, 5000000000)",
+            COINBASE_9
+        ));
+        let block = btc::BlockInfoCombined {
+            info: fixture("getblock_170.json"),
+            stats: fixture("getblockstats_170.json"),
+        };
+        let (outputs, spent) = async_std::task::block_on(async {
+            let mut conn = db.pool.acquire().await.unwrap();
+            persist(&mut conn, &block, false).await.unwrap();
+            let outputs: Vec<OutputRow> = sqlx::query_as(
+                "SELECT txhash, vout, blockheight, txindex, scripthash, script, value \
+                    FROM final_outputs WHERE blockheight = 170 ORDER BY txindex, vout",
+            )
+            .fetch_all(&mut conn)
+            .await
+            .unwrap();
+            let spent: (Option<Vec<u8>>, Option<i32>, Option<i32>) = sqlx::query_as(
+                "SELECT spent_txhash, spent_vin, spent_height FROM final_outputs \
+                    WHERE txhash = $1 AND vout = 0",
+            )
+            .bind(hex::decode(COINBASE_9).unwrap())
+            .fetch_one(&mut conn)
+            .await
+            .unwrap();
+            (outputs, spent)
+        });
+
+        let expected: Vec<OutputRow> = block
+            .info
+            .tx
+            .iter()
+            .enumerate()
+            .flat_map(|(txindex, t)| {
+                let txhash = hex::decode(t.txid.as_ref().unwrap()).unwrap();
+                t.vout.iter().map(move |out| {
+                    let script = hex::decode(&out.script_pub_key.hex).unwrap();
+                    let scripthash = Sha256::digest(&script).to_vec();
+                    (
+                        txhash.clone(),
+                        out.n as i32,
+                        170,
+                        txindex as i32,
+                        scripthash,
+                        script,
+                        out.sats(),
+                    )
+                })
+            })
+            .collect();
+        assert_eq!(outputs.len(), 3);
+        assert_eq!(outputs, expected);
+        assert_eq!(outputs[2].6, 4_000_000_000);
+        assert_eq!(
+            spent,
+            (Some(hex::decode(TX_SPEND_170).unwrap()), Some(0), Some(170))
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use explorer_types::RpcResponse;
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 use ureq::{Agent, AgentBuilder};
@@ -6,155 +7,39 @@ pub trait BlockchainClient {
     fn get_chain_info(&self) -> anyhow::Result<ChainInfo>;
     fn get_block(&self, hash: &str) -> anyhow::Result<BlockInfoCombined>;
     fn get_block_hash(&self, height: u32) -> anyhow::Result<String>;
+    /// blocks at heights `from..from + count`, fetched in two batched calls
+    fn get_blocks(&self, from: u32, count: u32) -> anyhow::Result<Vec<BlockInfoCombined>>;
 }
 
-struct Client {
-    address: String,
-    username: String,
-    password: String,
+/// Credentials of the node
+#[derive(Clone, Debug)]
+pub enum Auth {
+    UserPass(String, String),
+    /// `.cookie` file of the node, read on every request
+    /// as the node writes the new one when it is restarted
+    Cookie(String),
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct ChainInfo {
-    pub chain: String,
-    pub blocks: u32,
-    pub headers: u32,
-    pub bestblockhash: String,
-    pub difficulty: f64,
-    pub mediantime: u64,
-    pub verificationprogress: f64,
-    pub initialblockdownload: bool,
-    pub chainwork: String,
-    pub size_on_disk: u64,
-    pub pruned: bool,
-}
-
-#[derive(Clone, Debug, Deserialize)]
-struct ChainInfoResponse {
-    result: ChainInfo,
-}
-
-#[derive(Clone, Debug, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct TxScriptSig {
-    pub asm: String,
-    pub hex: String,
-}
-
-#[derive(Clone, Debug, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct TxScriptPubKey {
-    pub asm: String,
-    pub hex: String,
-    pub req_sigs: Option<u32>,
-    #[serde(rename = "type")]
-    pub script_type: String, // witness_v0_keyhash, witness_v0_scripthash, pubkeyhash, nulldata
-    pub addresses: Option<Vec<String>>,
-}
-
-#[derive(Clone, Debug, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct BlockTxVin {
-    pub txid: Option<String>,
-    pub vout: Option<u32>,
-    pub script_sig: Option<TxScriptSig>,
-    pub txinwitness: Option<Vec<String>>,
-    pub sequence: u64,
-}
-
-#[derive(Clone, Debug, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct BlockTxVout {
-    pub value: f32,
-    pub n: u32,
-    pub script_pub_key: TxScriptPubKey,
-}
-
-#[derive(Clone, Debug, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct BlockTransaction {
-    pub txid: Option<String>,
-    pub hash: String,
-    pub version: u32,
-    pub size: u32,
-    pub vsize: u32,
-    pub weight: u32,
-    pub locktime: u32,
-    pub vin: Vec<BlockTxVin>,
-    pub vout: Vec<BlockTxVout>,
-    pub hex: String,
-}
-
-#[derive(Clone, Debug, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct BlockInfo {
-    pub hash: String,
-    pub confirmations: i32,
-    pub strippedsize: u64,
-    pub size: i64,
-    pub weight: u64,
-    pub height: u32,
-    pub version: u64,
-    pub version_hex: String,
-    pub merkleroot: String,
-    pub tx: Vec<BlockTransaction>,
-    pub time: i64,
-    pub mediantime: i64,
-    pub nonce: u64,
-    pub bits: String,
-    pub difficulty: f64,
-    pub chainwork: String,
-    pub n_tx: u32,
-    pub previousblockhash: String,
-    pub nextblockhash: Option<String>,
-}
-
-#[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct BlockStatsInfo {
-    pub avgfee: u32,
-    pub avgfeerate: u32,
-    pub avgtxsize: u64,
-    pub blockhash: String,
-    pub feerate_percentiles: Vec<u32>,
-    pub height: u32,
-    pub ins: u32,
-    pub maxfee: u64,
-    pub maxfeerate: u64,
-    pub maxtxsize: u32,
-    pub medianfee: u32,
-    pub mediantime: u64,
-    pub mediantxsize: u32,
-    pub minfee: u64,
-    pub minfeerate: u64,
-    pub mintxsize: u32,
-    pub outs: u32,
-    pub subsidy: u64,
-    pub swtotal_size: u32,
-    pub swtotal_weight: u64,
-    pub swtxs: u32,
-    pub time: u64,
-    pub total_out: u64,
-    pub total_size: u64,
-    pub total_weight: u64,
-    pub totalfee: u64,
-    pub txs: u32,
-    pub utxo_increase: i32,
-    pub utxo_size_inc: i32,
+struct Client {
+    address: String,
+    auth: Auth,
 }
 
-#[derive(Clone, Debug, Deserialize)]
-struct BlockStatsResponse {
-    result: BlockStatsInfo,
-}
+pub use explorer_types::{
+    BlockInfo, BlockStatsInfo, BlockTransaction, BlockTxVin, BlockTxVout, ChainInfo,
+    TxScriptPubKey, TxScriptSig,
+};
 
-#[derive(Clone, Debug, Deserialize)]
-struct StringResultResponse {
-    result: String,
-}
+type ChainInfoResponse = RpcResponse<ChainInfo>;
+type BlockStatsResponse = RpcResponse<BlockStatsInfo>;
+type StringResultResponse = RpcResponse<String>;
+type BlockInfoResponse = RpcResponse<BlockInfo>;
 
 #[derive(Clone, Debug, Deserialize)]
-struct BlockInfoResponse {
-    result: BlockInfo,
+struct BatchReply {
+    id: u64,
+    result: Option<serde_json::Value>,
+    error: Option<serde_json::Value>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -163,18 +48,69 @@ pub struct BlockInfoCombined {
     pub stats: BlockStatsInfo,
 }
 
+impl Client {
+    /// value of `Authorization` header
+    fn authorization(&self) -> anyhow::Result<String> {
+        let auth_token = match &self.auth {
+            Auth::UserPass(user, pass) => format!("{}:{}", user, pass),
+            Auth::Cookie(path) => std::fs::read_to_string(path)
+                .map_err(|e| anyhow::anyhow!("cannot read cookie {}: {}", path, e))?
+                .trim()
+                .to_string(),
+        };
+        Ok(format!("Basic {}", base64::encode(auth_token)))
+    }
+
+    /// sends JSON-RPC batch, results are returned in the order of calls
+    fn batch(&self, calls: &[(&str, serde_json::Value)]) -> anyhow::Result<Vec<serde_json::Value>> {
+        let agent: Agent = AgentBuilder::new()
+            .timeout_read(Duration::from_secs(30))
+            .build();
+        let auth_hdr = self.authorization()?;
+        let payload: Vec<serde_json::Value> = calls
+            .iter()
+            .enumerate()
+            .map(|(id, (method, params))| {
+                serde_json::json!({"jsonrpc": "1.0", "id": id, "method": method, "params": params})
+            })
+            .collect();
+        let mut replies: Vec<BatchReply> = agent
+            .post(self.address.as_str())
+            .set("Authorization", auth_hdr.as_str())
+            .set("Content-Type", "application/json")
+            .send_string(serde_json::Value::Array(payload).to_string().as_str())?
+            .into_json()?;
+        if replies.len() != calls.len() {
+            return Err(anyhow::anyhow!(
+                "node replied to {} of {} batched calls",
+                replies.len(),
+                calls.len()
+            ));
+        }
+        replies.sort_by_key(|r| r.id);
+        let mut out = Vec::with_capacity(replies.len());
+        for (reply, (method, params)) in replies.into_iter().zip(calls.iter()) {
+            match (reply.result, reply.error) {
+                (Some(result), None) => out.push(result),
+                (_, e) => return Err(anyhow::anyhow!("{} {} failed: {:?}", method, params, e)),
+            }
+        }
+        Ok(out)
+    }
+}
+
 impl BlockchainClient for Client {
     fn get_chain_info(&self) -> anyhow::Result<ChainInfo> {
         let agent: Agent = AgentBuilder::new()
             .timeout_read(Duration::from_secs(5))
             .build();
-        let auth_token = format!("{}:{}", self.username.as_str(), self.password.as_str());
+        let auth_hdr = self.authorization()?;
         let result: ChainInfoResponse = agent.post(self.address.as_str())
-            .set("Authorization", format!("Basic {}", base64::encode(auth_token)).as_str())
+            .set("Authorization", auth_hdr.as_str())
             .set("Content-Type", "application/json")
             .send_string("{\"jsonrpc\":\"1.0\",\"id\":\"i0\",\"method\":\"getblockchaininfo\",\"params\":[]}")?
             .into_json()?;
-        println!("{:?}", result);
+        tracing::debug!("{:?}", result);
         Ok(result.result)
     }
 
@@ -182,13 +118,11 @@ impl BlockchainClient for Client {
         let agent: Agent = AgentBuilder::new()
             .timeout_read(Duration::from_secs(5))
             .build();
-        let auth_token = format!("{}:{}", self.username.as_str(), self.password.as_str());
-        let auth_hdr = format!("Basic {}", base64::encode(auth_token));
+        let auth_hdr = self.authorization()?;
         let payload = format!(
             "{{\"jsonrpc\":\"1.0\",\"id\":\"h{}\",\"method\":\"getblockhash\",\"params\":[{}]}}",
             height, height,
         );
-        // println!("{}", payload);
         let result: StringResultResponse = agent
             .post(self.address.as_str())
             .set("Authorization", auth_hdr.as_str())
@@ -202,8 +136,7 @@ impl BlockchainClient for Client {
         let agent: Agent = AgentBuilder::new()
             .timeout_read(Duration::from_secs(5))
             .build();
-        let auth_token = format!("{}:{}", self.username.as_str(), self.password.as_str());
-        let auth_hdr = format!("Basic {}", base64::encode(auth_token));
+        let auth_hdr = self.authorization()?;
 
         let payload1 = format!(
             "{{\"jsonrpc\":\"1.0\",\"id\":\"{}\",\"method\":\"getblockstats\",\"params\":[\"{}\"]}}",
@@ -219,8 +152,8 @@ impl BlockchainClient for Client {
         let stats: BlockStatsResponse = match serde_json::from_str(stats_str.as_str()) {
             Ok(x) => x,
             Err(e) => {
-                println!("REQUEST >> {}", payload1);
-                println!("RESPONSE {}", stats_str);
+                tracing::info!("REQUEST >> {}", payload1);
+                tracing::info!("RESPONSE {}", stats_str);
                 return Err(anyhow::Error::from(e));
             }
         };
@@ -240,12 +173,36 @@ impl BlockchainClient for Client {
             info: info.result,
         })
     }
+
+    fn get_blocks(&self, from: u32, count: u32) -> anyhow::Result<Vec<BlockInfoCombined>> {
+        let calls: Vec<(&str, serde_json::Value)> = (from..from + count)
+            .map(|height| ("getblockhash", serde_json::json!([height])))
+            .collect();
+        let hashes = self.batch(&calls)?;
+
+        let mut calls: Vec<(&str, serde_json::Value)> = vec![];
+        for hash in hashes {
+            calls.push(("getblockstats", serde_json::json!([hash])));
+            calls.push(("getblock", serde_json::json!([hash, 2])));
+        }
+        let mut replies = self.batch(&calls)?.into_iter();
+        let mut out = vec![];
+        while let Some(stats) = replies.next() {
+            let info = replies
+                .next()
+                .ok_or_else(|| anyhow::anyhow!("no getblock reply for block stats"))?;
+            out.push(BlockInfoCombined {
+                stats: serde_json::from_value(stats)?,
+                info: serde_json::from_value(info)?,
+            });
+        }
+        Ok(out)
+    }
 }
 
-pub fn new() -> Box<dyn BlockchainClient> {
+pub fn new(address: &str, auth: Auth) -> Box<dyn BlockchainClient> {
     Box::new(Client {
-        address: "http://localhost:8332/".to_owned(),
-        username: "bitcoinrpc".to_owned(),
-        password: "bitcoinpass".to_owned(),
+        address: address.to_owned(),
+        auth,
     })
 }
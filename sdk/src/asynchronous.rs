@@ -0,0 +1,120 @@
+use crate::blocking::{Client, PageResult};
+use crate::error::Result;
+use crate::types::{self, Page};
+use async_std::task::spawn_blocking;
+use bitcoincore_rpc_json as json;
+use futures::stream::{self, Stream, StreamExt};
+use std::collections::BTreeMap;
+
+/// Async client of the explorer HTTP API.
+///
+/// Requests are executed by the blocking client on the async-std blocking pool,
+/// so the caller's executor is never stalled.
+#[derive(Clone, Debug)]
+pub struct AsyncClient {
+    inner: Client,
+}
+
+impl AsyncClient {
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            inner: Client::new(base_url),
+        }
+    }
+
+    pub fn from_blocking(inner: Client) -> Self {
+        Self { inner }
+    }
+
+    pub async fn home(&self) -> Result<types::Home> {
+        let c = self.inner.clone();
+        spawn_blocking(move || c.home()).await
+    }
+
+    pub async fn blocks(&self, page: Page) -> Result<types::BlocksList> {
+        let c = self.inner.clone();
+        spawn_blocking(move || c.blocks(&page)).await
+    }
+
+    /// stream of all blocks down from the tip, fetching `limit` blocks per request
+    pub fn blocks_stream(&self, limit: u32) -> impl Stream<Item = Result<types::Block>> {
+        let c = self.clone();
+        paginated(Page::new(limit), move |pg| {
+            let c = c.clone();
+            async move {
+                let out = c.blocks(pg).await?;
+                Ok((out.list, out.pager.and_then(|p| p.from)))
+            }
+        })
+    }
+
+    pub async fn block(&self, hash: &str) -> Result<types::BlockDetails> {
+        let (c, hash) = (self.inner.clone(), hash.to_string());
+        spawn_blocking(move || c.block(hash.as_str())).await
+    }
+
+    pub async fn tx(&self, txid: &str) -> Result<types::TxDetail> {
+        let (c, txid) = (self.inner.clone(), txid.to_string());
+        spawn_blocking(move || c.tx(txid.as_str())).await
+    }
+
+    pub async fn address(&self, address: &str, page: Page) -> Result<types::TxList> {
+        let (c, address) = (self.inner.clone(), address.to_string());
+        spawn_blocking(move || c.address(address.as_str(), &page)).await
+    }
+
+    /// stream of the whole history of the address
+    pub fn address_stream(
+        &self,
+        address: &str,
+        limit: u32,
+    ) -> impl Stream<Item = Result<json::GetRawTransactionResult>> {
+        let (c, address) = (self.clone(), address.to_string());
+        paginated(Page::new(limit), move |pg| {
+            let (c, address) = (c.clone(), address.clone());
+            async move {
+                let out = c.address(address.as_str(), pg).await?;
+                Ok((out.list, out.pager.and_then(|p| p.from)))
+            }
+        })
+    }
+
+    pub async fn broadcast(&self, hex: &str) -> Result<explorer_types::Broadcast> {
+        let (c, hex) = (self.inner.clone(), hex.to_string());
+        spawn_blocking(move || c.broadcast(hex.as_str())).await
+    }
+
+    pub async fn decode(&self, tx: &str) -> Result<types::DecodedTx> {
+        let (c, tx) = (self.inner.clone(), tx.to_string());
+        spawn_blocking(move || c.decode(tx.as_str())).await
+    }
+
+    pub async fn search(&self, query: &str) -> Result<BTreeMap<String, String>> {
+        let (c, query) = (self.inner.clone(), query.to_string());
+        spawn_blocking(move || c.search(query.as_str())).await
+    }
+}
+
+/// flattens pages into the stream of items, following `pager.from` cursor
+fn paginated<T, F, Fut>(first: Page, fetch: F) -> impl Stream<Item = Result<T>>
+where
+    F: FnMut(Page) -> Fut,
+    Fut: std::future::Future<Output = PageResult<T>>,
+{
+    stream::unfold((Some(first), fetch), |(next, mut fetch)| async move {
+        let page = next?;
+        let limit = page.limit;
+        let (items, next): (Vec<Result<T>>, Option<Page>) = match fetch(page).await {
+            Ok((list, from)) => (
+                list.into_iter().map(Ok).collect(),
+                from.map(|from| Page {
+                    from: Some(from),
+                    limit,
+                }),
+            ),
+            Err(e) => (vec![Err(e)], None),
+        };
+        Some((stream::iter(items), (next, fetch)))
+    })
+    .flatten()
+}
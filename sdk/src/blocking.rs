@@ -0,0 +1,278 @@
+use crate::error::{Error, Result};
+use crate::types::{self, response, Page};
+use bitcoincore_rpc_json as json;
+use explorer_types::{ErrorResponse, Network};
+use serde::de::DeserializeOwned;
+use std::collections::{BTreeMap, VecDeque};
+use std::time::Duration;
+use ureq::{Agent, AgentBuilder};
+
+/// Blocking client of the explorer HTTP API
+#[derive(Clone, Debug)]
+pub struct Client {
+    base_url: String,
+    agent: Agent,
+    api_key: Option<String>,
+    network: Option<Network>,
+}
+
+impl Client {
+    /// `base_url` is the root of the server, i.e. `http://localhost:8000`
+    pub fn new(base_url: &str) -> Self {
+        let agent: Agent = AgentBuilder::new()
+            .timeout_read(Duration::from_secs(30))
+            .build();
+        Self::with_agent(base_url, agent)
+    }
+
+    pub fn with_agent(base_url: &str, agent: Agent) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            agent,
+            api_key: None,
+            network: None,
+        }
+    }
+
+    /// sends the key with every request, for the rate limit of its tier
+    pub fn with_api_key(mut self, api_key: &str) -> Self {
+        self.api_key = Some(api_key.to_string());
+        self
+    }
+
+    /// calls the API of the network under `/api/{network}/`,
+    /// instead of the primary network of the server
+    pub fn with_network(mut self, network: Network) -> Self {
+        self.network = Some(network);
+        self
+    }
+
+    /// URL of the endpoint, `path` is relative to the root of the primary network
+    fn url(&self, path: &str) -> String {
+        let network = match self.network {
+            Some(x) => x,
+            None => return format!("{}{}", self.base_url, path),
+        };
+        // chain summary `/` is at the root of the network
+        let rest = path.strip_prefix("/api").unwrap_or("");
+        format!("{}/api/{}{}", self.base_url, network, rest)
+    }
+
+    fn authorize(&self, req: ureq::Request) -> ureq::Request {
+        match &self.api_key {
+            Some(key) => req.set("X-API-Key", key.as_str()),
+            None => req,
+        }
+    }
+
+    fn send(&self, req: ureq::Request) -> Result<(u16, String)> {
+        let res = match self.authorize(req).call() {
+            Ok(res) => res,
+            Err(ureq::Error::Status(_, res)) => res,
+            Err(e) => return Err(Error::Transport(e.to_string())),
+        };
+        let status = res.status();
+        Ok((status, res.into_string()?))
+    }
+
+    fn get(&self, path: &str, page: Option<&Page>) -> Result<(u16, String)> {
+        let mut req = self.agent.get(self.url(path).as_str());
+        if let Some(pg) = page {
+            if let Some(from) = &pg.from {
+                req = req.query("from", from.as_str());
+            }
+            req = req.query("limit", pg.limit.to_string().as_str());
+        }
+        self.send(req)
+    }
+
+    /// decodes reply of the endpoint that is not wrapped into `response::*` enum
+    fn plain<T: DeserializeOwned>(status: u16, body: &str) -> Result<T> {
+        if status >= 400 {
+            return match serde_json::from_str::<ErrorResponse>(body) {
+                Ok(e) => Err(e.error.into()),
+                Err(_) => Err(Error::Http {
+                    status,
+                    message: body.to_string(),
+                }),
+            };
+        }
+        Ok(serde_json::from_str(body)?)
+    }
+
+    /// reply did not match the expected shape, it might still be an error envelope
+    fn undecodable(status: u16, body: &str, e: serde_json::Error) -> Error {
+        match Self::plain::<serde_json::Value>(status, body) {
+            Err(api) => api,
+            Ok(_) => e.into(),
+        }
+    }
+
+    /// `GET /`
+    pub fn home(&self) -> Result<types::Home> {
+        let (status, body) = self.get("/", None)?;
+        Self::plain(status, body.as_str())
+    }
+
+    /// `GET /api/blocks`, latest blocks first
+    pub fn blocks(&self, page: &Page) -> Result<types::BlocksList> {
+        let (status, body) = self.get("/api/blocks", Some(page))?;
+        match serde_json::from_str(body.as_str()) {
+            Ok(response::BlockList::Blocks(x)) => Ok(x),
+            Ok(response::BlockList::Failure(e)) => Err(e.into()),
+            Err(e) => Err(Self::undecodable(status, body.as_str(), e)),
+        }
+    }
+
+    /// iterates over all blocks down from the tip, fetching `limit` blocks per request
+    pub fn blocks_iter(
+        &self,
+        limit: u32,
+    ) -> Paginated<types::Block, impl FnMut(&Page) -> PageResult<types::Block> + '_> {
+        Paginated::new(Page::new(limit), move |pg| {
+            let out = self.blocks(pg)?;
+            Ok((out.list, out.pager.and_then(|p| p.from)))
+        })
+    }
+
+    /// `GET /api/blocks/:block`
+    pub fn block(&self, hash: &str) -> Result<types::BlockDetails> {
+        let (status, body) = self.get(format!("/api/blocks/{}", hash).as_str(), None)?;
+        match serde_json::from_str(body.as_str()) {
+            Ok(response::Block::Block(x)) => Ok(x),
+            Ok(response::Block::Failure(e)) => Err(e.into()),
+            Err(e) => Err(Self::undecodable(status, body.as_str(), e)),
+        }
+    }
+
+    /// `GET /api/tx/:tx`
+    pub fn tx(&self, txid: &str) -> Result<types::TxDetail> {
+        let (status, body) = self.get(format!("/api/tx/{}", txid).as_str(), None)?;
+        match serde_json::from_str(body.as_str()) {
+            Ok(response::Tx::Tx(x)) => Ok(x),
+            Ok(response::Tx::Failure(e)) => Err(e.into()),
+            Err(e) => Err(Self::undecodable(status, body.as_str(), e)),
+        }
+    }
+
+    /// `GET /api/address/:address`
+    pub fn address(&self, address: &str, page: &Page) -> Result<types::TxList> {
+        let path = format!("/api/address/{}", address);
+        let (status, body) = self.get(path.as_str(), Some(page))?;
+        match serde_json::from_str(body.as_str()) {
+            Ok(response::Address::Tx(x)) => Ok(x),
+            Ok(response::Address::Failure(e)) => Err(e.into()),
+            Err(e) => Err(Self::undecodable(status, body.as_str(), e)),
+        }
+    }
+
+    /// iterates over the whole history of the address
+    pub fn address_iter<'a>(
+        &'a self,
+        address: &'a str,
+        limit: u32,
+    ) -> Paginated<
+        json::GetRawTransactionResult,
+        impl FnMut(&Page) -> PageResult<json::GetRawTransactionResult> + 'a,
+    > {
+        Paginated::new(Page::new(limit), move |pg| {
+            let out = self.address(address, pg)?;
+            Ok((out.list, out.pager.and_then(|p| p.from)))
+        })
+    }
+
+    /// `POST /api/tx`, relays the raw transaction given as hex
+    pub fn broadcast(&self, hex: &str) -> Result<explorer_types::Broadcast> {
+        let req = self.agent.post(self.url("/api/tx").as_str());
+        let res = match self.authorize(req).send_string(hex) {
+            Ok(res) => res,
+            Err(ureq::Error::Status(_, res)) => res,
+            Err(e) => return Err(Error::Transport(e.to_string())),
+        };
+        let status = res.status();
+        let body = res.into_string()?;
+        match serde_json::from_str(body.as_str()) {
+            Ok(response::Broadcast::Sent(x)) => Ok(x),
+            Ok(response::Broadcast::Rejected(x)) => Err(Error::Rejected(x)),
+            Ok(response::Broadcast::Failure(e)) => Err(e.into()),
+            Err(e) => Err(Self::undecodable(status, body.as_str(), e)),
+        }
+    }
+
+    /// `POST /api/decode`, decodes the raw transaction given as hex or PSBT as base64
+    pub fn decode(&self, tx: &str) -> Result<types::DecodedTx> {
+        let req = self.agent.post(self.url("/api/decode").as_str());
+        let res = match self.authorize(req).send_string(tx) {
+            Ok(res) => res,
+            Err(ureq::Error::Status(_, res)) => res,
+            Err(e) => return Err(Error::Transport(e.to_string())),
+        };
+        let status = res.status();
+        let body = res.into_string()?;
+        match serde_json::from_str(body.as_str()) {
+            Ok(response::Decode::Tx(x)) => Ok(x),
+            Ok(response::Decode::Failure(e)) => Err(e.into()),
+            Err(e) => Err(Self::undecodable(status, body.as_str(), e)),
+        }
+    }
+
+    /// `POST /api/search`
+    pub fn search(&self, query: &str) -> Result<BTreeMap<String, String>> {
+        let req = self.agent.post(self.url("/api/search").as_str());
+        let res = match self.authorize(req).send_string(query) {
+            Ok(res) => res,
+            Err(ureq::Error::Status(_, res)) => res,
+            Err(e) => return Err(Error::Transport(e.to_string())),
+        };
+        let status = res.status();
+        Self::plain(status, res.into_string()?.as_str())
+    }
+}
+
+/// items of the single page and the cursor of the next one
+pub type PageResult<T> = Result<(Vec<T>, Option<String>)>;
+
+/// Iterator over items of the paginated endpoint,
+/// following `pager.from` cursor until the last page
+pub struct Paginated<T, F> {
+    fetch: F,
+    next: Option<Page>,
+    buf: VecDeque<T>,
+}
+
+impl<T, F> Paginated<T, F>
+where
+    F: FnMut(&Page) -> PageResult<T>,
+{
+    pub fn new(first: Page, fetch: F) -> Self {
+        Self {
+            fetch,
+            next: Some(first),
+            buf: VecDeque::new(),
+        }
+    }
+}
+
+impl<T, F> Iterator for Paginated<T, F>
+where
+    F: FnMut(&Page) -> PageResult<T>,
+{
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.buf.is_empty() {
+            let page = self.next.take()?;
+            match (self.fetch)(&page) {
+                Ok((list, from)) => {
+                    self.next = from.map(|from| Page {
+                        from: Some(from),
+                        limit: page.limit,
+                    });
+                    self.buf.extend(list);
+                }
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        self.buf.pop_front().map(Ok)
+    }
+}
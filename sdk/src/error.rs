@@ -0,0 +1,95 @@
+use explorer_types::{ApiError, ErrorCode, Rejection};
+use std::fmt;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Clone, Debug)]
+pub enum Error {
+    /// block, transaction or endpoint does not exist
+    NotFound(String),
+    /// request parameters were rejected by the server
+    InvalidParam(String),
+    /// server could not reach the bitcoin node
+    UpstreamUnavailable(String),
+    /// data is not in the index of the server (yet)
+    NotIndexed(String),
+    /// request was throttled by the server
+    RateLimited(String),
+    /// API key was not accepted by the server
+    Unauthorized(String),
+    /// server failed to handle the request
+    InternalError(String),
+    /// node refused the broadcast transaction
+    Rejected(Rejection),
+    /// server replied with an error that is not in the API envelope
+    Http { status: u16, message: String },
+    /// server could not be reached or the connection was broken
+    Transport(String),
+    /// reply could not be decoded into the expected type
+    Decode(String),
+}
+
+impl Error {
+    /// code of the API error, if the server replied with the error envelope
+    pub fn code(&self) -> Option<ErrorCode> {
+        match self {
+            Self::NotFound(_) => Some(ErrorCode::NotFound),
+            Self::InvalidParam(_) => Some(ErrorCode::InvalidParam),
+            Self::UpstreamUnavailable(_) => Some(ErrorCode::UpstreamUnavailable),
+            Self::NotIndexed(_) => Some(ErrorCode::NotIndexed),
+            Self::RateLimited(_) => Some(ErrorCode::RateLimited),
+            Self::Unauthorized(_) => Some(ErrorCode::Unauthorized),
+            Self::InternalError(_) => Some(ErrorCode::InternalError),
+            _ => None,
+        }
+    }
+}
+
+impl From<ApiError> for Error {
+    fn from(e: ApiError) -> Self {
+        match e.code {
+            ErrorCode::NotFound => Self::NotFound(e.message),
+            ErrorCode::InvalidParam => Self::InvalidParam(e.message),
+            ErrorCode::UpstreamUnavailable => Self::UpstreamUnavailable(e.message),
+            ErrorCode::NotIndexed => Self::NotIndexed(e.message),
+            ErrorCode::RateLimited => Self::RateLimited(e.message),
+            ErrorCode::Unauthorized => Self::Unauthorized(e.message),
+            ErrorCode::InternalError => Self::InternalError(e.message),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound(m) => write!(f, "{}: {}", ErrorCode::NotFound, m),
+            Self::InvalidParam(m) => write!(f, "{}: {}", ErrorCode::InvalidParam, m),
+            Self::UpstreamUnavailable(m) => write!(f, "{}: {}", ErrorCode::UpstreamUnavailable, m),
+            Self::NotIndexed(m) => write!(f, "{}: {}", ErrorCode::NotIndexed, m),
+            Self::RateLimited(m) => write!(f, "{}: {}", ErrorCode::RateLimited, m),
+            Self::Unauthorized(m) => write!(f, "{}: {}", ErrorCode::Unauthorized, m),
+            Self::InternalError(m) => write!(f, "{}: {}", ErrorCode::InternalError, m),
+            Self::Rejected(r) => match &r.details {
+                Some(details) => write!(f, "rejected: {} ({})", r.reason, details),
+                None => write!(f, "rejected: {}", r.reason),
+            },
+            Self::Http { status, message } => write!(f, "http error {}: {}", status, message),
+            Self::Transport(e) => write!(f, "transport error: {}", e),
+            Self::Decode(e) => write!(f, "decode error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Decode(e.to_string())
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Self::Transport(e.to_string())
+    }
+}
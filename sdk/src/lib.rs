@@ -0,0 +1,18 @@
+//! Typed client of the bitcoin-explorer HTTP API.
+//!
+//! ```no_run
+//! let client = explorer_sdk::Client::new("http://localhost:8000");
+//! for block in client.blocks_iter(20).take(100) {
+//!     println!("{}", block.unwrap().header.height);
+//! }
+//! ```
+
+pub mod asynchronous;
+pub mod blocking;
+pub mod error;
+pub mod types;
+
+pub use asynchronous::AsyncClient;
+pub use blocking::Client;
+pub use error::{Error, Result};
+pub use types::Page;
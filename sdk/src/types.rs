@@ -0,0 +1,126 @@
+use bitcoincore_rpc_json as json;
+use explorer_types::{pager, ApiError, Rejection};
+use serde::Deserialize;
+
+pub use explorer_types::{BlockHeader, BlockStatsInfo, BlockSummary, TxDetail};
+
+/// cursor of the paginated endpoints
+#[derive(Clone, Debug)]
+pub struct Page {
+    // token to start
+    pub from: Option<String>,
+    // limit of records
+    pub limit: u32,
+}
+
+impl Default for Page {
+    fn default() -> Self {
+        Self {
+            from: None,
+            limit: 20,
+        }
+    }
+}
+
+impl Page {
+    pub fn new(limit: u32) -> Self {
+        Self { from: None, limit }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Home {
+    pub app: String,
+    pub blocks: String,
+    pub difficulty: String,
+}
+
+/// transaction decoded by `POST /api/decode`
+#[derive(Clone, Debug, Deserialize)]
+pub struct DecodedTx {
+    #[serde(flatten)]
+    pub tx: TxDetail,
+    pub psbt: bool,
+}
+
+/// block of the list, the confirmations are left out of it by the server
+#[derive(Clone, Debug, Deserialize)]
+pub struct Block {
+    pub header: BlockHeader,
+    pub stats: BlockStatsInfo,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct BlocksList {
+    pub list: Vec<Block>,
+    pub pager: Option<pager::Output>,
+}
+
+/// the confirmations are left out of the block by the server
+#[derive(Clone, Debug, Deserialize)]
+pub struct BlockDetails {
+    pub block: BlockSummary,
+    pub stats: BlockStatsInfo,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct TxList {
+    pub list: Vec<json::GetRawTransactionResult>,
+    pub pager: Option<pager::Output>,
+}
+
+/// mirrors of `types::response` enums of the server
+#[allow(clippy::large_enum_variant)]
+pub(crate) mod response {
+    use super::*;
+
+    #[derive(Deserialize)]
+    pub enum BlockList {
+        #[serde(rename = "error")]
+        Failure(ApiError),
+        #[serde(rename = "blocks")]
+        Blocks(BlocksList),
+    }
+
+    #[derive(Deserialize)]
+    pub enum Block {
+        #[serde(rename = "error")]
+        Failure(ApiError),
+        #[serde(rename = "block")]
+        Block(BlockDetails),
+    }
+
+    #[derive(Deserialize)]
+    pub enum Tx {
+        #[serde(rename = "error")]
+        Failure(ApiError),
+        #[serde(rename = "tx")]
+        Tx(TxDetail),
+    }
+
+    #[derive(Deserialize)]
+    pub enum Address {
+        #[serde(rename = "error")]
+        Failure(ApiError),
+        #[serde(rename = "list")]
+        Tx(TxList),
+    }
+
+    #[derive(Deserialize)]
+    pub enum Broadcast {
+        #[serde(rename = "error")]
+        Failure(ApiError),
+        #[serde(rename = "rejected")]
+        Rejected(Rejection),
+        #[serde(rename = "tx")]
+        Sent(explorer_types::Broadcast),
+    }
+
+    #[derive(Deserialize)]
+    pub enum Decode {
+        #[serde(rename = "error")]
+        Failure(ApiError),
+        #[serde(rename = "tx")]
+        Tx(DecodedTx),
+    }
+}
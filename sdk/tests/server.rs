@@ -0,0 +1,171 @@
+//! SDK clients against the API server running in-process on a stand-in node
+
+#[path = "../../server/tests/common/mod.rs"]
+mod common;
+
+use common::{Server, BLOCK_170, TIP, TX_COINBASE_170, TX_SPEND_170};
+use explorer_sdk::{AsyncClient, Client, Error, Page};
+use explorer_types::ErrorCode;
+use serde_json::json;
+
+#[test]
+fn home() {
+    let server = Server::start();
+    let home = Client::new(&server.url).home().unwrap();
+    assert_eq!(home.app, "bitcoin-explorer");
+    assert_eq!(home.blocks, TIP.to_string());
+}
+
+#[test]
+fn blocks_pages() {
+    let server = Server::start();
+    let client = Client::new(&server.url);
+    let first = client.blocks(&Page::new(3)).unwrap();
+    let heights: Vec<u32> = first.list.iter().map(|b| b.header.height).collect();
+    assert_eq!(heights, vec![171, 170, 169]);
+    let from = first
+        .pager
+        .and_then(|p| p.from)
+        .expect("cursor of the next page");
+    let page = Page {
+        from: Some(from),
+        limit: 3,
+    };
+    let second = client.blocks(&page).unwrap();
+    assert_eq!(second.list[0].header.height, 168);
+}
+
+#[test]
+fn blocks_iter_follows_cursor() {
+    let server = Server::start();
+    let client = Client::new(&server.url);
+    let heights: Vec<u32> = client
+        .blocks_iter(2)
+        .take(5)
+        .map(|b| b.unwrap().header.height)
+        .collect();
+    assert_eq!(heights, vec![171, 170, 169, 168, 167]);
+}
+
+#[test]
+fn block_and_tx() {
+    let server = Server::start();
+    let client = Client::new(&server.url);
+    let block = client.block(BLOCK_170).unwrap();
+    assert_eq!(block.block.height, 170);
+    assert_eq!(block.block.tx.len(), 2);
+    assert_eq!(block.stats.height, 170);
+
+    let tx = client.tx(TX_SPEND_170).unwrap();
+    assert_eq!(tx.txid, TX_SPEND_170);
+    assert_eq!(tx.blockhash.as_deref(), Some(BLOCK_170));
+    assert_eq!(tx.blockheight, Some(170));
+    assert_eq!(tx.vout.len(), 2);
+    assert_eq!(tx.vout[0].value_sat, 1_000_000_000);
+
+    let coinbase = client.tx(TX_COINBASE_170).unwrap();
+    assert!(coinbase.vin[0].coinbase.is_some());
+}
+
+#[test]
+fn typed_errors() {
+    let server = Server::start();
+    let client = Client::new(&server.url);
+    let unknown = "00".repeat(32);
+    match client.tx(&unknown) {
+        Err(Error::NotFound(_)) => {}
+        x => panic!("unexpected {:?}", x),
+    }
+    match client.block("not-a-hash") {
+        Err(e) => assert_eq!(e.code(), Some(ErrorCode::InvalidParam)),
+        x => panic!("unexpected {:?}", x),
+    }
+    // nothing is served there
+    match Client::new("http://127.0.0.1:9").home() {
+        Err(Error::Transport(_)) => {}
+        x => panic!("unexpected {:?}", x),
+    }
+}
+
+#[test]
+fn broadcast_rejected() {
+    let server = Server::start();
+    server.node.on("testmempoolaccept", |_| {
+        Ok(json!([{
+            "txid": TX_SPEND_170,
+            "allowed": false,
+            "reject-reason": "missing-inputs",
+        }]))
+    });
+    let hex = common::fixture("getrawtransaction_f4184f.json")["hex"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    match Client::new(&server.url).broadcast(&hex) {
+        Err(Error::Rejected(x)) => {
+            assert_eq!(x.reason, "missing-inputs");
+            assert_eq!(x.txid.as_deref(), Some(TX_SPEND_170));
+        }
+        x => panic!("unexpected {:?}", x),
+    }
+    assert_eq!(server.node.calls("sendrawtransaction"), 0);
+}
+
+#[test]
+fn decode_hex() {
+    let server = Server::start();
+    let hex = common::fixture("getrawtransaction_f4184f.json")["hex"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let out = Client::new(&server.url).decode(&hex).unwrap();
+    assert!(!out.psbt);
+    assert_eq!(out.tx.txid, TX_SPEND_170);
+    assert_eq!(out.tx.vin.len(), 1);
+}
+
+#[test]
+fn async_client() {
+    let server = Server::start();
+    let client = AsyncClient::new(&server.url);
+    async_std::task::block_on(async {
+        let tx = client.tx(TX_SPEND_170).await.unwrap();
+        assert_eq!(tx.txid, TX_SPEND_170);
+        let blocks = client.blocks(Page::new(1)).await.unwrap();
+        assert_eq!(blocks.list.len(), 1);
+        match client.tx(&"00".repeat(32)).await {
+            Err(Error::NotFound(_)) => {}
+            x => panic!("unexpected {:?}", x),
+        }
+    });
+}
+
+#[test]
+fn limit_above_max() {
+    let server = Server::start();
+    let client = Client::new(&server.url);
+    match client.blocks(&Page::new(101)) {
+        Err(Error::InvalidParam(_)) => {}
+        x => panic!("unexpected {:?}", x),
+    }
+    assert_eq!(server.node.calls("getblockhash"), 0);
+}
+
+#[test]
+fn malformed_query() {
+    let server = Server::start();
+    let (status, body) = server.get("/api/blocks?limit=abc&from=100");
+    assert_eq!(status, 400, "{}", body);
+    assert_eq!(body["error"]["code"], "invalid_param");
+    assert_eq!(server.node.calls("getblockhash"), 0);
+}
+
+#[test]
+fn short_batch_reply() {
+    let server = Server::start();
+    server.node.drop_in_batch("getblockstats");
+    match Client::new(&server.url).blocks(&Page::new(3)) {
+        Err(Error::UpstreamUnavailable(_)) => {}
+        x => panic!("unexpected {:?}", x),
+    }
+}
@@ -1,10 +1,15 @@
+use crate::error::{self, respond, ApiError};
+use crate::httpcache::{with_freshness, Freshness};
+use crate::network;
 use crate::rpc;
+use crate::txdetail;
+use crate::types::response;
 use crate::State;
+use async_std::io::ReadExt;
 use bitcoin::hashes::hex::FromHex;
 // use chrono::prelude::*;
 // use chrono::{DateTime, Datelike, NaiveDate, Utc};
-use std::str::FromStr;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use tide::{Body, Request, Response, Result};
 
 use bitcoincore_rpc_json as json;
@@ -12,22 +17,40 @@ use json::bitcoin;
 
 pub async fn home(req: Request<State>) -> Result {
     let rpcclient = req.state().rpc_client.clone();
-    let chaininfo = rpc::get_blockchain_info(rpcclient.clone());
+    let chaininfo = match rpc::get_blockchain_info(rpcclient.clone()).await {
+        Ok(x) => x,
+        Err(e) => return error::failure(e),
+    };
     let mut m: BTreeMap<&str, String> = BTreeMap::new();
     m.insert("app", "bitcoin-explorer".to_owned());
+    m.insert("network", req.state().network.to_string());
     m.insert("blocks", format!("{}", chaininfo.blocks));
     m.insert("difficulty", format!("{}", chaininfo.difficulty));
-    let mut res = Response::new(200);
-    res.set_body(Body::from_json(&m)?);
-    Ok(res)
+    with_freshness(respond(200, &m), Freshness::default())
+}
+
+/// body of the request as text, refusing the one longer than `limit` bytes
+pub async fn body_text(
+    req: &mut Request<State>,
+    limit: u64,
+) -> std::result::Result<String, ApiError> {
+    let mut text = String::new();
+    req.take_body()
+        .take(limit + 1)
+        .read_to_string(&mut text)
+        .await
+        .map_err(|e| ApiError::invalid_param(format!("body reading error {}", e)))?;
+    if text.len() as u64 > limit {
+        return Err(ApiError::invalid_param(format!(
+            "body is larger than {} bytes",
+            limit
+        )));
+    }
+    Ok(text)
 }
 
 fn invalid_param(str: String) -> tide::Result {
-    let mut m: BTreeMap<&'static str, String> = BTreeMap::new();
-    m.insert("error", str.clone());
-    let mut res = Response::new(400);
-    res.set_body(tide::Body::from_json(&m)?);
-    Ok(res)
+    error::failure(ApiError::invalid_param(str))
 }
 
 pub async fn transaction(req: Request<State>) -> Result {
@@ -39,11 +62,20 @@ pub async fn transaction(req: Request<State>) -> Result {
         Ok(x) => x,
         Err(e) => return invalid_param(format!("tx param parsing error {}", e)),
     };
-    let rpcclient = req.state().rpc_client.clone();
-    let rpcresult = rpc::get_raw_transaction_info(rpcclient.clone(), tx);
-    let mut res = Response::new(200);
-    res.set_body(Body::from_json(&rpcresult)?);
-    Ok(res)
+    let state = req.state();
+    let (detail, confirmations) =
+        match rpc::get_raw_transaction_info(state.rpc_client.clone(), tx).await {
+            Ok(raw) => (
+                txdetail::build(state, &raw, HashMap::new()).await,
+                raw.confirmations,
+            ),
+            Err(e) => (Err(e), None),
+        };
+    let rpcresult = response::Tx::from(detail);
+    with_freshness(
+        respond(rpcresult.status(), &rpcresult),
+        rpcresult.freshness(confirmations),
+    )
 }
 
 pub async fn block(req: Request<State>) -> Result {
@@ -53,23 +85,25 @@ pub async fn block(req: Request<State>) -> Result {
     };
     let block = match bitcoin::BlockHash::from_hex(block_hash) {
         Ok(x) => x,
-        Err(e) => return invalid_param(format!("tx param parsing error {}", e)),
+        Err(e) => return invalid_param(format!("block param parsing error {}", e)),
     };
     let rpcclient = req.state().rpc_client.clone();
-    let pg = crate::pager::Input::from_request(req);
-    let rpcresult = rpc::get_block_info(rpcclient.clone(), block, pg);
-    let mut res = Response::new(200);
-    res.set_body(Body::from_json(&rpcresult)?);
-    Ok(res)
+    let pg = match crate::pager::Input::from_request(&req) {
+        Ok(x) => x,
+        Err(e) => return error::failure(e),
+    };
+    let rpcresult = response::Block::from(rpc::get_block_info(rpcclient.clone(), block, pg).await);
+    with_freshness(respond(rpcresult.status(), &rpcresult), rpcresult.freshness())
 }
 
 pub async fn blocks(req: Request<State>) -> Result {
     let rpcclient = req.state().rpc_client.clone();
-    let pg = crate::pager::Input::from_request(req);
-    let rpcresult = rpc::get_latest_blocks(rpcclient.clone(), pg);
-    let mut res = Response::new(if rpcresult.is_invalid() { 400 } else { 200 });
-    res.set_body(Body::from_json(&rpcresult)?);
-    Ok(res)
+    let pg = match crate::pager::Input::from_request(&req) {
+        Ok(x) => x,
+        Err(e) => return error::failure(e),
+    };
+    let rpcresult = response::BlockList::from(rpc::get_latest_blocks(rpcclient.clone(), pg.clone()).await);
+    with_freshness(respond(rpcresult.status(), &rpcresult), rpcresult.freshness(&pg))
 }
 
 pub async fn address(req: Request<State>) -> Result {
@@ -77,16 +111,17 @@ pub async fn address(req: Request<State>) -> Result {
         Ok(x) => x,
         Err(e) => return invalid_param(format!("missing address param {}", e)),
     };
-    let address = match bitcoin::Address::from_str(address_str) {
+    let address = match network::parse_address(req.state().network, address_str) {
         Ok(x) => x,
-        Err(e) => return invalid_param(format!("address param error {}", e)),
+        Err(e) => return error::failure(e),
     };
     let rpcclient = req.state().rpc_client.clone();
-    let pg = crate::pager::Input::from_request(req);
-    let rpcresult = rpc::get_address_history(rpcclient.clone(), address, pg);
-    let mut res = Response::new(if rpcresult.is_invalid() { 400 } else { 200 });
-    res.set_body(Body::from_json(&rpcresult)?);
-    Ok(res)
+    let pg = match crate::pager::Input::from_request(&req) {
+        Ok(x) => x,
+        Err(e) => return error::failure(e),
+    };
+    let rpcresult = response::Address::from(rpc::get_address_history(rpcclient.clone(), address, pg).await);
+    respond(rpcresult.status(), &rpcresult)
 }
 
 pub async fn search(req: Request<State>) -> Result {
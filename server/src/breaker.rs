@@ -0,0 +1,85 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+struct Inner {
+    // consecutive failures since the last success
+    failures: u32,
+    // moment when the circuit was opened
+    opened_at: Option<Instant>,
+}
+
+/// Circuit breaker of the node connection.
+///
+/// After `threshold` consecutive failures the circuit opens and calls fail
+/// immediately for `cooldown`. Then one call is let through: on success the
+/// circuit closes, on failure it opens for another `cooldown`.
+#[derive(Clone, Debug)]
+pub struct Breaker {
+    threshold: u32,
+    cooldown: Duration,
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl Default for Breaker {
+    fn default() -> Self {
+        Self::new(5, Duration::from_secs(30))
+    }
+}
+
+impl Breaker {
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            threshold,
+            cooldown,
+            inner: Arc::new(Mutex::new(Inner {
+                failures: 0,
+                opened_at: None,
+            })),
+        }
+    }
+
+    /// whether the call should be attempted, taking the single slot of the half-open circuit,
+    /// so it is to be asked only right before the call
+    pub fn allow(&self) -> bool {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        match inner.opened_at {
+            Some(at) if at.elapsed() < self.cooldown => false,
+            Some(_) => {
+                // half-open: let this call through, and keep others failing fast
+                inner.opened_at = Some(Instant::now());
+                true
+            }
+            None => true,
+        }
+    }
+
+    /// whether calls are failing fast, without taking the slot of the half-open circuit
+    pub fn is_open(&self) -> bool {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        matches!(inner.opened_at, Some(at) if at.elapsed() < self.cooldown)
+    }
+
+    /// whether the circuit has opened and not closed since, half-open included
+    pub fn is_tripped(&self) -> bool {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.opened_at.is_some()
+    }
+
+    pub fn success(&self) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.failures = 0;
+        inner.opened_at = None;
+    }
+
+    pub fn failure(&self) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.failures += 1;
+        if inner.failures >= self.threshold {
+            if inner.opened_at.is_none() {
+                tracing::warn!("node circuit opened after {} failures", inner.failures);
+            }
+            inner.opened_at = Some(Instant::now());
+        }
+    }
+}
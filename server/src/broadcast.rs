@@ -0,0 +1,149 @@
+//! `POST /api/tx`, relays the raw transaction to the network, i.e. to rebroadcast the stuck one.
+//!
+//! The transaction is checked with `testmempoolaccept` first, so the rejection reason
+//! is reported without sending anything. Every attempt is written to `broadcast_log`.
+
+use crate::api;
+use crate::error::{self, respond, ApiError, ErrorCode};
+use crate::network;
+use crate::ratelimit;
+use crate::rpc;
+use crate::telemetry;
+use crate::types::response;
+use crate::types::{Broadcast, Rejection};
+use crate::State;
+use bitcoin::hashes::hex::{FromHex, ToHex};
+use bitcoincore_rpc_json::bitcoin;
+use serde::Deserialize;
+use tide::http::Method;
+use tide::Request;
+use tracing_futures::Instrument;
+
+// the transaction is already in the mempool of the node, which is what a rebroadcast is for
+const IN_MEMPOOL: &str = "txn-already-in-mempool";
+
+/// JSON body of the request, the body can also be the plain hex
+#[derive(Deserialize)]
+struct Body {
+    hex: String,
+}
+
+/// whether the request is the broadcast, of the primary or the other network
+pub fn is_broadcast<S>(req: &Request<S>) -> bool {
+    if req.method() != Method::Post {
+        return false;
+    }
+    let path = req.url().path();
+    match network::strip(path) {
+        Some((_, rest)) => rest == "/tx",
+        None => path == "/api/tx",
+    }
+}
+
+/// raw transaction from the body, as lowercase hex
+async fn read_hex(req: &mut Request<State>, max_size: usize) -> Result<String, ApiError> {
+    // hex doubles the size, some room is left for JSON around it
+    let text = api::body_text(req, (max_size * 2 + 1024) as u64).await?;
+    let text = text.trim();
+    let hex = if text.starts_with('{') {
+        let body: Body = serde_json::from_str(text)
+            .map_err(|e| ApiError::invalid_param(format!("body parsing error {}", e)))?;
+        body.hex
+    } else {
+        text.to_string()
+    };
+    let raw = Vec::<u8>::from_hex(hex.trim())
+        .map_err(|e| ApiError::invalid_param(format!("transaction hex parsing error {}", e)))?;
+    if raw.is_empty() {
+        return Err(ApiError::invalid_param("transaction is empty"));
+    }
+    if raw.len() > max_size {
+        return Err(ApiError::invalid_param(format!(
+            "transaction is larger than {} bytes",
+            max_size
+        )));
+    }
+    Ok(raw.to_hex())
+}
+
+/// tests the transaction and relays it unless it is rejected
+pub async fn broadcast(state: &State, hex: &str) -> response::Broadcast {
+    let rpcclient = state.rpc_client.clone();
+    let test = match rpc::test_mempool_accept(rpcclient.clone(), hex).await {
+        Ok(x) => x,
+        Err(e) => return response::Broadcast::Failure(e),
+    };
+    let reason = test.reject_reason.clone().unwrap_or_default();
+    if !test.allowed && reason != IN_MEMPOOL {
+        return response::Broadcast::Rejected(Rejection {
+            txid: Some(test.txid),
+            reason,
+            details: test.reject_details,
+        });
+    }
+    match rpc::send_raw_transaction(rpcclient, hex).await {
+        Ok(txid) => response::Broadcast::Sent(Broadcast {
+            txid,
+            vsize: test.vsize,
+            fee: test.fees.map(|f| (f.base * 100_000_000.0).round() as u64),
+        }),
+        // the node has changed its mind since the test, i.e. an input was spent meanwhile
+        Err(e) if e.code == ErrorCode::InvalidParam => response::Broadcast::Rejected(Rejection {
+            txid: Some(test.txid),
+            reason: e.message,
+            details: None,
+        }),
+        Err(e) => response::Broadcast::Failure(e),
+    }
+}
+
+/// writes the attempt to the log and to `broadcast_log` table
+pub async fn audit(state: &State, client: &str, size: usize, out: &response::Broadcast) {
+    let (outcome, txid, reason) = match out {
+        response::Broadcast::Sent(x) => ("sent", Some(x.txid.clone()), None),
+        response::Broadcast::Rejected(x) => ("rejected", x.txid.clone(), Some(x.reason.clone())),
+        response::Broadcast::Failure(e) => ("failed", None, Some(e.to_string())),
+    };
+    tracing::info!(
+        target: "audit",
+        client = client,
+        network = %state.network,
+        txid = txid.as_deref().unwrap_or(""),
+        size = size as u64,
+        outcome = outcome,
+        reason = reason.as_deref().unwrap_or(""),
+        "transaction broadcast"
+    );
+    let sql = "INSERT INTO broadcast_log (client, network, txid, size, outcome, reason) \
+        VALUES ($1, $2, $3, $4, $5, $6)";
+    let res = sqlx::query(sql)
+        .bind(client)
+        .bind(state.network.name())
+        .bind(txid)
+        .bind(size as i32)
+        .bind(outcome)
+        .bind(reason)
+        .execute(&state.pool)
+        .instrument(telemetry::sql_span(sql))
+        .await;
+    // the transaction is already sent, only the log line remains
+    if let Err(e) = res {
+        tracing::warn!("broadcast audit failure: {}", e);
+    }
+}
+
+pub async fn handler(mut req: Request<State>) -> tide::Result {
+    let client = match req.ext::<ratelimit::Client>() {
+        Some(x) => x.0.clone(),
+        None => format!("ip:{}", req.peer_addr().unwrap_or("unknown")),
+    };
+    let max_size = req.state().broadcast_max_size;
+    let hex = match read_hex(&mut req, max_size).await {
+        Ok(x) => x,
+        Err(e) => return error::failure(e),
+    };
+    let state = req.state();
+    let out = broadcast(state, &hex).await;
+    audit(state, &client, hex.len() / 2, &out).await;
+    respond(out.status(), &out)
+}
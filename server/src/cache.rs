@@ -0,0 +1,362 @@
+use crate::error::ApiError;
+use crate::telemetry;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing_futures::Instrument;
+
+/// Blocks with at least this number of confirmations are not expected to change
+pub const DEEP_CONFIRMATIONS: u64 = 6;
+
+/// Kind of the cached entry, used for the metrics
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    ChainInfo,
+    Blocks,
+    Block,
+    BlockStats,
+    Tx,
+}
+
+impl Kind {
+    pub const ALL: [Kind; 5] = [
+        Kind::ChainInfo,
+        Kind::Blocks,
+        Kind::Block,
+        Kind::BlockStats,
+        Kind::Tx,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::ChainInfo => "chaininfo",
+            Self::Blocks => "blocks",
+            Self::Block => "block",
+            Self::BlockStats => "blockstats",
+            Self::Tx => "tx",
+        }
+    }
+}
+
+/// Storage of the serialized entries
+#[async_trait::async_trait]
+pub trait Backend: Send + Sync {
+    async fn get(&self, key: &str) -> Option<String>;
+    /// `volatile` entries describe the tip of the chain and are dropped on a new block
+    async fn set(&self, key: &str, value: String, ttl: Duration, volatile: bool);
+    async fn invalidate_volatile(&self);
+}
+
+struct MemoryEntry {
+    value: String,
+    expires: Instant,
+    volatile: bool,
+    // position in the LRU order
+    used: u64,
+}
+
+#[derive(Default)]
+struct MemoryInner {
+    entries: HashMap<String, MemoryEntry>,
+    // least recently used first
+    order: BTreeMap<u64, String>,
+    tick: u64,
+}
+
+/// In-process LRU storage, bounded by the number of entries
+pub struct Memory {
+    capacity: usize,
+    inner: Mutex<MemoryInner>,
+}
+
+impl Memory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inner: Mutex::new(MemoryInner::default()),
+        }
+    }
+}
+
+impl MemoryInner {
+    fn remove(&mut self, key: &str) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.order.remove(&entry.used);
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Backend for Memory {
+    async fn get(&self, key: &str) -> Option<String> {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.tick += 1;
+        let tick = inner.tick;
+        let (value, used) = match inner.entries.get_mut(key) {
+            Some(entry) if entry.expires > Instant::now() => {
+                let used = entry.used;
+                entry.used = tick;
+                (entry.value.clone(), used)
+            }
+            Some(_) => {
+                inner.remove(key);
+                return None;
+            }
+            None => return None,
+        };
+        inner.order.remove(&used);
+        inner.order.insert(tick, key.to_string());
+        Some(value)
+    }
+
+    async fn set(&self, key: &str, value: String, ttl: Duration, volatile: bool) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.remove(key);
+        while inner.entries.len() >= self.capacity {
+            let oldest = match inner.order.keys().next() {
+                Some(x) => *x,
+                None => break,
+            };
+            if let Some(k) = inner.order.remove(&oldest) {
+                inner.entries.remove(&k);
+            }
+        }
+        inner.tick += 1;
+        let used = inner.tick;
+        inner.order.insert(used, key.to_string());
+        inner.entries.insert(
+            key.to_string(),
+            MemoryEntry {
+                value,
+                expires: Instant::now() + ttl,
+                volatile,
+                used,
+            },
+        );
+    }
+
+    async fn invalidate_volatile(&self) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let keys: Vec<String> = inner
+            .entries
+            .iter()
+            .filter(|(_, v)| v.volatile)
+            .map(|(k, _)| k.clone())
+            .collect();
+        for k in keys {
+            inner.remove(&k);
+        }
+    }
+}
+
+const CACHE_GET: &str = "SELECT value FROM api_cache WHERE key = $1 AND expires_at > NOW()";
+const CACHE_SET: &str = "INSERT INTO api_cache (key, value, volatile, expires_at) \
+    VALUES ($1, $2, $3, NOW() + $4 * INTERVAL '1 second') \
+    ON CONFLICT (key) DO UPDATE SET \
+    value = EXCLUDED.value, volatile = EXCLUDED.volatile, expires_at = EXCLUDED.expires_at";
+const CACHE_INVALIDATE: &str = "DELETE FROM api_cache WHERE volatile OR expires_at < NOW()";
+
+/// Storage in the `api_cache` table, shared by all replicas of the server
+pub struct Postgres {
+    pool: sqlx::Pool<sqlx::postgres::Postgres>,
+}
+
+impl Postgres {
+    pub fn new(pool: sqlx::Pool<sqlx::postgres::Postgres>) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl Backend for Postgres {
+    async fn get(&self, key: &str) -> Option<String> {
+        let row: Option<(String,)> = sqlx::query_as(CACHE_GET)
+            .bind(key)
+            .fetch_optional(&self.pool)
+            .instrument(telemetry::sql_span(CACHE_GET))
+            .await
+            .unwrap_or_else(|e| {
+                tracing::warn!("shared cache read failure {}", e);
+                None
+            });
+        row.map(|x| x.0)
+    }
+
+    async fn set(&self, key: &str, value: String, ttl: Duration, volatile: bool) {
+        let res = sqlx::query(CACHE_SET)
+            .bind(key)
+            .bind(value)
+            .bind(volatile)
+            .bind(ttl.as_secs() as f64)
+            .execute(&self.pool)
+            .instrument(telemetry::sql_span(CACHE_SET))
+            .await;
+        if let Err(e) = res {
+            tracing::warn!("shared cache write failure {}", e);
+        }
+    }
+
+    async fn invalidate_volatile(&self) {
+        let res = sqlx::query(CACHE_INVALIDATE)
+            .execute(&self.pool)
+            .instrument(telemetry::sql_span(CACHE_INVALIDATE))
+            .await;
+        if let Err(e) = res {
+            tracing::warn!("shared cache invalidation failure {}", e);
+        }
+    }
+}
+
+/// How long the entries are kept
+#[derive(Clone, Debug)]
+pub struct Policy {
+    // entries about the tip of the chain
+    pub tip_ttl: Duration,
+    // entries about blocks with `DEEP_CONFIRMATIONS` and more
+    pub deep_ttl: Duration,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self {
+            tip_ttl: Duration::from_secs(10),
+            deep_ttl: Duration::from_secs(86400),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Counter {
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+/// Cache of the node replies: in-process LRU in front of the optional shared storage
+#[derive(Clone)]
+pub struct Cache {
+    policy: Policy,
+    local: Arc<Memory>,
+    shared: Option<Arc<dyn Backend>>,
+    counters: Arc<Vec<Counter>>,
+}
+
+impl std::fmt::Debug for Cache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Cache")
+            .field("policy", &self.policy)
+            .field("capacity", &self.local.capacity)
+            .field("shared", &self.shared.is_some())
+            .finish()
+    }
+}
+
+impl Default for Cache {
+    fn default() -> Self {
+        Self::new(10000, Policy::default())
+    }
+}
+
+impl Cache {
+    pub fn new(capacity: usize, policy: Policy) -> Self {
+        Self {
+            policy,
+            local: Arc::new(Memory::new(capacity)),
+            shared: None,
+            counters: Arc::new(Kind::ALL.iter().map(|_| Counter::default()).collect()),
+        }
+    }
+
+    pub fn with_shared(mut self, backend: Arc<dyn Backend>) -> Self {
+        self.shared = Some(backend);
+        self
+    }
+
+    fn counter(&self, kind: Kind) -> &Counter {
+        let pos = Kind::ALL.iter().position(|k| *k == kind).unwrap_or(0);
+        &self.counters[pos]
+    }
+
+    /// hits and misses per kind of entry
+    pub fn stats(&self) -> Vec<(&'static str, u64, u64)> {
+        Kind::ALL
+            .iter()
+            .map(|k| {
+                let c = self.counter(*k);
+                (
+                    k.name(),
+                    c.hits.load(Ordering::Relaxed),
+                    c.misses.load(Ordering::Relaxed),
+                )
+            })
+            .collect()
+    }
+
+    /// time to live of the entry, and whether it is volatile.
+    /// `None` confirmations stand for the data about the tip of the chain
+    pub fn ttl(&self, confirmations: Option<u64>) -> (Duration, bool) {
+        match confirmations {
+            Some(x) if x >= DEEP_CONFIRMATIONS => (self.policy.deep_ttl, false),
+            _ => (self.policy.tip_ttl, true),
+        }
+    }
+
+    async fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        if let Some(x) = self.local.get(key).await {
+            return serde_json::from_str(x.as_str()).ok();
+        }
+        let shared = self.shared.as_ref()?;
+        let x = shared.get(key).await?;
+        let value = serde_json::from_str(x.as_str()).ok()?;
+        // keep it locally for the rest of the tip lifetime
+        self.local.set(key, x, self.policy.tip_ttl, true).await;
+        Some(value)
+    }
+
+    async fn set<T: Serialize>(&self, key: &str, value: &T, confirmations: Option<u64>) {
+        let value = match serde_json::to_string(value) {
+            Ok(x) => x,
+            Err(_) => return,
+        };
+        let (ttl, volatile) = self.ttl(confirmations);
+        if let Some(shared) = &self.shared {
+            shared.set(key, value.clone(), ttl, volatile).await;
+        }
+        self.local.set(key, value, ttl, volatile).await;
+    }
+
+    /// returns the cached entry, or fetches and stores it.
+    /// Errors are never cached
+    pub async fn get_or_fetch<T, Fut>(
+        &self,
+        kind: Kind,
+        key: String,
+        fetch: Fut,
+        confirmations: impl Fn(&T) -> Option<u64>,
+    ) -> Result<T, ApiError>
+    where
+        T: Serialize + DeserializeOwned,
+        Fut: Future<Output = Result<T, ApiError>>,
+    {
+        let key = format!("{}:{}", kind.name(), key);
+        if let Some(x) = self.get(key.as_str()).await {
+            self.counter(kind).hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(x);
+        }
+        self.counter(kind).misses.fetch_add(1, Ordering::Relaxed);
+        let value = fetch.await?;
+        self.set(key.as_str(), &value, confirmations(&value)).await;
+        Ok(value)
+    }
+
+    /// drops all entries about the tip of the chain, called on a new block
+    pub async fn invalidate_tip(&self) {
+        self.local.invalidate_volatile().await;
+        if let Some(shared) = &self.shared {
+            shared.invalidate_volatile().await;
+        }
+    }
+}
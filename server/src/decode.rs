@@ -0,0 +1,190 @@
+//! `POST /api/decode`, inspects the raw transaction or PSBT that is not on chain.
+//!
+//! Outputs spent by the transaction are taken from PSBT when it carries them,
+//! otherwise from the index, so the fee is known once all of them are found.
+//! The reply is the same transaction detail as `/api/tx/:tx` has.
+
+use crate::api;
+use crate::error::{respond, ApiError};
+use crate::index::Prevout;
+use crate::network;
+use crate::script;
+use crate::txdetail;
+use crate::types::response;
+use crate::types::DecodedTx;
+use crate::State;
+use bitcoin::consensus::encode::{deserialize, serialize};
+use bitcoin::hashes::hex::{FromHex, ToHex};
+use bitcoin::util::psbt::PartiallySignedTransaction;
+use bitcoin::{Amount, Transaction, TxOut};
+use bitcoincore_rpc_json as json;
+use json::bitcoin;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use tide::Request;
+
+// longest body, PSBT carries the previous transactions of its inputs
+const MAX_SIZE: u64 = 4_000_000;
+const PSBT_MAGIC: &[u8] = b"psbt\xff";
+
+/// JSON body of the request, the body can also be the plain hex or base64
+#[derive(Deserialize)]
+struct Body {
+    hex: Option<String>,
+    psbt: Option<String>,
+}
+
+/// Transaction of PSBT with the outputs spent by its inputs, where PSBT has them
+struct Psbt {
+    tx: Transaction,
+    spent: Vec<Option<TxOut>>,
+}
+
+fn invalid(what: &str, e: impl std::fmt::Display) -> ApiError {
+    ApiError::invalid_param(format!("{} parsing error {}", what, e))
+}
+
+/// weight of the transaction, `get_weight` is renamed in the later versions of `bitcoin` crate
+#[allow(deprecated)]
+pub fn weight(tx: &Transaction) -> usize {
+    tx.get_weight()
+}
+
+/// transaction of PSBT with the outputs spent by its inputs, where PSBT has them.
+/// The inputs that are finalized carry their final scripts and witnesses
+fn parse_psbt(bytes: &[u8]) -> Result<Psbt, ApiError> {
+    let psbt: PartiallySignedTransaction = deserialize(bytes).map_err(|e| invalid("PSBT", e))?;
+    let tx = psbt.clone().extract_tx();
+    let spent = tx
+        .input
+        .iter()
+        .zip(psbt.inputs)
+        .map(
+            |(input, found)| match (found.witness_utxo, found.non_witness_utxo) {
+                (Some(out), _) => Some(out),
+                // the whole previous transaction
+                (None, Some(prev)) => prev
+                    .output
+                    .get(input.previous_output.vout as usize)
+                    .cloned(),
+                (None, None) => None,
+            },
+        )
+        .collect();
+    Ok(Psbt { tx, spent })
+}
+
+/// transaction in the shape of `getrawtransaction` reply of the node
+fn raw_transaction(tx: &Transaction) -> Result<json::GetRawTransactionResult, ApiError> {
+    let raw = serialize(tx);
+    let vin: Vec<Value> = tx
+        .input
+        .iter()
+        .map(|input| {
+            let script_sig = input.script_sig.as_bytes().to_hex();
+            let mut out = if tx.is_coin_base() {
+                json!({ "coinbase": script_sig })
+            } else {
+                json!({
+                    "txid": input.previous_output.txid.to_string(),
+                    "vout": input.previous_output.vout,
+                    "scriptSig": { "asm": script::asm(&input.script_sig, true), "hex": script_sig },
+                })
+            };
+            let witness: Vec<String> = input.witness.iter().map(|w| w.to_hex()).collect();
+            if !witness.is_empty() {
+                out["txinwitness"] = json!(witness);
+            }
+            out["sequence"] = json!(input.sequence);
+            out
+        })
+        .collect();
+    // the type and the address of the output script are left to the transaction detail
+    let vout: Vec<Value> = tx
+        .output
+        .iter()
+        .enumerate()
+        .map(|(n, out)| {
+            json!({
+                "value": Amount::from_sat(out.value).as_btc(),
+                "n": n,
+                "scriptPubKey": {
+                    "asm": script::asm(&out.script_pubkey, false),
+                    "hex": out.script_pubkey.as_bytes().to_hex(),
+                },
+            })
+        })
+        .collect();
+    let value = json!({
+        "txid": tx.txid().to_string(),
+        "hash": tx.wtxid().to_string(),
+        "version": tx.version,
+        "size": raw.len(),
+        "vsize": (weight(tx) + 3) / 4,
+        "locktime": tx.lock_time,
+        "vin": vin,
+        "vout": vout,
+        "hex": raw.to_hex(),
+    });
+    serde_json::from_value(value).map_err(|e| invalid("transaction", e))
+}
+
+async fn decode(state: &State, text: &str) -> Result<DecodedTx, ApiError> {
+    let text = text.trim();
+    let given = if text.starts_with('{') {
+        let body: Body = serde_json::from_str(text).map_err(|e| invalid("body", e))?;
+        match body.psbt.or(body.hex) {
+            Some(x) => x,
+            None => return Err(ApiError::invalid_param("body has neither hex nor psbt")),
+        }
+    } else {
+        text.to_string()
+    };
+    let given = given.trim();
+    let bytes = match Vec::<u8>::from_hex(given) {
+        Ok(x) => x,
+        Err(_) => base64::decode(given).map_err(|e| invalid("hex or base64", e))?,
+    };
+    let is_psbt = bytes.starts_with(PSBT_MAGIC);
+    let (tx, spent) = if is_psbt {
+        let psbt = parse_psbt(&bytes)?;
+        (psbt.tx, psbt.spent)
+    } else {
+        let tx: Transaction = deserialize(&bytes).map_err(|e| invalid("transaction", e))?;
+        (tx, vec![])
+    };
+    // outputs carried by PSBT, the rest of them are looked up in the index
+    let chain = network::address_network(state.network);
+    let known: HashMap<(String, u32), Prevout> = tx
+        .input
+        .iter()
+        .zip(spent)
+        .filter_map(|(input, out)| {
+            let out = out?;
+            let prev = &input.previous_output;
+            let found = Prevout {
+                address: bitcoin::Address::from_script(&out.script_pubkey, chain)
+                    .map(|a| a.to_string()),
+                script: Some(out.script_pubkey.to_bytes()),
+                value: out.value as i64,
+            };
+            Some(((prev.txid.to_string(), prev.vout), found))
+        })
+        .collect();
+    Ok(DecodedTx {
+        tx: txdetail::build(state, &raw_transaction(&tx)?, known).await?,
+        psbt: is_psbt,
+    })
+}
+
+pub async fn handler(mut req: Request<State>) -> tide::Result {
+    let out = match api::body_text(&mut req, MAX_SIZE).await {
+        Ok(text) => match decode(req.state(), &text).await {
+            Ok(x) => response::Decode::Tx(x),
+            Err(e) => response::Decode::Failure(e),
+        },
+        Err(e) => response::Decode::Failure(e),
+    };
+    respond(out.status(), &out)
+}
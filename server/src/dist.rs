@@ -1,3 +1,4 @@
+use crate::routes;
 use crate::State;
 use std::path::{Path, PathBuf};
 use std::{ffi::OsStr, io};
@@ -20,7 +21,7 @@ impl tide::Middleware<State> for Middleware {
         let path = req.url().path().to_owned();
         let method = req.method().to_string();
 
-        if method == "GET" && path != "/" && !path.starts_with("/api/") {
+        if method == "GET" && path != "/" && !routes::is_api(&path) && !routes::is_service(&path) {
             let dir = PathBuf::from(req.state().static_dir.clone());
             let path = path.trim_start_matches('/');
             let mut file_path = dir.clone();
@@ -0,0 +1,811 @@
+//! Electrum protocol server, so Electrum-compatible wallets can use our own node and index.
+//!
+//! Speaks newline-delimited JSON-RPC over plain TCP. History, balances and unspent outputs
+//! come from `final_outputs` index, so they are as fresh as the indexer is,
+//! and the transactions in the mempool are not reported.
+//!
+//! Requests are throttled by the client address with the limits of the API requests
+//! without API key, the buckets are separate from those of the HTTP API.
+
+use crate::broadcast;
+use crate::health;
+use crate::index;
+use crate::ratelimit::Limiter;
+use crate::rpc;
+use crate::types::response;
+use crate::State;
+use async_std::io::{BufReader, ReadExt};
+use async_std::net::{TcpListener, TcpStream};
+use async_std::prelude::*;
+use async_std::sync::Mutex;
+use bitcoin::hashes::hex::{FromHex, ToHex};
+use bitcoin::hashes::{sha256, sha256d, Hash};
+use bitcoincore_rpc_json as json;
+use json::bitcoin;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, PoisonError};
+use std::time::{Duration, Instant};
+
+const PROTOCOL_VERSION: &str = "1.4";
+// longest request line, the connection is closed on a longer one
+const MAX_LINE: u64 = 1 << 20;
+// scripthashes one connection can subscribe to
+const MAX_SUBSCRIPTIONS: usize = 10_000;
+// requests in one batch line
+const MAX_BATCH: usize = 100;
+// open connections from one client address, more are closed right away
+const MAX_CONNECTIONS: usize = 16;
+// headers returned by one `blockchain.block.headers` call
+const MAX_HEADERS: u64 = 2016;
+// how often the tip and the subscribed scripthashes are checked for changes
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Error of the call, codes are those of JSON-RPC and ElectrumX
+#[derive(Debug)]
+struct RpcError {
+    code: i32,
+    message: String,
+}
+
+impl RpcError {
+    fn method_not_found(method: &str) -> Self {
+        Self {
+            code: -32601,
+            message: format!("unknown method {}", method),
+        }
+    }
+
+    fn invalid_params(message: impl ToString) -> Self {
+        Self {
+            code: -32602,
+            message: message.to_string(),
+        }
+    }
+
+    fn bad_request(message: impl ToString) -> Self {
+        Self {
+            code: 1,
+            message: message.to_string(),
+        }
+    }
+
+    fn daemon(message: impl ToString) -> Self {
+        Self {
+            code: 2,
+            message: message.to_string(),
+        }
+    }
+
+    /// "excessive resource usage" of ElectrumX
+    fn rate_limited(retry_after: Duration) -> Self {
+        Self {
+            code: -101,
+            message: format!(
+                "too many requests, retry in {} s",
+                retry_after.as_secs_f64().ceil().max(1.0)
+            ),
+        }
+    }
+}
+
+impl From<explorer_types::ApiError> for RpcError {
+    fn from(e: explorer_types::ApiError) -> Self {
+        Self::daemon(e)
+    }
+}
+
+impl From<index::Error> for RpcError {
+    fn from(e: index::Error) -> Self {
+        match e {
+            index::Error::TooLarge => Self::bad_request(e),
+            index::Error::Db(_) => Self::daemon(e),
+        }
+    }
+}
+
+type Reply = Result<Value, RpcError>;
+
+fn param_str(params: &[Value], i: usize) -> Result<&str, RpcError> {
+    params
+        .get(i)
+        .and_then(Value::as_str)
+        .ok_or_else(|| RpcError::invalid_params(format!("missing string param {}", i)))
+}
+
+fn param_u64(params: &[Value], i: usize) -> Result<u64, RpcError> {
+    params
+        .get(i)
+        .and_then(Value::as_u64)
+        .ok_or_else(|| RpcError::invalid_params(format!("missing integer param {}", i)))
+}
+
+/// scripthash of the protocol is the reversed SHA-256 of the script
+fn parse_scripthash(value: &str) -> Result<Vec<u8>, RpcError> {
+    let hash = sha256::Hash::from_hex(value)
+        .map_err(|e| RpcError::invalid_params(format!("invalid scripthash: {}", e)))?;
+    let mut out = hash.into_inner().to_vec();
+    out.reverse();
+    Ok(out)
+}
+
+/// status of the scripthash, the hash of its history, none when the history is empty
+fn status(history: &[index::HistoryItem]) -> Option<String> {
+    if history.is_empty() {
+        return None;
+    }
+    let text: String = history
+        .iter()
+        .map(|h| format!("{}:{}:", h.tx_hash, h.height))
+        .collect();
+    Some(sha256::Hash::hash(text.as_bytes()).into_inner().to_hex())
+}
+
+/// merkle branch of the transaction at `pos`, hashes are in the display order
+fn merkle_branch(mut hashes: Vec<[u8; 32]>, mut pos: usize) -> Vec<String> {
+    let mut branch = vec![];
+    while hashes.len() > 1 {
+        if hashes.len() % 2 == 1 {
+            hashes.push(hashes[hashes.len() - 1]);
+        }
+        branch.push(sha256d::Hash::from_inner(hashes[pos ^ 1]).to_hex());
+        pos /= 2;
+        hashes = hashes
+            .chunks(2)
+            .map(|pair| sha256d::Hash::hash(&[pair[0], pair[1]].concat()).into_inner())
+            .collect();
+    }
+    branch
+}
+
+/// Open connections per client address
+#[derive(Default)]
+struct Connections(std::sync::Mutex<HashMap<IpAddr, usize>>);
+
+/// Counted connection, uncounted when it is dropped
+struct Counted {
+    connections: Arc<Connections>,
+    ip: IpAddr,
+}
+
+impl Connections {
+    /// counts the connection, none if the address has too many of them
+    fn open(self: &Arc<Self>, ip: IpAddr) -> Option<Counted> {
+        let mut open = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        let count = open.entry(ip).or_insert(0);
+        if *count >= MAX_CONNECTIONS {
+            return None;
+        }
+        *count += 1;
+        Some(Counted {
+            connections: self.clone(),
+            ip,
+        })
+    }
+}
+
+impl Drop for Counted {
+    fn drop(&mut self) {
+        let mut open = self
+            .connections
+            .0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some(count) = open.get_mut(&self.ip) {
+            *count -= 1;
+            if *count == 0 {
+                open.remove(&self.ip);
+            }
+        }
+    }
+}
+
+/// Connection of the wallet
+struct Session {
+    state: State,
+    // client as it is throttled and audited, `ip:<address>`
+    client: String,
+    limiter: Arc<Limiter>,
+    writer: Mutex<TcpStream>,
+    headers: AtomicBool,
+    // subscribed scripthashes with their last reported status
+    scripthashes: Mutex<HashMap<String, Option<String>>>,
+    closed: AtomicBool,
+}
+
+impl Session {
+    async fn send(&self, msg: &Value) -> std::io::Result<()> {
+        let mut line = msg.to_string();
+        line.push('\n');
+        self.writer.lock().await.write_all(line.as_bytes()).await
+    }
+
+    async fn tip(&self) -> Reply {
+        let client = &self.state.rpc_client;
+        let chain = rpc::get_blockchain_info(client.clone()).await?;
+        let hex: String = client
+            .call(
+                "getblockheader",
+                vec![json!(chain.best_block_hash.to_string()), json!(false)],
+            )
+            .await?;
+        Ok(json!({ "height": chain.blocks, "hex": hex }))
+    }
+
+    async fn header(&self, height: u64) -> Result<String, RpcError> {
+        let client = &self.state.rpc_client;
+        let hash: String = client.call("getblockhash", vec![json!(height)]).await?;
+        Ok(client
+            .call("getblockheader", vec![json!(hash), json!(false)])
+            .await?)
+    }
+
+    async fn headers(&self, start: u64, count: u64) -> Reply {
+        let client = &self.state.rpc_client;
+        let tip = rpc::get_blockchain_info(client.clone()).await?.blocks;
+        let count = count.min(MAX_HEADERS).min((tip + 1).saturating_sub(start));
+        let calls = (start..start + count)
+            .map(|h| ("getblockhash", vec![json!(h)]))
+            .collect();
+        let hashes = client.call_batch(calls).await?;
+        let calls = hashes
+            .into_iter()
+            .map(|hash| ("getblockheader", vec![hash, json!(false)]))
+            .collect();
+        let hex: String = client
+            .call_batch(calls)
+            .await?
+            .iter()
+            .filter_map(Value::as_str)
+            .collect();
+        Ok(json!({ "count": count, "hex": hex, "max": MAX_HEADERS }))
+    }
+
+    async fn merkle(&self, txid: &str, height: u64) -> Reply {
+        let client = &self.state.rpc_client;
+        let hash: String = client.call("getblockhash", vec![json!(height)]).await?;
+        let block: json::GetBlockResult =
+            client.call("getblock", vec![json!(hash), json!(1)]).await?;
+        let txid = bitcoin::Txid::from_hex(txid)
+            .map_err(|e| RpcError::invalid_params(format!("invalid tx hash: {}", e)))?;
+        let pos = match block.tx.iter().position(|x| *x == txid) {
+            Some(x) => x,
+            None => return Err(RpcError::bad_request("tx is not in the block")),
+        };
+        let hashes = block.tx.iter().map(|x| x.into_inner()).collect();
+        Ok(json!({
+            "block_height": height,
+            "merkle": merkle_branch(hashes, pos),
+            "pos": pos,
+        }))
+    }
+
+    async fn subscribe(&self, scripthash: &str) -> Reply {
+        let script = parse_scripthash(scripthash)?;
+        let history = index::history(&self.state.pool, &script).await?;
+        let status = status(&history);
+        let mut subscribed = self.scripthashes.lock().await;
+        if !subscribed.contains_key(scripthash) && subscribed.len() >= MAX_SUBSCRIPTIONS {
+            return Err(RpcError::bad_request("too many subscriptions"));
+        }
+        subscribed.insert(scripthash.to_string(), status.clone());
+        Ok(json!(status))
+    }
+
+    /// tests and relays the transaction the same way `POST /api/tx` does, with the audit
+    async fn broadcast(&self, hex: &str) -> Reply {
+        let raw = Vec::<u8>::from_hex(hex.trim()).map_err(|e| {
+            RpcError::invalid_params(format!("transaction hex parsing error {}", e))
+        })?;
+        let max_size = self.state.broadcast_max_size;
+        if raw.is_empty() || raw.len() > max_size {
+            return Err(RpcError::invalid_params(format!(
+                "transaction is empty or larger than {} bytes",
+                max_size
+            )));
+        }
+        let out = broadcast::broadcast(&self.state, &raw.to_hex()).await;
+        broadcast::audit(&self.state, &self.client, raw.len(), &out).await;
+        match out {
+            response::Broadcast::Sent(x) => Ok(json!(x.txid)),
+            response::Broadcast::Rejected(x) => Err(RpcError::bad_request(x.reason)),
+            response::Broadcast::Failure(e) => Err(e.into()),
+        }
+    }
+
+    async fn dispatch(&self, method: &str, params: &[Value]) -> Reply {
+        let client = &self.state.rpc_client;
+        let pool = &self.state.pool;
+        match method {
+            "server.version" => Ok(json!([
+                format!("bitcoin-explorer {}", env!("CARGO_PKG_VERSION")),
+                PROTOCOL_VERSION
+            ])),
+            "server.banner" => Ok(json!(format!(
+                "bitcoin-explorer {} on {}",
+                env!("CARGO_PKG_VERSION"),
+                self.state.network
+            ))),
+            "server.ping" => Ok(Value::Null),
+            "server.donation_address" => Ok(json!("")),
+            "server.peers.subscribe" => Ok(json!([])),
+            "server.features" => Ok(json!({
+                "server_version": format!("bitcoin-explorer {}", env!("CARGO_PKG_VERSION")),
+                "protocol_min": PROTOCOL_VERSION,
+                "protocol_max": PROTOCOL_VERSION,
+                "hash_function": "sha256",
+                "pruning": Value::Null,
+                "hosts": {},
+            })),
+            "mempool.get_fee_histogram" => Ok(json!([])),
+            "blockchain.headers.subscribe" => {
+                self.headers.store(true, Ordering::Relaxed);
+                self.tip().await
+            }
+            "blockchain.block.header" => Ok(json!(self.header(param_u64(params, 0)?).await?)),
+            "blockchain.block.headers" => {
+                self.headers(param_u64(params, 0)?, param_u64(params, 1)?)
+                    .await
+            }
+            "blockchain.scripthash.get_history" => {
+                let script = parse_scripthash(param_str(params, 0)?)?;
+                Ok(json!(index::history(pool, &script).await?))
+            }
+            "blockchain.scripthash.get_balance" => {
+                let script = parse_scripthash(param_str(params, 0)?)?;
+                let confirmed = index::balance(pool, &script).await?;
+                Ok(json!({ "confirmed": confirmed, "unconfirmed": 0 }))
+            }
+            "blockchain.scripthash.get_mempool" => Ok(json!([])),
+            "blockchain.scripthash.listunspent" => {
+                let script = parse_scripthash(param_str(params, 0)?)?;
+                Ok(json!(index::unspent(pool, &script).await?))
+            }
+            "blockchain.scripthash.subscribe" => self.subscribe(param_str(params, 0)?).await,
+            "blockchain.scripthash.unsubscribe" => {
+                let scripthash = param_str(params, 0)?;
+                Ok(json!(self
+                    .scripthashes
+                    .lock()
+                    .await
+                    .remove(scripthash)
+                    .is_some()))
+            }
+            "blockchain.transaction.get" => {
+                let txid = param_str(params, 0)?;
+                let verbose = params.get(1).and_then(Value::as_bool).unwrap_or(false);
+                Ok(client
+                    .call::<Value>("getrawtransaction", vec![json!(txid), json!(verbose)])
+                    .await?)
+            }
+            "blockchain.transaction.get_merkle" => {
+                self.merkle(param_str(params, 0)?, param_u64(params, 1)?)
+                    .await
+            }
+            "blockchain.transaction.broadcast" => self.broadcast(param_str(params, 0)?).await,
+            "blockchain.estimatefee" => {
+                let blocks = param_u64(params, 0)?;
+                let out: json::EstimateSmartFeeResult =
+                    client.call("estimatesmartfee", vec![json!(blocks)]).await?;
+                // BTC per kilobyte, -1 when the node can't estimate
+                Ok(match out.fee_rate {
+                    Some(x) => json!(x.as_btc()),
+                    None => json!(-1),
+                })
+            }
+            "blockchain.relayfee" => {
+                let info: Value = client.call("getnetworkinfo", vec![]).await?;
+                Ok(info.get("relayfee").cloned().unwrap_or(Value::Null))
+            }
+            _ => Err(RpcError::method_not_found(method)),
+        }
+    }
+
+    /// response to the single request object
+    async fn handle(&self, req: &Value) -> Value {
+        let id = req.get("id").cloned().unwrap_or(Value::Null);
+        let method = req.get("method").and_then(Value::as_str).unwrap_or("");
+        let params = match req.get("params") {
+            Some(Value::Array(x)) => x.clone(),
+            _ => vec![],
+        };
+        let start = Instant::now();
+        let is_broadcast = method == "blockchain.transaction.broadcast";
+        let out = match self.limiter.take_anonymous(&self.client, is_broadcast) {
+            Ok(()) => self.dispatch(method, &params).await,
+            Err(retry_after) => Err(RpcError::rate_limited(retry_after)),
+        };
+        let status = match &out {
+            Ok(_) => 200,
+            Err(e) if e.code == -32601 => 404,
+            Err(e) if e.code == -101 => 429,
+            Err(e) if e.code == 2 => 502,
+            Err(_) => 400,
+        };
+        self.state
+            .metrics
+            .observe_request(method, "ELECTRUM", status, start.elapsed());
+        match out {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(e) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": e.code, "message": e.message },
+            }),
+        }
+    }
+
+    /// response to the request line, which is either a single request or a batch
+    async fn handle_line(&self, line: &str) -> Value {
+        let req: Value = match serde_json::from_str(line) {
+            Ok(x) => x,
+            Err(e) => {
+                return json!({
+                    "jsonrpc": "2.0",
+                    "id": Value::Null,
+                    "error": { "code": -32700, "message": format!("parse error: {}", e) },
+                })
+            }
+        };
+        match req {
+            Value::Array(batch) if batch.len() > MAX_BATCH => json!({
+                "jsonrpc": "2.0",
+                "id": Value::Null,
+                "error": {
+                    "code": -32600,
+                    "message": format!("batch is longer than {} requests", MAX_BATCH),
+                },
+            }),
+            Value::Array(batch) => {
+                let mut out = Vec::with_capacity(batch.len());
+                for req in batch.iter() {
+                    out.push(self.handle(req).await);
+                }
+                Value::Array(out)
+            }
+            req => self.handle(&req).await,
+        }
+    }
+
+    /// notifies about the new tip, and about the subscribed scripthashes
+    /// that are funded or spent in the blocks added to the index since the last check
+    async fn watch(self: Arc<Self>) {
+        let mut last_tip: Option<bitcoin::BlockHash> = None;
+        let mut indexed: Option<i64> = None;
+        while !self.closed.load(Ordering::Relaxed) {
+            async_std::task::sleep(POLL_INTERVAL).await;
+            if self.headers.load(Ordering::Relaxed) && !self.notify_tip(&mut last_tip).await {
+                return;
+            }
+            if self.scripthashes.lock().await.is_empty() {
+                continue;
+            }
+            let height = match health::indexed_height(&self.state.pool).await {
+                Ok(x) => x,
+                Err(e) => {
+                    tracing::warn!("electrum index height failed: {}", e);
+                    continue;
+                }
+            };
+            let changed = match indexed {
+                Some(x) if x == height => continue,
+                Some(x) if x < height => self.touched(x).await,
+                // the first check or the index was rolled back
+                _ => Ok(self.scripthashes.lock().await.keys().cloned().collect()),
+            };
+            let changed = match changed {
+                Ok(x) => x,
+                Err(e) => {
+                    tracing::warn!("electrum touched scripthashes failed: {}", e);
+                    continue;
+                }
+            };
+            indexed = Some(height);
+            for scripthash in changed {
+                if !self.notify_status(scripthash).await {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// sends the header of the new tip, false if the connection is gone
+    async fn notify_tip(&self, last: &mut Option<bitcoin::BlockHash>) -> bool {
+        let chain = match rpc::get_blockchain_info(self.state.rpc_client.clone()).await {
+            Ok(x) => x,
+            Err(_) => return true,
+        };
+        if last.as_ref() == Some(&chain.best_block_hash) {
+            return true;
+        }
+        let first = last.is_none();
+        *last = Some(chain.best_block_hash);
+        if first {
+            return true;
+        }
+        let tip = match self.tip().await {
+            Ok(x) => x,
+            Err(_) => return true,
+        };
+        let msg = json!({
+            "jsonrpc": "2.0",
+            "method": "blockchain.headers.subscribe",
+            "params": [tip],
+        });
+        self.send(&msg).await.is_ok()
+    }
+
+    /// subscribed scripthashes funded or spent in the blocks above the height
+    async fn touched(&self, above: i64) -> Result<Vec<String>, index::Error> {
+        let scripts: HashMap<Vec<u8>, String> = self
+            .scripthashes
+            .lock()
+            .await
+            .keys()
+            .filter_map(|x| Some((parse_scripthash(x).ok()?, x.clone())))
+            .collect();
+        let keys: Vec<Vec<u8>> = scripts.keys().cloned().collect();
+        let touched = index::touched(&self.state.pool, &keys, above as i32).await?;
+        Ok(touched
+            .iter()
+            .filter_map(|x| scripts.get(x).cloned())
+            .collect())
+    }
+
+    /// sends the status of the scripthash if it has changed, false if the connection is gone
+    async fn notify_status(&self, scripthash: String) -> bool {
+        let script = match parse_scripthash(&scripthash) {
+            Ok(x) => x,
+            Err(_) => return true,
+        };
+        let current = match index::history(&self.state.pool, &script).await {
+            Ok(x) => status(&x),
+            Err(e) => {
+                tracing::warn!("electrum status of {} failed: {}", scripthash, e);
+                return true;
+            }
+        };
+        match self.scripthashes.lock().await.get_mut(&scripthash) {
+            // unsubscribed meanwhile
+            None => return true,
+            Some(x) if *x == current => return true,
+            Some(x) => *x = current.clone(),
+        }
+        let msg = json!({
+            "jsonrpc": "2.0",
+            "method": "blockchain.scripthash.subscribe",
+            "params": [scripthash, current],
+        });
+        self.send(&msg).await.is_ok()
+    }
+}
+
+async fn serve(
+    state: State,
+    stream: TcpStream,
+    limiter: Arc<Limiter>,
+    client: String,
+) -> anyhow::Result<()> {
+    let session = Arc::new(Session {
+        state,
+        client,
+        limiter,
+        writer: Mutex::new(stream.clone()),
+        headers: AtomicBool::new(false),
+        scripthashes: Mutex::new(HashMap::new()),
+        closed: AtomicBool::new(false),
+    });
+    let shutdown = session.state.shutdown.clone();
+    let watch = session.clone().watch();
+    let stopped = shutdown.clone();
+    async_std::task::spawn(watch.race(async move { stopped.wait().await }));
+    let read = async {
+        let mut reader = BufReader::new(stream.clone());
+        loop {
+            let mut buf = vec![];
+            let n = (&mut reader)
+                .take(MAX_LINE)
+                .read_until(b'\n', &mut buf)
+                .await?;
+            if n == 0 {
+                return Ok(());
+            }
+            if buf.last() != Some(&b'\n') && n as u64 == MAX_LINE {
+                anyhow::bail!("request is longer than {} bytes", MAX_LINE);
+            }
+            let line = String::from_utf8_lossy(&buf);
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let res = session.handle_line(line).await;
+            session.send(&res).await?;
+        }
+    };
+    // the wallets reconnect to another server once the connection is closed
+    let stop = async {
+        shutdown.wait().await;
+        Ok(())
+    };
+    let out = read.race(stop).await;
+    session.closed.store(true, Ordering::Relaxed);
+    let _ = stream.shutdown(std::net::Shutdown::Both);
+    out
+}
+
+/// serves the Electrum protocol on the address, with the index of the given network
+pub async fn listen(state: State, addr: String, limiter: Limiter) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(addr.as_str()).await?;
+    tracing::info!(
+        "Electrum server listening on tcp://{}",
+        listener.local_addr()?
+    );
+    let limiter = Arc::new(limiter);
+    let connections = Arc::new(Connections::default());
+    let mut incoming = listener.incoming();
+    while let Some(stream) = incoming.next().await {
+        let stream = match stream {
+            Ok(x) => x,
+            Err(e) => {
+                tracing::warn!("electrum accept failure: {}", e);
+                continue;
+            }
+        };
+        let peer = match stream.peer_addr() {
+            Ok(x) => x,
+            Err(_) => continue,
+        };
+        let counted = match connections.open(peer.ip()) {
+            Some(x) => x,
+            None => {
+                tracing::debug!("electrum connection {} refused, too many", peer);
+                continue;
+            }
+        };
+        let state = state.clone();
+        let limiter = limiter.clone();
+        async_std::task::spawn(async move {
+            let client = format!("ip:{}", peer.ip());
+            if let Err(e) = serve(state, stream, limiter, client).await {
+                tracing::debug!("electrum connection {} error: {}", peer, e);
+            }
+            drop(counted);
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::Args;
+    use async_std::io::BufReadExt;
+    use structopt::StructOpt;
+
+    /// wallet connected to the session served on the other end of the socket
+    struct Wallet {
+        reader: BufReader<TcpStream>,
+        writer: TcpStream,
+        state: State,
+    }
+
+    impl Wallet {
+        async fn connect(extra: &[&str]) -> Self {
+            // nothing is listening at the node and at the database
+            let mut flags = vec![
+                "bitcoin-explorer",
+                "--rpc-addr",
+                "http://127.0.0.1:9",
+                "--database-url",
+                "postgres://explorer@127.0.0.1:9/explorer",
+            ];
+            flags.extend_from_slice(extra);
+            let args = Args::from_iter_safe(flags).unwrap();
+            let state = State::from_args(&args).await.unwrap();
+            let limiter = Arc::new(Limiter::from_args(&args, state.pool.clone()).unwrap());
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let writer = TcpStream::connect(listener.local_addr().unwrap())
+                .await
+                .unwrap();
+            let (stream, _) = listener.accept().await.unwrap();
+            let served = state.clone();
+            async_std::task::spawn(async move {
+                serve(served, stream, limiter, "ip:127.0.0.1".to_string()).await
+            });
+            Self {
+                reader: BufReader::new(writer.clone()),
+                writer,
+                state,
+            }
+        }
+
+        async fn write(&mut self, text: &str) {
+            self.writer.write_all(text.as_bytes()).await.unwrap();
+        }
+
+        /// next line sent by the server, none when the connection is closed
+        async fn read(&mut self) -> Option<Value> {
+            let mut line = String::new();
+            let read = self.reader.read_line(&mut line);
+            match async_std::future::timeout(Duration::from_secs(5), read).await {
+                Ok(Ok(0)) => None,
+                Ok(Ok(_)) => Some(serde_json::from_str(&line).unwrap()),
+                x => panic!("no reply: {:?}", x),
+            }
+        }
+
+        async fn call(&mut self, line: &str) -> Value {
+            self.write(&format!("{}\n", line)).await;
+            self.read().await.unwrap()
+        }
+    }
+
+    #[async_std::test]
+    async fn newline_delimited_requests() {
+        let mut wallet = Wallet::connect(&["--rate-limit", ""]).await;
+        let reply = wallet
+            .call(r#"{"jsonrpc":"2.0","id":7,"method":"server.version","params":["test","1.4"]}"#)
+            .await;
+        assert_eq!(reply["id"], 7);
+        assert_eq!(reply["result"][1], PROTOCOL_VERSION);
+
+        // two requests in one write are answered in order, one line each
+        wallet
+            .write("{\"id\":1,\"method\":\"server.ping\"}\n\n{\"id\":2,\"method\":\"nope\"}\n")
+            .await;
+        let first = wallet.read().await.unwrap();
+        assert_eq!(first["id"], 1);
+        assert!(first["result"].is_null() && first["error"].is_null());
+        let second = wallet.read().await.unwrap();
+        assert_eq!(second["id"], 2);
+        assert_eq!(second["error"]["code"], -32601);
+
+        let batch = wallet
+            .call(r#"[{"id":3,"method":"server.ping"},{"id":4,"method":"mempool.get_fee_histogram"}]"#)
+            .await;
+        assert_eq!(batch[0]["id"], 3);
+        assert_eq!(batch[1]["result"], json!([]));
+
+        let reply = wallet.call("not json").await;
+        assert_eq!(reply["error"]["code"], -32700);
+
+        let long: Vec<Value> = (0..=MAX_BATCH)
+            .map(|id| json!({ "id": id, "method": "server.ping" }))
+            .collect();
+        let reply = wallet.call(&Value::Array(long).to_string()).await;
+        assert_eq!(reply["error"]["code"], -32600);
+    }
+
+    #[async_std::test]
+    async fn requests_are_throttled() {
+        let mut wallet = Wallet::connect(&["--rate-limit", "0.01/2"]).await;
+        let ping = r#"{"id":1,"method":"server.ping"}"#;
+        assert!(wallet.call(ping).await["error"].is_null());
+        assert!(wallet.call(ping).await["error"].is_null());
+        assert_eq!(wallet.call(ping).await["error"]["code"], -101);
+    }
+
+    #[async_std::test]
+    async fn closed_on_shutdown() {
+        let mut wallet = Wallet::connect(&[]).await;
+        let ping = r#"{"id":1,"method":"server.ping"}"#;
+        assert!(wallet.call(ping).await["error"].is_null());
+        wallet.state.shutdown.trigger();
+        assert!(wallet.read().await.is_none());
+    }
+
+    #[test]
+    fn connections_per_address() {
+        let connections = Arc::new(Connections::default());
+        let ip: IpAddr = "192.0.2.1".parse().unwrap();
+        let open: Vec<Counted> = (0..MAX_CONNECTIONS)
+            .map(|_| connections.open(ip).unwrap())
+            .collect();
+        assert!(connections.open(ip).is_none());
+        assert!(connections.open("192.0.2.2".parse().unwrap()).is_some());
+        drop(open);
+        assert!(connections.open(ip).is_some());
+    }
+}
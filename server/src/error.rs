@@ -0,0 +1,75 @@
+use serde::Serialize;
+use tide::{Body, Next, Request, Response};
+
+pub use explorer_types::error::{ApiError, ErrorCode, ErrorResponse};
+
+/// classifies the error object of the node JSON-RPC reply
+pub fn from_rpc_code(code: i32, message: String) -> ApiError {
+    match code {
+        // RPC_INVALID_ADDRESS_OR_KEY, i.e. block or transaction is not known to the node
+        -5 => ApiError::not_found(message),
+        // RPC_INVALID_PARAMETER, RPC_TYPE_ERROR
+        -8 | -3 => ApiError::invalid_param(message),
+        // RPC_VERIFY_ERROR, RPC_VERIFY_REJECTED, RPC_VERIFY_ALREADY_IN_CHAIN
+        // and RPC_DESERIALIZATION_ERROR of the sent transaction
+        -25 | -26 | -27 | -22 => ApiError::invalid_param(message),
+        // RPC_IN_WARMUP, the node is starting and the call is retried
+        -28 => ApiError::upstream_unavailable(message),
+        // the node has replied, the call would fail the same way again
+        _ => ApiError::internal_error(message),
+    }
+}
+
+/// classifies the error of the node RPC call
+pub fn from_rpc(e: &bitcoincore_rpc::Error) -> ApiError {
+    use bitcoincore_rpc::jsonrpc::error::Error as JsonRpcError;
+    match e {
+        bitcoincore_rpc::Error::JsonRpc(JsonRpcError::Rpc(rpc)) => {
+            from_rpc_code(rpc.code, rpc.message.clone())
+        }
+        _ => ApiError::upstream_unavailable(e.to_string()),
+    }
+}
+
+/// JSON response with the given status
+pub fn respond<T: Serialize>(status: u16, body: &T) -> tide::Result {
+    let mut res = Response::new(status);
+    res.set_body(Body::from_json(body)?);
+    Ok(res)
+}
+
+/// error envelope with the status of the error code
+pub fn failure(e: ApiError) -> tide::Result {
+    respond(e.status(), &ErrorResponse { error: e })
+}
+
+/// Makes sure that API errors produced outside of handlers
+/// (unknown routes, failed handlers) are sent in the same envelope
+#[derive(Debug, Default, Clone)]
+pub struct Envelope;
+
+#[tide::utils::async_trait]
+impl<State: Clone + Send + Sync + 'static> tide::Middleware<State> for Envelope {
+    async fn handle(&self, req: Request<State>, next: Next<'_, State>) -> tide::Result {
+        let is_api = req.url().path().starts_with("/api/");
+        let res = next.run(req).await;
+        if !is_api || res.is_empty() == Some(false) {
+            return Ok(res);
+        }
+        let e = match res.status() as u16 {
+            404 => ApiError::not_found("no such endpoint"),
+            400 => ApiError::invalid_param(match res.error() {
+                Some(e) => e.to_string(),
+                None => "bad request".to_string(),
+            }),
+            status if status >= 500 => ApiError::internal_error(match res.error() {
+                Some(e) => e.to_string(),
+                None => "internal error".to_string(),
+            }),
+            _ => return Ok(res),
+        };
+        let mut out = failure(e)?;
+        out.set_status(res.status());
+        Ok(out)
+    }
+}
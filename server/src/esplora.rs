@@ -0,0 +1,472 @@
+//! Compatibility layer with Blockstream Esplora REST API, under `/esplora/`,
+//! for the tools that speak it.
+//!
+//! Blocks and transactions come from the node, address histories and spent outputs
+//! from the index. Errors are sent as plain text with the status, as Esplora does.
+
+use crate::decode;
+use crate::error::{respond, ApiError, ErrorCode};
+use crate::httpcache::{with_freshness, Freshness};
+use crate::index;
+use crate::network;
+use crate::routes::Handler;
+use crate::rpc;
+use crate::script::{self, ScriptType};
+use crate::State;
+use bitcoin::hashes::hex::{FromHex, ToHex};
+use bitcoincore_rpc_json as json;
+use json::bitcoin;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use tide::{Request, Response};
+
+pub const PREFIX: &str = "/esplora/";
+
+// confirmed transactions on one page of the address history, as in Esplora
+const ADDRESS_PAGE: usize = 25;
+// confirmation targets of `/fee-estimates`, in blocks
+const FEE_TARGETS: &[u16] = &[
+    1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 144,
+    504, 1008,
+];
+
+/// Confirmation status of the transaction
+#[derive(Debug, Clone, Serialize)]
+pub struct Status {
+    pub confirmed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_height: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_hash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_time: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Output {
+    pub scriptpubkey: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scriptpubkey_asm: Option<String>,
+    pub scriptpubkey_type: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scriptpubkey_address: Option<String>,
+    pub value: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Input {
+    pub txid: String,
+    pub vout: u32,
+    // output spent by the input, null for coinbase and outputs that are not indexed yet
+    pub prevout: Option<Output>,
+    pub scriptsig: String,
+    pub scriptsig_asm: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub witness: Vec<String>,
+    pub is_coinbase: bool,
+    pub sequence: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Tx {
+    pub txid: String,
+    pub version: u32,
+    pub locktime: u32,
+    pub vin: Vec<Input>,
+    pub vout: Vec<Output>,
+    pub size: usize,
+    pub weight: usize,
+    // known when all spent outputs are indexed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fee: Option<u64>,
+    pub status: Status,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Block {
+    pub id: String,
+    pub height: usize,
+    pub version: i32,
+    pub timestamp: usize,
+    pub tx_count: usize,
+    pub size: usize,
+    pub weight: usize,
+    pub merkle_root: String,
+    pub previousblockhash: Option<String>,
+    pub mediantime: Option<usize>,
+    pub nonce: u32,
+    pub bits: u32,
+    pub difficulty: f64,
+}
+
+fn output(state: &State, vout: &json::GetRawTransactionResultVout) -> Output {
+    let script = bitcoin::Script::from(vout.script_pub_key.hex.clone());
+    let chain = network::address_network(state.network);
+    Output {
+        scriptpubkey: vout.script_pub_key.hex.to_hex(),
+        scriptpubkey_asm: Some(vout.script_pub_key.asm.clone()),
+        scriptpubkey_type: ScriptType::of(&script).esplora_name(),
+        scriptpubkey_address: bitcoin::Address::from_script(&script, chain).map(|a| a.to_string()),
+        value: vout.value.as_sat(),
+    }
+}
+
+/// output of the index, with the script it was stored with or the one of its address,
+/// none when neither is known
+fn prevout(found: &index::Prevout) -> Option<Output> {
+    let script = match (&found.script, &found.address) {
+        (Some(x), _) => bitcoin::Script::from(x.clone()),
+        (None, Some(a)) => bitcoin::Address::from_str(a).ok()?.script_pubkey(),
+        (None, None) => return None,
+    };
+    Some(Output {
+        scriptpubkey: script.as_bytes().to_hex(),
+        scriptpubkey_asm: Some(script::asm(&script, false)),
+        scriptpubkey_type: ScriptType::of(&script).esplora_name(),
+        scriptpubkey_address: found.address.clone(),
+        value: found.value as u64,
+    })
+}
+
+async fn status(state: &State, raw: &json::GetRawTransactionResult) -> Result<Status, ApiError> {
+    let hash = match raw.blockhash {
+        Some(x) => x,
+        None => {
+            return Ok(Status {
+                confirmed: false,
+                block_height: None,
+                block_hash: None,
+                block_time: None,
+            })
+        }
+    };
+    let header: json::GetBlockHeaderResult = state
+        .rpc_client
+        .call("getblockheader", vec![json!(hash.to_string()), json!(true)])
+        .await?;
+    Ok(Status {
+        confirmed: true,
+        block_height: Some(header.height as u64),
+        block_hash: Some(hash.to_string()),
+        block_time: Some(header.time as u64),
+    })
+}
+
+fn weight(raw: &[u8]) -> Option<usize> {
+    bitcoin::consensus::encode::deserialize::<bitcoin::Transaction>(raw)
+        .ok()
+        .map(|t| decode::weight(&t))
+}
+
+/// transaction of the node in Esplora shape, with the spent outputs from the index
+async fn tx(state: &State, raw: &json::GetRawTransactionResult) -> Result<Tx, ApiError> {
+    let spent: Vec<(Vec<u8>, u32)> = raw
+        .vin
+        .iter()
+        .filter_map(|vin| match (vin.txid, vin.vout) {
+            (Some(txid), Some(vout)) => Some((Vec::from_hex(&txid.to_string()).ok()?, vout)),
+            _ => None,
+        })
+        .collect();
+    // the transaction is still served without the spent outputs while the index is down
+    let prevouts = match index::prevouts(&state.pool, &spent).await {
+        Ok(x) => x,
+        Err(e) => {
+            tracing::warn!("esplora prevouts of {}: {}", raw.txid, e);
+            Default::default()
+        }
+    };
+    let vin: Vec<Input> = raw
+        .vin
+        .iter()
+        .map(|vin| {
+            let (scriptsig, scriptsig_asm) = match (&vin.coinbase, &vin.script_sig) {
+                (Some(coinbase), _) => (coinbase.to_hex(), String::new()),
+                (None, Some(sig)) => (sig.hex.to_hex(), sig.asm.clone()),
+                (None, None) => (String::new(), String::new()),
+            };
+            let txid = vin.txid.map(|x| x.to_string());
+            let vout = vin.vout.unwrap_or(u32::MAX);
+            Input {
+                prevout: txid
+                    .as_ref()
+                    .and_then(|t| prevouts.get(&(t.clone(), vout)))
+                    .and_then(prevout),
+                txid: txid.unwrap_or_else(|| "0".repeat(64)),
+                vout,
+                scriptsig,
+                scriptsig_asm,
+                witness: vin
+                    .txinwitness
+                    .iter()
+                    .flatten()
+                    .map(|w| w.to_hex())
+                    .collect(),
+                is_coinbase: vin.coinbase.is_some(),
+                sequence: vin.sequence,
+            }
+        })
+        .collect();
+    let vout: Vec<Output> = raw.vout.iter().map(|x| output(state, x)).collect();
+    let out_total: u64 = vout.iter().map(|x| x.value).sum();
+    let fee = if vin.iter().any(|x| x.is_coinbase) {
+        Some(0)
+    } else {
+        vin.iter()
+            .map(|x| x.prevout.as_ref().map(|p| p.value))
+            .sum::<Option<u64>>()
+            .map(|in_total| in_total.saturating_sub(out_total))
+    };
+    let weight = weight(&raw.hex).unwrap_or(raw.vsize * 4);
+    Ok(Tx {
+        txid: raw.txid.to_string(),
+        version: raw.version,
+        locktime: raw.locktime,
+        vin,
+        vout,
+        size: raw.size,
+        weight,
+        fee,
+        status: status(state, raw).await?,
+    })
+}
+
+/// error as plain text, as Esplora sends it
+fn fail(e: ApiError) -> tide::Result {
+    Ok(Response::builder(e.status())
+        .content_type(tide::http::mime::PLAIN)
+        .body(e.message)
+        .build())
+}
+
+fn text(body: String) -> tide::Result {
+    Ok(Response::builder(200)
+        .content_type(tide::http::mime::PLAIN)
+        .body(body)
+        .build())
+}
+
+fn param<'a>(req: &'a Request<State>, name: &str) -> Result<&'a str, ApiError> {
+    req.param(name)
+        .map_err(|e| ApiError::invalid_param(format!("missing {} param {}", name, e)))
+}
+
+async fn raw_tx(req: &Request<State>) -> Result<json::GetRawTransactionResult, ApiError> {
+    let txid = bitcoin::Txid::from_hex(param(req, "txid")?)
+        .map_err(|e| ApiError::invalid_param(format!("txid param parsing error {}", e)))?;
+    rpc::get_raw_transaction_info(req.state().rpc_client.clone(), txid).await
+}
+
+pub async fn block(req: Request<State>) -> tide::Result {
+    let hash = match param(&req, "hash").and_then(|x| {
+        bitcoin::BlockHash::from_hex(x)
+            .map_err(|e| ApiError::invalid_param(format!("hash param parsing error {}", e)))
+    }) {
+        Ok(x) => x,
+        Err(e) => return fail(e),
+    };
+    let rpcclient = req.state().rpc_client.clone();
+    let details = match rpc::get_block_info(rpcclient, hash, Default::default()).await {
+        Ok(x) => x,
+        Err(e) => return fail(e),
+    };
+    let b = &details.block;
+    let out = Block {
+        id: b.hash.to_string(),
+        height: b.height,
+        version: b.version,
+        timestamp: b.time,
+        tx_count: b.n_tx,
+        size: b.size,
+        weight: b.weight,
+        merkle_root: b.merkleroot.to_string(),
+        previousblockhash: b.previousblockhash.map(|x| x.to_string()),
+        mediantime: b.mediantime,
+        nonce: b.nonce,
+        bits: u32::from_str_radix(&b.bits, 16).unwrap_or(0),
+        difficulty: b.difficulty,
+    };
+    let freshness = Freshness::new(Some(b.confirmations.max(0) as u64), Some(b.time as u64));
+    with_freshness(respond(200, &out), freshness)
+}
+
+pub async fn block_height(req: Request<State>) -> tide::Result {
+    let height: u64 = match param(&req, "height").and_then(|x| {
+        x.parse()
+            .map_err(|e| ApiError::invalid_param(format!("height param parsing error {}", e)))
+    }) {
+        Ok(x) => x,
+        Err(e) => return fail(e),
+    };
+    let rpcclient = &req.state().rpc_client;
+    match rpcclient
+        .call::<String>("getblockhash", vec![json!(height)])
+        .await
+    {
+        Ok(hash) => text(hash),
+        // the node reports the height out of range as invalid parameter
+        Err(e) if e.code == ErrorCode::InvalidParam || e.code == ErrorCode::NotFound => {
+            fail(ApiError::not_found("Block not found"))
+        }
+        Err(e) => fail(e),
+    }
+}
+
+pub async fn transaction(req: Request<State>) -> tide::Result {
+    let raw = match raw_tx(&req).await {
+        Ok(x) => x,
+        Err(e) => return fail(e),
+    };
+    let out = match tx(req.state(), &raw).await {
+        Ok(x) => x,
+        Err(e) => return fail(e),
+    };
+    // the missing spent outputs show up once they are indexed
+    let complete =
+        out.fee.is_some() && out.vin.iter().all(|x| x.is_coinbase || x.prevout.is_some());
+    let freshness = if complete {
+        Freshness::new(raw.confirmations.map(|x| x as u64), None)
+    } else {
+        Freshness::default()
+    };
+    with_freshness(respond(200, &out), freshness)
+}
+
+pub async fn transaction_status(req: Request<State>) -> tide::Result {
+    let raw = match raw_tx(&req).await {
+        Ok(x) => x,
+        Err(e) => return fail(e),
+    };
+    match status(req.state(), &raw).await {
+        Ok(x) => respond(200, &x),
+        Err(e) => fail(e),
+    }
+}
+
+/// page of the address history, after the `last_seen` transaction when it is given
+async fn address_page(req: &Request<State>, last_seen: Option<&str>) -> Result<Vec<Tx>, ApiError> {
+    let state = req.state();
+    let address = network::parse_address(state.network, param(req, "address")?)?;
+    let before = match last_seen {
+        Some(txid) => {
+            let hash = Vec::from_hex(txid).map_err(|e| {
+                ApiError::invalid_param(format!("last_seen_txid param parsing error {}", e))
+            })?;
+            match index::tx_position(&state.pool, &hash).await? {
+                Some(x) => Some(x),
+                None => return Err(ApiError::not_found("last_seen_txid is not indexed")),
+            }
+        }
+        None => None,
+    };
+    let refs = index::address_txs(&state.pool, &address.to_string(), before, ADDRESS_PAGE).await?;
+    let mut out = Vec::with_capacity(refs.len());
+    for r in refs.iter() {
+        let txid = bitcoin::Txid::from_hex(&r.tx_hash)
+            .map_err(|e| ApiError::upstream_unavailable(e.to_string()))?;
+        let raw = rpc::get_raw_transaction_info(state.rpc_client.clone(), txid).await?;
+        out.push(tx(state, &raw).await?);
+    }
+    Ok(out)
+}
+
+pub async fn address_txs(req: Request<State>) -> tide::Result {
+    match address_page(&req, None).await {
+        Ok(x) => respond(200, &x),
+        Err(e) => fail(e),
+    }
+}
+
+pub async fn address_txs_chain(req: Request<State>) -> tide::Result {
+    let last_seen = match param(&req, "last_seen_txid") {
+        Ok(x) => x.to_string(),
+        Err(e) => return fail(e),
+    };
+    match address_page(&req, Some(&last_seen)).await {
+        Ok(x) => respond(200, &x),
+        Err(e) => fail(e),
+    }
+}
+
+pub async fn tip_height(req: Request<State>) -> tide::Result {
+    match rpc::get_blockchain_info(req.state().rpc_client.clone()).await {
+        Ok(x) => text(x.blocks.to_string()),
+        Err(e) => fail(e),
+    }
+}
+
+pub async fn tip_hash(req: Request<State>) -> tide::Result {
+    match rpc::get_blockchain_info(req.state().rpc_client.clone()).await {
+        Ok(x) => text(x.best_block_hash.to_string()),
+        Err(e) => fail(e),
+    }
+}
+
+/// fee rates by confirmation target, in sat/vB
+pub async fn fee_estimates(req: Request<State>) -> tide::Result {
+    let calls = FEE_TARGETS
+        .iter()
+        .map(|target| ("estimatesmartfee", vec![json!(target)]))
+        .collect();
+    let replies = match req.state().rpc_client.call_batch(calls).await {
+        Ok(x) => x,
+        Err(e) => return fail(e),
+    };
+    let mut out: BTreeMap<u16, f64> = BTreeMap::new();
+    for (target, reply) in FEE_TARGETS.iter().zip(replies.iter()) {
+        // BTC per kvB, missing while the node has no estimate
+        if let Some(rate) = reply.get("feerate").and_then(Value::as_f64) {
+            out.insert(*target, rate * 100_000.0);
+        }
+    }
+    respond(200, &out)
+}
+
+/// Route of Esplora API, all of them are GET
+pub struct Route {
+    pub path: &'static str,
+    pub handler: Handler,
+}
+
+pub fn routes() -> Vec<Route> {
+    vec![
+        Route {
+            path: "/esplora/block/:hash",
+            handler: |req| Box::pin(block(req)),
+        },
+        Route {
+            path: "/esplora/block-height/:height",
+            handler: |req| Box::pin(block_height(req)),
+        },
+        Route {
+            path: "/esplora/tx/:txid",
+            handler: |req| Box::pin(transaction(req)),
+        },
+        Route {
+            path: "/esplora/tx/:txid/status",
+            handler: |req| Box::pin(transaction_status(req)),
+        },
+        Route {
+            path: "/esplora/address/:address/txs",
+            handler: |req| Box::pin(address_txs(req)),
+        },
+        Route {
+            path: "/esplora/address/:address/txs/chain/:last_seen_txid",
+            handler: |req| Box::pin(address_txs_chain(req)),
+        },
+        Route {
+            path: "/esplora/blocks/tip/height",
+            handler: |req| Box::pin(tip_height(req)),
+        },
+        Route {
+            path: "/esplora/blocks/tip/hash",
+            handler: |req| Box::pin(tip_hash(req)),
+        },
+        Route {
+            path: "/esplora/fee-estimates",
+            handler: |req| Box::pin(fee_estimates(req)),
+        },
+    ]
+}
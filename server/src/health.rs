@@ -0,0 +1,266 @@
+use crate::telemetry;
+use crate::State;
+use async_std::future::timeout;
+use explorer_types::ChainInfo;
+use serde::Serialize;
+use std::time::{Duration, Instant};
+use tide::{Request, StatusCode};
+use tracing_futures::Instrument;
+
+// time limit of every readiness check, the probe is not retried
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Ok,
+    Fail,
+}
+
+/// Result of the single check of the dependency
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    pub status: Status,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    // time spent on the check, in milliseconds
+    pub took_ms: u128,
+    #[serde(skip_serializing_if = "serde_json::Value::is_null")]
+    pub details: serde_json::Value,
+}
+
+impl Check {
+    fn ok(start: Instant, details: serde_json::Value) -> Self {
+        Self {
+            status: Status::Ok,
+            message: None,
+            took_ms: start.elapsed().as_millis(),
+            details,
+        }
+    }
+
+    fn fail(start: Instant, message: impl ToString, details: serde_json::Value) -> Self {
+        Self {
+            status: Status::Fail,
+            message: Some(message.to_string()),
+            took_ms: start.elapsed().as_millis(),
+            details,
+        }
+    }
+}
+
+/// Report returned by `/healthz`
+#[derive(Debug, Clone, Serialize)]
+pub struct Liveness {
+    pub status: Status,
+    // the server is shutting down and should not get new requests
+    pub draining: bool,
+}
+
+/// Report returned by `/readyz`
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub status: Status,
+    // the server is shutting down and should not get new requests
+    pub draining: bool,
+    pub database: Check,
+    pub node: Check,
+    pub sync: Check,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub indexer: Option<Check>,
+}
+
+impl Report {
+    fn new(
+        draining: bool,
+        database: Check,
+        node: Check,
+        sync: Check,
+        indexer: Option<Check>,
+    ) -> Self {
+        let failed = draining
+            || [&database, &node, &sync]
+                .iter()
+                .chain(indexer.as_ref().iter())
+                .any(|c| c.status == Status::Fail);
+        Self {
+            status: if failed { Status::Fail } else { Status::Ok },
+            draining,
+            database,
+            node,
+            sync,
+            indexer,
+        }
+    }
+}
+
+/// height of the last block saved by the indexer
+pub async fn indexed_height(pool: &sqlx::PgPool) -> Result<i64, sqlx::Error> {
+    let sql = "SELECT COALESCE(MAX(blockheight),0) FROM final_blocks";
+    let row: (i32,) = sqlx::query_as(sql)
+        .fetch_one(pool)
+        .instrument(telemetry::sql_span(sql))
+        .await?;
+    Ok(row.0 as i64)
+}
+
+/// runs the query, failing if it takes longer than `CHECK_TIMEOUT`
+async fn within<T>(
+    query: impl std::future::Future<Output = Result<T, sqlx::Error>>,
+) -> Result<T, String> {
+    match timeout(CHECK_TIMEOUT, query).await {
+        Ok(Ok(x)) => Ok(x),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err(format!("no reply in {:?}", CHECK_TIMEOUT)),
+    }
+}
+
+async fn check_database(pool: &sqlx::PgPool) -> Check {
+    let start = Instant::now();
+    let sql = "SELECT 1";
+    let query = sqlx::query(sql)
+        .execute(pool)
+        .instrument(telemetry::sql_span(sql));
+    match within(query).await {
+        Ok(_) => Check::ok(
+            start,
+            serde_json::json!({
+                "connections": pool.size(),
+                "idle": pool.num_idle(),
+            }),
+        ),
+        Err(e) => Check::fail(start, e, serde_json::Value::Null),
+    }
+}
+
+async fn check_node(state: &State) -> (Check, Option<ChainInfo>) {
+    let start = Instant::now();
+    // bypasses the cache and the retries: the check is about the node being reachable now
+    match state
+        .rpc_client
+        .call_once::<ChainInfo>("getblockchaininfo", CHECK_TIMEOUT)
+        .await
+    {
+        Ok(chain) => {
+            let details = serde_json::json!({
+                "chain": chain.chain,
+                "blocks": chain.blocks,
+                "headers": chain.headers,
+                "nodes": nodes(state),
+            });
+            if chain.chain != state.network.chain() {
+                let message = format!(
+                    "node is on {} chain, {} is configured",
+                    chain.chain, state.network
+                );
+                return (Check::fail(start, message, details), None);
+            }
+            (Check::ok(start, details), Some(chain))
+        }
+        Err(e) => {
+            let details = serde_json::json!({ "nodes": nodes(state) });
+            (Check::fail(start, e, details), None)
+        }
+    }
+}
+
+/// state of every node, as seen by the last probe
+fn nodes(state: &State) -> serde_json::Value {
+    let nodes: Vec<serde_json::Value> = state
+        .rpc_client
+        .nodes
+        .nodes()
+        .iter()
+        .map(|n| {
+            serde_json::json!({
+                "addr": n.addr,
+                "up": !n.is_down(),
+                "height": n.height(),
+            })
+        })
+        .collect();
+    serde_json::Value::Array(nodes)
+}
+
+fn check_sync(chain: Option<&ChainInfo>) -> Check {
+    let start = Instant::now();
+    let chain = match chain {
+        Some(x) => x,
+        None => return Check::fail(start, "node is unreachable", serde_json::Value::Null),
+    };
+    let details = serde_json::json!({
+        "initialblockdownload": chain.initialblockdownload,
+        "verificationprogress": chain.verificationprogress,
+    });
+    if chain.initialblockdownload {
+        Check::fail(start, "node is in initial block download", details)
+    } else {
+        Check::ok(start, details)
+    }
+}
+
+async fn check_indexer(pool: &sqlx::PgPool, chain: Option<&ChainInfo>, max_lag: u32) -> Check {
+    let start = Instant::now();
+    let height = match within(indexed_height(pool)).await {
+        Ok(x) => x,
+        Err(e) => return Check::fail(start, e, serde_json::Value::Null),
+    };
+    let chain = match chain {
+        Some(x) => x,
+        None => {
+            let details = serde_json::json!({ "height": height });
+            return Check::fail(start, "node is unreachable", details);
+        }
+    };
+    let lag = chain.blocks as i64 - height;
+    let details = serde_json::json!({
+        "height": height,
+        "lag": lag,
+        "max_lag": max_lag,
+    });
+    if lag > max_lag as i64 {
+        Check::fail(start, format!("indexer is {} blocks behind", lag), details)
+    } else {
+        Check::ok(start, details)
+    }
+}
+
+/// runs all checks concurrently, each of them once and within `CHECK_TIMEOUT`
+pub async fn report(state: &State) -> Report {
+    let (database, (node, chain)) = futures::join!(check_database(&state.pool), check_node(state));
+    let sync = check_sync(chain.as_ref());
+    let indexer = match state.indexer_max_lag {
+        Some(max_lag) => Some(check_indexer(&state.pool, chain.as_ref(), max_lag).await),
+        None => None,
+    };
+    let draining = state.shutdown.is_draining();
+    Report::new(draining, database, node, sync, indexer)
+}
+
+fn respond(status: StatusCode, report: &impl Serialize) -> tide::Result {
+    let mut res = tide::Response::new(status);
+    res.insert_header("Cache-Control", "no-store");
+    res.set_body(tide::Body::from_json(report)?);
+    Ok(res)
+}
+
+/// `GET /healthz` - liveness, the process is serving requests.
+/// Doesn't touch the dependencies, so the orchestrator doesn't restart
+/// the server when the node or the database is down or slow.
+pub async fn healthz(req: Request<State>) -> tide::Result {
+    let report = Liveness {
+        status: Status::Ok,
+        draining: req.state().shutdown.is_draining(),
+    };
+    respond(StatusCode::Ok, &report)
+}
+
+/// `GET /readyz` - readiness, all dependencies are usable
+pub async fn readyz(req: Request<State>) -> tide::Result {
+    let report = report(req.state()).await;
+    let status = match report.status {
+        Status::Ok => StatusCode::Ok,
+        Status::Fail => StatusCode::ServiceUnavailable,
+    };
+    respond(status, &report)
+}
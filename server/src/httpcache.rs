@@ -0,0 +1,118 @@
+use crate::State;
+use bitcoin::hashes::hex::ToHex;
+use bitcoin::hashes::{sha256, Hash};
+use bitcoincore_rpc_json::bitcoin;
+use tide::http::Method;
+use tide::{Body, Next, Request, StatusCode};
+
+/// How fresh is the data in the response, attached by handlers as extension
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Freshness {
+    // confirmations of the block the data belongs to, `None` for the tip data
+    pub confirmations: Option<u64>,
+    // unix time of the block the data belongs to
+    pub modified: Option<u64>,
+}
+
+impl Freshness {
+    pub fn new(confirmations: Option<u64>, modified: Option<u64>) -> Self {
+        Self {
+            confirmations,
+            modified,
+        }
+    }
+}
+
+/// attaches freshness to the successful response of the handler
+pub fn with_freshness(res: tide::Result, freshness: Freshness) -> tide::Result {
+    let mut res = res?;
+    if res.status() == StatusCode::Ok {
+        res.insert_ext(freshness);
+    }
+    Ok(res)
+}
+
+/// formats unix time as HTTP date, i.e. `Sun, 06 Nov 1994 08:49:37 GMT`
+fn http_date(unix: u64) -> String {
+    const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let days = (unix / 86400) as i64;
+    let secs = unix % 86400;
+    // civil date from days since 1970-01-01, proleptic Gregorian calendar
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        WEEKDAYS[(days % 7) as usize],
+        day,
+        MONTHS[(month - 1) as usize],
+        year,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60,
+    )
+}
+
+/// Sets `ETag`, `Last-Modified` and `Cache-Control` on API responses
+/// that are marked with `Freshness`, and replies 304 to matching `If-None-Match`.
+/// Responses about deeply confirmed blocks are cached for long, the tip data - briefly,
+/// so the bodies carry no counts relative to the tip, i.e. confirmations.
+#[derive(Default)]
+pub struct Middleware {}
+
+#[tide::utils::async_trait]
+impl tide::Middleware<State> for Middleware {
+    async fn handle(&self, req: Request<State>, next: Next<'_, State>) -> tide::Result {
+        let is_get = req.method() == Method::Get;
+        let if_none_match = req.header("If-None-Match").map(|h| h.as_str().to_string());
+        let cache = req.state().rpc_client.cache.clone();
+        let mut res = next.run(req).await;
+
+        let freshness = match res.ext::<Freshness>() {
+            Some(x) if is_get && res.status() == StatusCode::Ok => *x,
+            _ => return Ok(res),
+        };
+        let body = res.take_body();
+        let mime = body.mime().clone();
+        let bytes = body.into_bytes().await?;
+
+        // the same body has the same tag on every instance and after restarts
+        let etag = format!("W/\"{}\"", sha256::Hash::hash(&bytes).into_inner().to_hex());
+        let (ttl, _) = cache.ttl(freshness.confirmations);
+
+        res.insert_header("ETag", etag.as_str());
+        res.insert_header(
+            "Cache-Control",
+            format!("public, max-age={}", ttl.as_secs()).as_str(),
+        );
+        if let Some(modified) = freshness.modified {
+            res.insert_header("Last-Modified", http_date(modified).as_str());
+        }
+
+        let matches = match if_none_match {
+            Some(x) => x
+                .split(',')
+                .map(|t| t.trim())
+                .any(|t| t == "*" || t.trim_start_matches("W/") == etag.trim_start_matches("W/")),
+            None => false,
+        };
+        if matches {
+            res.set_status(StatusCode::NotModified);
+            res.set_body(Body::empty());
+        } else {
+            let mut body = Body::from(bytes);
+            body.set_mime(mime);
+            res.set_body(body);
+        }
+        Ok(res)
+    }
+}
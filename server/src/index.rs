@@ -0,0 +1,281 @@
+//! Queries of the outputs index that is built by the indexer in `final_outputs` table
+
+use crate::error::ApiError;
+use crate::telemetry;
+use bitcoin::hashes::hex::ToHex;
+use bitcoincore_rpc_json::bitcoin;
+use serde::Serialize;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use tracing_futures::Instrument;
+
+// transactions of the script that are returned at most,
+// longer histories are refused rather than loaded into memory
+pub const MAX_HISTORY: usize = 10_000;
+
+/// Transaction in the history of the script
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HistoryItem {
+    pub tx_hash: String,
+    pub height: i32,
+}
+
+/// Unspent output of the script
+#[derive(Debug, Clone, Serialize)]
+pub struct Utxo {
+    pub tx_hash: String,
+    pub tx_pos: i32,
+    pub height: i32,
+    pub value: i64,
+}
+
+/// Error of the index query
+#[derive(Debug)]
+pub enum Error {
+    Db(sqlx::Error),
+    TooLarge,
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Db(e) => write!(f, "index query failed: {}", e),
+            Error::TooLarge => write!(f, "history has more than {} transactions", MAX_HISTORY),
+        }
+    }
+}
+
+impl From<sqlx::Error> for Error {
+    fn from(e: sqlx::Error) -> Self {
+        Error::Db(e)
+    }
+}
+
+/// transactions funding or spending the script, oldest first in the order of the chain
+pub async fn history(pool: &PgPool, scripthash: &[u8]) -> Result<Vec<HistoryItem>, Error> {
+    let sql = "SELECT o.txhash, o.blockheight, t.txindex FROM final_outputs o \
+            JOIN final_tx t ON t.txhash = o.txhash \
+            WHERE o.scripthash = $1 \
+        UNION \
+        SELECT o.spent_txhash, o.spent_height, t.txindex FROM final_outputs o \
+            JOIN final_tx t ON t.txhash = o.spent_txhash \
+            WHERE o.scripthash = $1 AND o.spent_txhash IS NOT NULL \
+        ORDER BY 2, 3 LIMIT $2";
+    let rows: Vec<(Vec<u8>, i32, i32)> = sqlx::query_as(sql)
+        .bind(scripthash)
+        .bind(MAX_HISTORY as i64 + 1)
+        .fetch_all(pool)
+        .instrument(telemetry::sql_span(sql))
+        .await?;
+    if rows.len() > MAX_HISTORY {
+        return Err(Error::TooLarge);
+    }
+    Ok(rows
+        .into_iter()
+        .map(|(txhash, height, _)| HistoryItem {
+            tx_hash: txhash.to_hex(),
+            height,
+        })
+        .collect())
+}
+
+/// scripts among the given ones that are funded or spent in the blocks above the height
+pub async fn touched(
+    pool: &PgPool,
+    scripthashes: &[Vec<u8>],
+    above: i32,
+) -> Result<Vec<Vec<u8>>, Error> {
+    if scripthashes.is_empty() {
+        return Ok(vec![]);
+    }
+    let sql = "SELECT DISTINCT scripthash FROM final_outputs \
+        WHERE scripthash = ANY($1) AND (blockheight > $2 OR spent_height > $2)";
+    let rows: Vec<(Vec<u8>,)> = sqlx::query_as(sql)
+        .bind(scripthashes)
+        .bind(above)
+        .fetch_all(pool)
+        .instrument(telemetry::sql_span(sql))
+        .await?;
+    Ok(rows.into_iter().map(|x| x.0).collect())
+}
+
+/// sum of the unspent outputs of the script, in satoshi
+pub async fn balance(pool: &PgPool, scripthash: &[u8]) -> Result<i64, Error> {
+    let sql = "SELECT COALESCE(SUM(value), 0)::bigint FROM final_outputs \
+        WHERE scripthash = $1 AND spent_txhash IS NULL";
+    let row: (i64,) = sqlx::query_as(sql)
+        .bind(scripthash)
+        .fetch_one(pool)
+        .instrument(telemetry::sql_span(sql))
+        .await?;
+    Ok(row.0)
+}
+
+/// unspent outputs of the script, oldest first
+pub async fn unspent(pool: &PgPool, scripthash: &[u8]) -> Result<Vec<Utxo>, Error> {
+    let sql = "SELECT txhash, vout, blockheight, value FROM final_outputs \
+        WHERE scripthash = $1 AND spent_txhash IS NULL \
+        ORDER BY blockheight, txhash, vout LIMIT $2";
+    let rows: Vec<(Vec<u8>, i32, i32, i64)> = sqlx::query_as(sql)
+        .bind(scripthash)
+        .bind(MAX_HISTORY as i64 + 1)
+        .fetch_all(pool)
+        .instrument(telemetry::sql_span(sql))
+        .await?;
+    if rows.len() > MAX_HISTORY {
+        return Err(Error::TooLarge);
+    }
+    Ok(rows
+        .into_iter()
+        .map(|(txhash, vout, height, value)| Utxo {
+            tx_hash: txhash.to_hex(),
+            tx_pos: vout,
+            height,
+            value,
+        })
+        .collect())
+}
+
+/// Transaction in the history of the address, with its position in the chain
+#[derive(Debug, Clone, PartialEq)]
+pub struct TxRef {
+    pub tx_hash: String,
+    pub height: i32,
+    pub txindex: i32,
+}
+
+/// Output spent by the input, as it is in the index
+#[derive(Debug, Clone)]
+pub struct Prevout {
+    pub address: Option<String>,
+    /// script of the output, none in the rows indexed before it was stored
+    pub script: Option<Vec<u8>>,
+    pub value: i64,
+}
+
+/// Input spending the output, as it is in the index
+#[derive(Debug, Clone)]
+pub struct Spending {
+    pub tx_hash: String,
+    pub vin: i32,
+    pub height: Option<i32>,
+}
+
+/// height and index in the block of the final transaction, none if it is not indexed
+pub async fn tx_position(pool: &PgPool, txhash: &[u8]) -> Result<Option<(i32, i32)>, Error> {
+    let sql = "SELECT blockheight, txindex FROM final_tx WHERE txhash = $1";
+    let row: Option<(i32, i32)> = sqlx::query_as(sql)
+        .bind(txhash)
+        .fetch_optional(pool)
+        .instrument(telemetry::sql_span(sql))
+        .await?;
+    Ok(row)
+}
+
+/// transactions funding or spending the address, newest first,
+/// the ones before the `before` position in the chain when it is given
+pub async fn address_txs(
+    pool: &PgPool,
+    address: &str,
+    before: Option<(i32, i32)>,
+    limit: usize,
+) -> Result<Vec<TxRef>, Error> {
+    let sql = "SELECT txhash, blockheight, txindex FROM ( \
+            SELECT txhash, blockheight, txindex FROM final_outputs WHERE address = $1 \
+            UNION \
+            SELECT o.spent_txhash, o.spent_height, t.txindex FROM final_outputs o \
+                JOIN final_tx t ON t.txhash = o.spent_txhash \
+                WHERE o.address = $1 \
+        ) h WHERE (blockheight, txindex) < ($2, $3) \
+        ORDER BY blockheight DESC, txindex DESC LIMIT $4";
+    let (height, txindex) = before.unwrap_or((i32::MAX, i32::MAX));
+    let rows: Vec<(Vec<u8>, i32, i32)> = sqlx::query_as(sql)
+        .bind(address)
+        .bind(height)
+        .bind(txindex)
+        .bind(limit as i64)
+        .fetch_all(pool)
+        .instrument(telemetry::sql_span(sql))
+        .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(txhash, height, txindex)| TxRef {
+            tx_hash: txhash.to_hex(),
+            height,
+            txindex,
+        })
+        .collect())
+}
+
+// transaction hash, output index, address, script and value
+type PrevoutRow = (Vec<u8>, i32, Option<String>, Option<Vec<u8>>, i64);
+
+/// indexed outputs spent by the inputs, keyed by the transaction hash and the output index.
+/// Outputs that are not indexed yet are missing
+pub async fn prevouts(
+    pool: &PgPool,
+    inputs: &[(Vec<u8>, u32)],
+) -> Result<HashMap<(String, u32), Prevout>, Error> {
+    if inputs.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let sql = "SELECT txhash, vout, address, script, value FROM final_outputs \
+        WHERE txhash = ANY($1)";
+    let hashes: Vec<Vec<u8>> = inputs.iter().map(|(hash, _)| hash.clone()).collect();
+    let rows: Vec<PrevoutRow> = sqlx::query_as(sql)
+        .bind(hashes)
+        .fetch_all(pool)
+        .instrument(telemetry::sql_span(sql))
+        .await?;
+    // other outputs of the same transactions are loaded as well
+    Ok(rows
+        .into_iter()
+        .filter(|(txhash, vout, _, _, _)| inputs.contains(&(txhash.clone(), *vout as u32)))
+        .map(|(txhash, vout, address, script, value)| {
+            let found = Prevout {
+                address,
+                script,
+                value,
+            };
+            ((txhash.to_hex(), vout as u32), found)
+        })
+        .collect())
+}
+
+// output index, spending transaction hash, input index and height
+type SpendingRow = (i32, Option<Vec<u8>>, Option<i32>, Option<i32>);
+
+/// indexed outputs of the transaction, keyed by the output index,
+/// with the input spending them or none while they are unspent
+pub async fn spendings(
+    pool: &PgPool,
+    txhash: &[u8],
+) -> Result<HashMap<u32, Option<Spending>>, Error> {
+    let sql = "SELECT vout, spent_txhash, spent_vin, spent_height FROM final_outputs \
+        WHERE txhash = $1";
+    let rows: Vec<SpendingRow> = sqlx::query_as(sql)
+        .bind(txhash)
+        .fetch_all(pool)
+        .instrument(telemetry::sql_span(sql))
+        .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(vout, spent_txhash, spent_vin, spent_height)| {
+            let spending = spent_txhash.map(|hash| Spending {
+                tx_hash: hash.to_hex(),
+                vin: spent_vin.unwrap_or_default(),
+                height: spent_height,
+            });
+            (vout as u32, spending)
+        })
+        .collect())
+}
+
+impl From<Error> for ApiError {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::TooLarge => ApiError::invalid_param(e.to_string()),
+            Error::Db(_) => ApiError::upstream_unavailable(e.to_string()),
+        }
+    }
+}
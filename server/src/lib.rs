@@ -0,0 +1,168 @@
+//! Bitcoin explorer HTTP API server, its binary is `main.rs`.
+//! Exposed as a library for the tests that run it in-process.
+
+pub mod api;
+pub mod args;
+pub mod breaker;
+pub mod broadcast;
+pub mod cache;
+pub mod decode;
+pub mod dist;
+pub mod electrum;
+pub mod esplora;
+pub mod error;
+pub mod health;
+pub mod httpcache;
+pub mod index;
+pub mod metrics;
+pub mod network;
+pub mod node;
+pub mod openapi;
+pub mod pager;
+pub mod ratelimit;
+pub mod routes;
+pub mod rpc;
+pub mod script;
+pub mod security;
+pub mod shutdown;
+pub mod singleflight;
+pub mod telemetry;
+pub mod tls;
+pub mod txdetail;
+pub mod types;
+
+use explorer_types::Network;
+use sqlx::Executor;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Clone)]
+pub struct State {
+    pub network: Network,
+    pub pool: sqlx::Pool<sqlx::postgres::Postgres>,
+    pub static_dir: String,
+    pub rpc_client: rpc::Client,
+    pub metrics: metrics::Metrics,
+    // lag of the indexer allowed by the readiness check, if the index is used
+    pub indexer_max_lag: Option<u32>,
+    // max size of the broadcast raw transaction, in bytes
+    pub broadcast_max_size: usize,
+    pub shutdown: shutdown::Shutdown,
+}
+
+impl State {
+    /// state of the primary network
+    pub async fn from_args(src: &args::Args) -> anyhow::Result<State> {
+        let endpoint = network::Endpoint::primary(src);
+        Self::for_endpoint(
+            src,
+            &endpoint,
+            metrics::Metrics::default(),
+            shutdown::Shutdown::default(),
+        )
+        .await
+    }
+
+    /// states of the other networks in `--networks`,
+    /// sharing the metrics and the shutdown with the primary one
+    pub async fn networks(&self, src: &args::Args) -> anyhow::Result<Vec<State>> {
+        let mut out = vec![];
+        for endpoint in network::endpoints(src)?.iter().skip(1) {
+            let state =
+                Self::for_endpoint(src, endpoint, self.metrics.clone(), self.shutdown.clone())
+                    .await?;
+            out.push(state);
+        }
+        Ok(out)
+    }
+
+    async fn for_endpoint(
+        src: &args::Args,
+        endpoint: &network::Endpoint,
+        metrics: metrics::Metrics,
+        shutdown: shutdown::Shutdown,
+    ) -> anyhow::Result<State> {
+        let network = endpoint.network;
+        let mut options = sqlx::postgres::PgPoolOptions::new()
+            .max_connections(src.database_conn)
+            .connect_timeout(Duration::from_secs(3));
+        if network != Network::Mainnet {
+            // every network has its index in its own schema of the same database
+            let sql = format!("SET search_path TO {}", network.schema());
+            options = options.after_connect(move |conn| {
+                let sql = sql.clone();
+                Box::pin(async move {
+                    conn.execute(sql.as_str()).await?;
+                    Ok(())
+                })
+            });
+        }
+        // connections are opened on demand, so the server starts
+        // while the database is down and reports it with `/readyz`
+        let pool = options.connect_lazy(src.database_url.as_str())?;
+        if let Err(e) = pool.acquire().await {
+            tracing::warn!("Database connection failure ({}): {}", network, e);
+        };
+        let uses_index = matches!(src.blocks, args::BlocksSource::DB)
+            || matches!(src.tx, args::TxSource::DB);
+        let policy = cache::Policy {
+            tip_ttl: Duration::from_secs(src.cache_tip_ttl),
+            deep_ttl: Duration::from_secs(src.cache_deep_ttl),
+        };
+        let mut cache = cache::Cache::new(src.cache_size, policy);
+        if let args::CacheBackend::POSTGRES = src.cache_backend {
+            cache = cache.with_shared(Arc::new(cache::Postgres::new(pool.clone())));
+        }
+        let mut rpc_client = if endpoint.rpc_cookie.is_empty() {
+            rpc::Client::new(
+                &endpoint.rpc_addr,
+                &endpoint.rpc_username,
+                &endpoint.rpc_password,
+            )
+        } else {
+            rpc::Client::new(&endpoint.rpc_addr, "", "").with_cookie(&endpoint.rpc_cookie)
+        };
+        rpc_client = rpc_client
+            .with_timeout(Duration::from_secs(src.rpc_timeout))
+            .with_cache(cache)
+            .with_metrics(metrics.clone());
+        Ok(Self {
+            network,
+            pool,
+            static_dir: src.static_dir.clone(),
+            rpc_client,
+            metrics,
+            indexer_max_lag: if uses_index {
+                Some(src.indexer_max_lag)
+            } else {
+                None
+            },
+            broadcast_max_size: src.broadcast_max_size,
+            shutdown,
+        })
+    }
+}
+
+/// HTTP server with the middleware of the API and the routes,
+/// `all` are the states of every served network, the primary one first
+pub fn app(src: &args::Args, state: State, all: &[State]) -> anyhow::Result<tide::Server<State>> {
+    let limiter = ratelimit::Limiter::from_args(src, state.pool.clone())?;
+    let shutdown = state.shutdown.clone();
+    let mut app = tide::with_state(state);
+    app.with(shutdown::Middleware::new(shutdown));
+    app.with(telemetry::TraceMiddleware::new());
+    if let Some(cors) = security::cors(src)? {
+        app.with(cors);
+    }
+    app.with(security::Headers::from_args(src));
+    app.with(metrics::Middleware::default());
+    app.with(ratelimit::Middleware::new(limiter));
+    app.with(error::Envelope);
+    app.with(httpcache::Middleware::default());
+    app.with(dist::Middleware {});
+    routes::register(&mut app);
+    for network in all.iter() {
+        routes::register_network(&mut app, network.clone());
+    }
+    Ok(app)
+}
@@ -1,58 +1,88 @@
-pub mod api;
-pub mod args;
-pub mod dist;
-pub mod pager;
-pub mod rpc;
-pub mod telemetry;
-pub mod types;
-
-#[derive(Clone)]
-pub struct State {
-    pub pool: sqlx::Pool<sqlx::postgres::Postgres>,
-    pub static_dir: String,
-    pub rpc_client: rpc::Client,
-}
-
-impl State {
-    pub async fn from_args(src: &args::Args) -> State {
-        let pool = sqlx::postgres::PgPoolOptions::new()
-            .max_connections(src.database_conn)
-            .connect_timeout(std::time::Duration::from_secs(3))
-            .connect(src.database_url.as_str())
-            .await
-            .unwrap();
-        if let Err(e) = pool.acquire().await {
-            panic!("Database connection failure {} url={}", e, src.database_url);
-        };
-        Self {
-            pool,
-            static_dir: src.static_dir.clone(),
-            rpc_client: rpc::Client::new(&src.rpc_addr, &src.rpc_username, &src.rpc_password),
-        }
-    }
-}
+use async_std::prelude::FutureExt;
+use bitcoin_explorer::{app, args, electrum, ratelimit, rpc, telemetry, tls, State};
+use std::time::Duration;
 
 #[async_std::main]
 async fn main() -> tide::Result<()> {
-    tracing_subscriber::fmt()
-        .with_env_filter(tracing_subscriber::EnvFilter::new("sqlx=warn,info"))
-        .init();
     let args = match args::parse() {
         Ok(x) => x,
-        Err(e) => panic!("Args parsing error: {}", e),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
     };
+    if args.print_config {
+        print!("{}", explorer_config::render(&args.settings()));
+        return Ok(());
+    }
+    telemetry::init(&args)?;
+    tracing::info!(
+        "configuration:\n{}",
+        explorer_config::render(&args.settings())
+    );
 
-    let mut app = tide::with_state(State::from_args(&args).await);
-    app.with(telemetry::TraceMiddleware::new());
-    app.at("/api/address/:address").get(api::address);
-    app.at("/api/tx/:tx").get(api::transaction);
-    app.at("/api/blocks/:block").get(api::block);
-    app.at("/api/blocks").get(api::blocks);
-    app.at("/api/search").post(api::search);
-    // app.at("/api/chainstate").post(api::chainstate);
-    app.with(dist::Middleware {});
-    app.at("/").get(api::home);
-    // app.at("/").get(api::home);
-    app.listen(args.listen.as_str()).await?;
+    let state = State::from_args(&args).await?;
+    let networks = state.networks(&args).await?;
+    let shutdown = state.shutdown.clone();
+    shutdown.listen_signals()?;
+    let all: Vec<State> = std::iter::once(state.clone())
+        .chain(networks.into_iter())
+        .collect();
+    for network in all.iter() {
+        let watch = rpc::watch_tip(network.rpc_client.clone(), Duration::from_secs(5));
+        let stopped = shutdown.clone();
+        async_std::task::spawn(watch.race(async move { stopped.wait().await }));
+        let probe = rpc::watch_nodes(network.rpc_client.clone(), Duration::from_secs(10));
+        let stopped = shutdown.clone();
+        async_std::task::spawn(probe.race(async move { stopped.wait().await }));
+    }
+    if !args.electrum_listen.is_empty() {
+        let limiter = ratelimit::Limiter::from_args(&args, state.pool.clone())?;
+        let electrum = electrum::listen(state.clone(), args.electrum_listen.clone(), limiter);
+        let stopped = shutdown.clone();
+        async_std::task::spawn(async move {
+            let stop = async move {
+                stopped.wait().await;
+                Ok(())
+            };
+            if let Err(e) = electrum.race(stop).await {
+                tracing::error!("Electrum server failure: {}", e);
+            }
+        });
+    }
+    let app = app(&args, state, &all)?;
+
+    let serve = async {
+        if args.tls_cert.is_empty() {
+            app.listen(args.listen.as_str()).await?;
+        } else {
+            if !args.http_redirect.is_empty() {
+                let src = args.clone();
+                async_std::task::spawn(async move {
+                    if let Err(e) = tls::redirect(src).await {
+                        tracing::error!("HTTP redirect listener failure: {}", e);
+                    }
+                });
+            }
+            tls::listen(app, &args).await?;
+        }
+        Ok::<(), anyhow::Error>(())
+    };
+    // `/readyz` fails during the delay, then the listener is dropped
+    // while the connections that are already accepted keep being served
+    let stop = async {
+        shutdown.wait().await;
+        async_std::task::sleep(Duration::from_secs(args.shutdown_delay)).await;
+        tracing::info!("closing the listener");
+        Ok(())
+    };
+    serve.race(stop).await?;
+    shutdown
+        .drain(Duration::from_secs(args.shutdown_timeout))
+        .await;
+    for network in all.iter() {
+        network.pool.close().await;
+    }
+    telemetry::shutdown();
     Ok(())
 }
@@ -0,0 +1,274 @@
+use crate::esplora;
+use crate::network;
+use crate::routes;
+use crate::State;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tide::{Next, Request, Response};
+
+// upper bounds of the latency buckets, in seconds
+const BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+#[derive(Default)]
+struct Histogram {
+    // cumulative counts per bucket
+    buckets: [u64; 11],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, value: f64) {
+        for (i, le) in BUCKETS.iter().enumerate() {
+            if value <= *le {
+                self.buckets[i] += 1;
+            }
+        }
+        self.count += 1;
+        self.sum += value;
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        for (i, le) in BUCKETS.iter().enumerate() {
+            let _ = writeln!(
+                out,
+                "{}_bucket{{{},le=\"{}\"}} {}",
+                name, labels, le, self.buckets[i]
+            );
+        }
+        let _ = writeln!(
+            out,
+            "{}_bucket{{{},le=\"+Inf\"}} {}",
+            name, labels, self.count
+        );
+        let _ = writeln!(out, "{}_sum{{{}}} {}", name, labels, self.sum);
+        let _ = writeln!(out, "{}_count{{{}}} {}", name, labels, self.count);
+    }
+}
+
+// progress of the indexer between two scrapes
+struct IndexerSample {
+    height: i64,
+    at: Instant,
+    rate: f64,
+}
+
+#[derive(Default)]
+struct Inner {
+    // (route, method, status) => latency
+    requests: Mutex<BTreeMap<(String, String, u16), Histogram>>,
+    // (method, result) => latency
+    rpc: Mutex<BTreeMap<(String, &'static str), Histogram>>,
+    indexer: Mutex<Option<IndexerSample>>,
+    started: AtomicU64,
+}
+
+/// Registry of the server metrics, rendered in Prometheus text format
+#[derive(Clone, Default)]
+pub struct Metrics {
+    inner: Arc<Inner>,
+}
+
+impl std::fmt::Debug for Metrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Metrics").finish()
+    }
+}
+
+impl Metrics {
+    pub fn observe_request(&self, route: &str, method: &str, status: u16, took: Duration) {
+        let mut requests = self
+            .inner
+            .requests
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        requests
+            .entry((route.to_string(), method.to_string(), status))
+            .or_default()
+            .observe(took.as_secs_f64());
+    }
+
+    pub fn observe_rpc(&self, method: &str, ok: bool, took: Duration) {
+        let result = if ok { "ok" } else { "error" };
+        let mut rpc = self.inner.rpc.lock().unwrap_or_else(|e| e.into_inner());
+        rpc.entry((method.to_string(), result))
+            .or_default()
+            .observe(took.as_secs_f64());
+    }
+
+    /// number of requests being served right now
+    pub fn in_flight(&self) -> u64 {
+        self.inner.started.load(Ordering::Relaxed)
+    }
+
+    /// blocks per second indexed since the previous call
+    fn indexer_rate(&self, height: i64) -> f64 {
+        let mut sample = self.inner.indexer.lock().unwrap_or_else(|e| e.into_inner());
+        let rate = match sample.as_ref() {
+            Some(prev) if prev.at.elapsed().as_secs_f64() > 1.0 => {
+                (height - prev.height) as f64 / prev.at.elapsed().as_secs_f64()
+            }
+            Some(prev) => {
+                // scraped too often, keep the previous value
+                return prev.rate;
+            }
+            None => 0.0,
+        };
+        *sample = Some(IndexerSample {
+            height,
+            at: Instant::now(),
+            rate,
+        });
+        rate
+    }
+
+    fn render_requests(&self, out: &mut String) {
+        let requests = self
+            .inner
+            .requests
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        out.push_str("# TYPE http_requests_total counter\n");
+        for ((route, method, status), h) in requests.iter() {
+            let _ = writeln!(
+                out,
+                "http_requests_total{{route=\"{}\",method=\"{}\",status=\"{}\"}} {}",
+                route, method, status, h.count
+            );
+        }
+        out.push_str("# TYPE http_request_duration_seconds histogram\n");
+        for ((route, method, status), h) in requests.iter() {
+            let labels = format!(
+                "route=\"{}\",method=\"{}\",status=\"{}\"",
+                route, method, status
+            );
+            h.render(out, "http_request_duration_seconds", labels.as_str());
+        }
+        let _ = writeln!(out, "# TYPE http_requests_in_flight gauge");
+        let _ = writeln!(out, "http_requests_in_flight {}", self.in_flight());
+    }
+
+    fn render_rpc(&self, out: &mut String) {
+        let rpc = self.inner.rpc.lock().unwrap_or_else(|e| e.into_inner());
+        out.push_str("# TYPE rpc_calls_total counter\n");
+        for ((method, result), h) in rpc.iter() {
+            let _ = writeln!(
+                out,
+                "rpc_calls_total{{method=\"{}\",result=\"{}\"}} {}",
+                method, result, h.count
+            );
+        }
+        out.push_str("# TYPE rpc_call_duration_seconds histogram\n");
+        for ((method, result), h) in rpc.iter() {
+            let labels = format!("method=\"{}\",result=\"{}\"", method, result);
+            h.render(out, "rpc_call_duration_seconds", labels.as_str());
+        }
+    }
+}
+
+/// name of the route in the table that serves the path, for the metric labels
+fn route_of(path: &str) -> String {
+    if let Some((_, rest)) = network::strip(path) {
+        let route = route_of(format!("/api{}", rest).as_str());
+        return match route.strip_prefix("/api") {
+            Some(x) => format!("/api/:network{}", x),
+            None => "/api/:network".to_string(),
+        };
+    }
+    for route in routes::all() {
+        if routes::matches(route.path, path) {
+            return route.path.to_string();
+        }
+    }
+    for route in esplora::routes() {
+        if routes::matches(route.path, path) {
+            return route.path.to_string();
+        }
+    }
+    if routes::is_service(path) {
+        return path.to_string();
+    }
+    "static".to_string()
+}
+
+/// Counts requests and their latency per route and status
+#[derive(Default)]
+pub struct Middleware {}
+
+#[tide::utils::async_trait]
+impl tide::Middleware<State> for Middleware {
+    async fn handle(&self, req: Request<State>, next: Next<'_, State>) -> tide::Result {
+        let metrics = req.state().metrics.clone();
+        let route = route_of(req.url().path());
+        let method = req.method().to_string();
+        metrics.inner.started.fetch_add(1, Ordering::Relaxed);
+        let start = Instant::now();
+        let res = next.run(req).await;
+        metrics.inner.started.fetch_sub(1, Ordering::Relaxed);
+        metrics.observe_request(
+            route.as_str(),
+            method.as_str(),
+            res.status() as u16,
+            start.elapsed(),
+        );
+        Ok(res)
+    }
+}
+
+/// `GET /metrics`
+pub async fn handler(req: Request<State>) -> tide::Result {
+    let state = req.state();
+    let mut out = String::new();
+    state.metrics.render_requests(&mut out);
+    state.metrics.render_rpc(&mut out);
+
+    out.push_str("# TYPE cache_hits_total counter\n");
+    out.push_str("# TYPE cache_misses_total counter\n");
+    for (kind, hits, misses) in state.rpc_client.cache.stats() {
+        let _ = writeln!(out, "cache_hits_total{{kind=\"{}\"}} {}", kind, hits);
+        let _ = writeln!(out, "cache_misses_total{{kind=\"{}\"}} {}", kind, misses);
+    }
+
+    out.push_str("# TYPE rpc_node_up gauge\n");
+    out.push_str("# TYPE rpc_node_height gauge\n");
+    for node in state.rpc_client.nodes.nodes() {
+        let up = if node.is_down() { 0 } else { 1 };
+        let _ = writeln!(out, "rpc_node_up{{node=\"{}\"}} {}", node.addr, up);
+        let _ = writeln!(
+            out,
+            "rpc_node_height{{node=\"{}\"}} {}",
+            node.addr,
+            node.height()
+        );
+    }
+
+    out.push_str("# TYPE db_pool_connections gauge\n");
+    let _ = writeln!(out, "db_pool_connections {}", state.pool.size());
+    out.push_str("# TYPE db_pool_idle_connections gauge\n");
+    let _ = writeln!(out, "db_pool_idle_connections {}", state.pool.num_idle());
+
+    let indexed = crate::health::indexed_height(&state.pool).await.ok();
+    if let Some(indexed) = indexed {
+        let rate = state.metrics.indexer_rate(indexed);
+        out.push_str("# TYPE indexer_height gauge\n");
+        let _ = writeln!(out, "indexer_height {}", indexed);
+        out.push_str("# TYPE indexer_blocks_per_second gauge\n");
+        let _ = writeln!(out, "indexer_blocks_per_second {}", rate);
+        if let Ok(chain) = crate::rpc::get_blockchain_info(state.rpc_client.clone()).await {
+            out.push_str("# TYPE node_height gauge\n");
+            let _ = writeln!(out, "node_height {}", chain.blocks);
+            out.push_str("# TYPE indexer_lag_blocks gauge\n");
+            let _ = writeln!(out, "indexer_lag_blocks {}", chain.blocks as i64 - indexed);
+        }
+    }
+
+    Ok(Response::builder(200)
+        .header("Content-Type", "text/plain; version=0.0.4")
+        .body(out)
+        .build())
+}
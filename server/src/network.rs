@@ -0,0 +1,134 @@
+use crate::args::Args;
+use crate::error::ApiError;
+use bitcoin::util::address::Payload;
+use bitcoincore_rpc_json::bitcoin;
+use explorer_types::Network;
+use std::str::FromStr;
+use tide::http::Url;
+
+/// names of the networks, as accepted by `--network`
+pub const NAMES: &[&str] = &["mainnet", "testnet", "signet", "regtest"];
+
+/// address of the local node of the network on its default port
+pub fn default_rpc_addr(network: Network) -> String {
+    format!("http://localhost:{}/", network.rpc_port())
+}
+
+/// Node of the network served by this instance
+#[derive(Debug, Clone)]
+pub struct Endpoint {
+    pub network: Network,
+    pub rpc_addr: String,
+    pub rpc_username: String,
+    pub rpc_password: String,
+    pub rpc_cookie: String,
+}
+
+impl Endpoint {
+    /// node of the primary network, from `--rpc-*` settings
+    pub fn primary(src: &Args) -> Self {
+        Self {
+            network: src.network,
+            rpc_addr: src.rpc_addr.clone(),
+            rpc_username: src.rpc_username.clone(),
+            rpc_password: src.rpc_password.clone(),
+            rpc_cookie: src.rpc_cookie.clone(),
+        }
+    }
+
+    /// `name` or `name=url` entry of `--networks`.
+    /// Credentials are taken from the URL, or from the primary node when it has none.
+    /// The cookie of the primary node is not shared, as it belongs to another node
+    fn parse(entry: &str, src: &Args) -> anyhow::Result<Self> {
+        let (name, addr) = match entry.find('=') {
+            Some(i) => (&entry[..i], entry[i + 1..].trim()),
+            None => (entry, ""),
+        };
+        let network = Network::from_str(name).map_err(|e| anyhow::anyhow!(e))?;
+        let mut out = Self {
+            network,
+            rpc_addr: default_rpc_addr(network),
+            rpc_username: src.rpc_username.clone(),
+            rpc_password: src.rpc_password.clone(),
+            rpc_cookie: String::new(),
+        };
+        if addr.is_empty() {
+            return Ok(out);
+        }
+        let mut url =
+            Url::parse(addr).map_err(|e| anyhow::anyhow!("{} node address: {}", name, e))?;
+        if !url.username().is_empty() {
+            out.rpc_username = url.username().to_string();
+            out.rpc_password = url.password().unwrap_or("").to_string();
+            let _ = url.set_username("");
+            let _ = url.set_password(None);
+        }
+        out.rpc_addr = url.to_string();
+        Ok(out)
+    }
+}
+
+/// nodes of all served networks, the primary one first
+pub fn endpoints(src: &Args) -> anyhow::Result<Vec<Endpoint>> {
+    let mut out = vec![Endpoint::primary(src)];
+    for entry in src.networks.split(',').map(str::trim) {
+        if entry.is_empty() {
+            continue;
+        }
+        let endpoint = Endpoint::parse(entry, src)?;
+        if out.iter().any(|x| x.network == endpoint.network) {
+            anyhow::bail!("network {} is configured twice", endpoint.network);
+        }
+        out.push(endpoint);
+    }
+    Ok(out)
+}
+
+/// whether the address belongs to the network.
+/// Testnet and signet share the address prefixes,
+/// regtest shares base58 prefixes with them and has its own bech32 one
+pub fn accepts(network: Network, address: &bitcoin::Address) -> bool {
+    match (network, address.network) {
+        (Network::Mainnet, bitcoin::Network::Bitcoin) => true,
+        (Network::Testnet, bitcoin::Network::Testnet) => true,
+        (Network::Signet, bitcoin::Network::Testnet) => true,
+        (Network::Regtest, bitcoin::Network::Regtest) => true,
+        (Network::Regtest, bitcoin::Network::Testnet) => {
+            !matches!(address.payload, Payload::WitnessProgram { .. })
+        }
+        _ => false,
+    }
+}
+
+/// network of the `bitcoin` crate with the address prefixes of the served network
+pub fn address_network(network: Network) -> bitcoin::Network {
+    match network {
+        Network::Mainnet => bitcoin::Network::Bitcoin,
+        Network::Testnet | Network::Signet => bitcoin::Network::Testnet,
+        Network::Regtest => bitcoin::Network::Regtest,
+    }
+}
+
+/// parses the address of the served network
+pub fn parse_address(network: Network, value: &str) -> Result<bitcoin::Address, ApiError> {
+    let address = bitcoin::Address::from_str(value)
+        .map_err(|e| ApiError::invalid_param(format!("address param error {}", e)))?;
+    if !accepts(network, &address) {
+        return Err(ApiError::invalid_param(format!(
+            "address {} is not on {}",
+            value, network
+        )));
+    }
+    Ok(address)
+}
+
+/// network of the `/api/{network}/...` path and the rest of the path
+pub fn strip(path: &str) -> Option<(Network, &str)> {
+    let rest = path.strip_prefix("/api/")?;
+    let (name, rest) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, ""),
+    };
+    let network = Network::ALL.iter().find(|n| n.name() == name)?;
+    Some((*network, rest))
+}
@@ -0,0 +1,203 @@
+use crate::error::{ErrorCode, ErrorResponse};
+use crate::routes;
+use crate::State;
+use explorer_types::{
+    pager, BlockHeader, BlockStatsInfo, BlockSummary, Broadcast, RawTransaction, Rejection,
+    TxDetail,
+};
+use schemars::gen::SchemaSettings;
+use serde_json::{json, Map, Value};
+use tide::{Body, Request, Response, Result};
+
+/// schemas of the API types shared with the SDK, with the types they refer to,
+/// so the spec follows the fields of the structs without listing them by hand
+fn derived() -> Map<String, Value> {
+    let mut gen = SchemaSettings::openapi3().into_generator();
+    gen.subschema_for::<ErrorResponse>();
+    gen.subschema_for::<BlockStatsInfo>();
+    gen.subschema_for::<pager::Output>();
+    gen.subschema_for::<BlockHeader>();
+    gen.subschema_for::<BlockSummary>();
+    gen.subschema_for::<RawTransaction>();
+    gen.subschema_for::<TxDetail>();
+    gen.subschema_for::<Broadcast>();
+    gen.subschema_for::<Rejection>();
+    let mut definitions = gen.take_definitions();
+    // OpenAPI 3.0 ignores the siblings of `$ref`, so the optional struct fields
+    // are rewritten into `allOf` next to `nullable`, as it is done for the root schemas
+    for visitor in gen.visitors_mut() {
+        for schema in definitions.values_mut() {
+            visitor.visit_schema(schema);
+        }
+    }
+    definitions
+        .into_iter()
+        .map(|(name, schema)| (name, serde_json::to_value(schema).unwrap_or(Value::Null)))
+        .collect()
+}
+
+/// schema of the object with the listed fields, other fields are allowed
+fn object(fields: &[(&str, &str)]) -> Value {
+    let properties: Map<String, Value> = fields
+        .iter()
+        .map(|(k, t)| (k.to_string(), json!({ "type": t })))
+        .collect();
+    json!({ "type": "object", "properties": properties, "additionalProperties": true })
+}
+
+fn reference(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{}", name) })
+}
+
+/// externally tagged `types::response` enum: `{"<tag>": ...}` or `{"error": {...}}`
+fn envelope(tag: &str, inner: Value) -> Value {
+    json!({
+        "oneOf": [
+            { "type": "object", "required": [tag], "properties": { tag: inner } },
+            reference("Error"),
+        ]
+    })
+}
+
+/// every error code that can be sent by the API
+const CODES: [ErrorCode; 7] = [
+    ErrorCode::NotFound,
+    ErrorCode::InvalidParam,
+    ErrorCode::UpstreamUnavailable,
+    ErrorCode::NotIndexed,
+    ErrorCode::RateLimited,
+    ErrorCode::Unauthorized,
+    ErrorCode::InternalError,
+];
+
+fn schemas() -> Value {
+    let mut out = derived();
+    let rest = json!({
+        "StringMap": {
+            "type": "object",
+            "additionalProperties": { "type": "string" },
+        },
+        "OpenApi": { "type": "object" },
+        "BlockListResponse": envelope("blocks", json!({
+            "type": "object",
+            "properties": {
+                "list": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "header": reference("BlockHeader"),
+                            "stats": reference("BlockStatsInfo"),
+                        },
+                    },
+                },
+                "pager": reference("PagerOutput"),
+            },
+        })),
+        "BlockResponse": envelope("block", json!({
+            "type": "object",
+            "properties": {
+                "block": reference("BlockSummary"),
+                "stats": reference("BlockStatsInfo"),
+            },
+        })),
+        "TxResponse": envelope("tx", reference("TxDetail")),
+        "DecodedTx": {
+            "allOf": [reference("TxDetail"), object(&[("psbt", "boolean")])]
+        },
+        "DecodeResponse": envelope("tx", reference("DecodedTx")),
+        "BroadcastResponse": {
+            "oneOf": [
+                { "type": "object", "required": ["tx"], "properties": { "tx": reference("Broadcast") } },
+                {
+                    "type": "object",
+                    "required": ["rejected"],
+                    "properties": { "rejected": reference("Rejection") },
+                },
+                reference("Error"),
+            ]
+        },
+        "AddressResponse": envelope("list", json!({
+            "type": "object",
+            "properties": {
+                "list": { "type": "array", "items": reference("RawTransaction") },
+                "pager": reference("PagerOutput"),
+            },
+        })),
+    });
+    if let Value::Object(rest) = rest {
+        out.extend(rest);
+    }
+    Value::Object(out)
+}
+
+/// converts tide path `/api/tx/:tx` into OpenAPI path `/api/tx/{tx}`
+fn openapi_path(path: &str) -> String {
+    path.split('/')
+        .map(|segment| match segment.strip_prefix(':') {
+            Some(name) => format!("{{{}}}", name),
+            None => segment.to_string(),
+        })
+        .collect::<Vec<String>>()
+        .join("/")
+}
+
+/// OpenAPI 3 document built from the route table
+pub fn document() -> Value {
+    let mut paths: Map<String, Value> = Map::new();
+    for route in routes::all() {
+        let parameters: Vec<Value> = route
+            .params
+            .iter()
+            .map(|p| {
+                json!({
+                    "name": p.name,
+                    "in": if p.in_path { "path" } else { "query" },
+                    "required": p.in_path,
+                    "description": p.description,
+                    "schema": { "type": "string" },
+                })
+            })
+            .collect();
+        let mut responses: Map<String, Value> = Map::new();
+        responses.insert(
+            "200".to_string(),
+            json!({
+                "description": "OK",
+                "content": { "application/json": { "schema": reference(route.response) } },
+            }),
+        );
+        for code in CODES.iter() {
+            let error = json!({
+                "description": code.to_string(),
+                "content": { "application/json": { "schema": reference("Error") } },
+            });
+            responses.entry(code.status().to_string()).or_insert(error);
+        }
+        let operation = json!({
+            "summary": route.summary,
+            "parameters": parameters,
+            "responses": responses,
+        });
+        let item = paths
+            .entry(openapi_path(route.path))
+            .or_insert_with(|| json!({}));
+        item[route.method.to_string().to_lowercase()] = operation;
+    }
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Bitcoin Explorer",
+            "description": "API of the primary network. Every served network has the same API under `/api/{network}/`, i.e. `/api/testnet/tx/{tx}`",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": paths,
+        "components": { "schemas": schemas() },
+    })
+}
+
+pub async fn handler(_req: Request<State>) -> Result {
+    let mut res = Response::new(200);
+    res.set_body(Body::from_json(&document())?);
+    Ok(res)
+}
@@ -1,3 +1,4 @@
+use crate::error::ApiError;
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -17,33 +18,37 @@ impl Default for Input {
     }
 }
 
+#[derive(Debug, Default, Deserialize)]
+struct Query {
+    from: Option<String>,
+    limit: Option<u32>,
+}
+
+/// max number of records on the page
+pub const MAX_LIMIT: u32 = 100;
+
 impl Input {
-    pub fn from_request(req: tide::Request<crate::State>) -> Self {
+    /// reads `from` and `limit` from the query string of the request
+    pub fn from_request(req: &tide::Request<crate::State>) -> Result<Self, ApiError> {
         let default_limit: u32 = 20;
-        let limit = match req
-            .param("limit")
-            .map(|x| x.parse::<u32>().unwrap_or_default())
-        {
-            Ok(x) => {
-                if x == 0 {
-                    default_limit
-                } else {
-                    x
-                }
+        let query: Query = req
+            .query()
+            .map_err(|e| ApiError::invalid_param(format!("query parsing error {}", e)))?;
+        let limit = match query.limit {
+            Some(x) if x > MAX_LIMIT => {
+                return Err(ApiError::invalid_param(format!(
+                    "limit is larger than {}",
+                    MAX_LIMIT
+                )))
             }
-            Err(_) => default_limit,
+            Some(x) if x > 0 => x,
+            _ => default_limit,
         };
-        let from = match req.param("from") {
-            Ok(x) => {
-                if x.len() > 0 {
-                    Some(x.to_string())
-                } else {
-                    None
-                }
-            }
-            Err(_) => None,
+        let from = match query.from {
+            Some(x) if !x.is_empty() => Some(x),
+            _ => None,
         };
-        Self { from, limit }
+        Ok(Self { from, limit })
     }
 }
 
@@ -60,8 +65,4 @@ impl std::hash::Hash for Input {
     }
 }
 
-#[derive(Clone, Debug, Default, Serialize, Deserialize)]
-pub struct Output {
-    // token to start the next page
-    pub from: Option<String>,
-}
+pub use explorer_types::pager::Output;
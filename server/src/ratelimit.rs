@@ -0,0 +1,317 @@
+use crate::args::Args;
+use crate::broadcast;
+use crate::error::{self, ApiError};
+use crate::routes;
+use crate::telemetry;
+use crate::State;
+use bitcoin::hashes::hex::ToHex;
+use bitcoin::hashes::{sha256, Hash};
+use bitcoincore_rpc_json::bitcoin;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tide::{Next, Request};
+use tracing_futures::Instrument;
+
+// number of buckets that triggers dropping the least recently used half of them
+pub const MAX_BUCKETS: usize = 100_000;
+// how long the tier of the API key is remembered before it is read again
+const KEY_TTL: Duration = Duration::from_secs(60);
+
+/// Limit of the token bucket: sustained requests per second and size of the burst
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tier {
+    pub rate: f64,
+    pub burst: f64,
+}
+
+impl FromStr for Tier {
+    type Err = anyhow::Error;
+
+    /// `rate/burst`, i.e. `20/100`
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let mut parts = s.splitn(2, '/');
+        let rate: f64 = parts.next().unwrap_or("").trim().parse()?;
+        let burst: f64 = match parts.next() {
+            Some(x) => x.trim().parse()?,
+            None => rate,
+        };
+        if rate <= 0.0 || burst < 1.0 {
+            anyhow::bail!("invalid tier {}", s);
+        }
+        Ok(Self { rate, burst })
+    }
+}
+
+/// parses the list of tiers, `basic=20/100,pro=100/500`
+pub fn parse_tiers(src: &str) -> anyhow::Result<HashMap<String, Tier>> {
+    let mut out = HashMap::new();
+    for item in src.split(',').map(str::trim).filter(|x| !x.is_empty()) {
+        let mut parts = item.splitn(2, '=');
+        let name = parts.next().unwrap_or("").trim();
+        let tier = match parts.next() {
+            Some(x) => x.parse()?,
+            None => anyhow::bail!("tier {} has no limit", item),
+        };
+        out.insert(name.to_string(), tier);
+    }
+    Ok(out)
+}
+
+/// drops the least recently used half of the entries, so the map stays under the cap
+/// however many clients come, and the cost is spread over the next ones to be added
+fn evict_oldest<V>(map: &mut HashMap<String, V>, used: impl Fn(&V) -> Instant) {
+    let mut ages: Vec<Instant> = map.values().map(&used).collect();
+    let half = ages.len() / 2;
+    let (_, median, _) = ages.select_nth_unstable(half);
+    let median = *median;
+    map.retain(|_, v| used(v) > median);
+}
+
+struct Bucket {
+    tier: Tier,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self) {
+        let elapsed = self.updated.elapsed().as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.tier.rate).min(self.tier.burst);
+        self.updated = Instant::now();
+    }
+
+    /// takes a token, returns the number of remaining tokens
+    /// or the time until the next token is available
+    fn take(&mut self) -> Result<f64, Duration> {
+        self.refill();
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(self.tokens)
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - self.tokens) / self.tier.rate,
+            ))
+        }
+    }
+}
+
+/// Tiers of API keys stored in `api_keys` table, keys are kept as SHA-256 hex digests
+pub struct Keys {
+    pool: sqlx::PgPool,
+    cache: Mutex<HashMap<String, (Option<String>, Instant)>>,
+}
+
+impl Keys {
+    pub fn new(pool: sqlx::PgPool) -> Self {
+        Self {
+            pool,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// name of the tier of the key as it was read last time,
+    /// `None` if it has to be read again. Unknown keys are remembered as well
+    fn cached(&self, digest: &str) -> Option<Option<String>> {
+        match self.cache.lock().unwrap().get(digest) {
+            Some((tier, at)) if at.elapsed() < KEY_TTL => Some(tier.clone()),
+            _ => None,
+        }
+    }
+
+    /// name of the tier of the enabled key, `None` if the key is not known
+    pub async fn tier_of(&self, digest: &str) -> Result<Option<String>, sqlx::Error> {
+        if let Some(tier) = self.cached(digest) {
+            return Ok(tier);
+        }
+        let sql = "SELECT tier FROM api_keys WHERE key_hash = $1 AND enabled";
+        let row: Option<(String,)> = sqlx::query_as(sql)
+            .bind(digest)
+            .fetch_optional(&self.pool)
+            .instrument(telemetry::sql_span(sql))
+            .await?;
+        let tier = row.map(|x| x.0);
+        let mut cache = self.cache.lock().unwrap();
+        if cache.len() >= MAX_BUCKETS && !cache.contains_key(digest) {
+            evict_oldest(&mut cache, |(_, at)| *at);
+        }
+        cache.insert(digest.to_string(), (tier.clone(), Instant::now()));
+        Ok(tier)
+    }
+}
+
+/// Token buckets per client IP and per API key
+pub struct Limiter {
+    // limit of the requests without API key, none if they are not limited
+    anonymous: Option<Tier>,
+    tiers: HashMap<String, Tier>,
+    // limit of the transaction broadcasts per client, on top of the other limits
+    broadcast: Option<Tier>,
+    keys: Keys,
+    // take client address from `Forwarded` and `X-Forwarded-For` headers
+    trust_proxy: bool,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl Limiter {
+    pub fn from_args(src: &Args, pool: sqlx::PgPool) -> anyhow::Result<Self> {
+        let anonymous = if src.rate_limit.is_empty() {
+            None
+        } else {
+            Some(src.rate_limit.parse()?)
+        };
+        let broadcast = if src.broadcast_rate_limit.is_empty() {
+            None
+        } else {
+            Some(src.broadcast_rate_limit.parse()?)
+        };
+        Ok(Self {
+            anonymous,
+            tiers: parse_tiers(&src.rate_limit_tiers)?,
+            broadcast,
+            keys: Keys::new(pool),
+            trust_proxy: src.trust_proxy,
+            buckets: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn take(&self, id: &str, tier: &Tier) -> Result<f64, Duration> {
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_BUCKETS && !buckets.contains_key(id) {
+            // the dropped bucket comes back full, as the one of the client idle for long
+            evict_oldest(&mut buckets, |b| b.updated);
+        }
+        let bucket = buckets.entry(id.to_string()).or_insert(Bucket {
+            tier: *tier,
+            tokens: tier.burst,
+            updated: Instant::now(),
+        });
+        // the tier of the key could have been changed
+        bucket.tier = *tier;
+        bucket.take()
+    }
+
+    /// takes the token of the client without API key, i.e. of the Electrum connection,
+    /// and of the broadcast limit as well for the broadcast.
+    /// Fails with the time until the next token
+    pub fn take_anonymous(&self, id: &str, is_broadcast: bool) -> Result<(), Duration> {
+        if let Some(limit) = self.broadcast.filter(|_| is_broadcast) {
+            self.take(&format!("broadcast:{}", id), &limit)?;
+        }
+        if let Some(limit) = self.anonymous {
+            self.take(id, &limit)?;
+        }
+        Ok(())
+    }
+
+    fn client_ip<S>(&self, req: &Request<S>) -> String {
+        let addr = if self.trust_proxy {
+            req.remote()
+        } else {
+            req.peer_addr()
+        };
+        let addr = addr.unwrap_or("unknown");
+        // strip the port, keeping IPv6 addresses intact
+        match addr.parse::<std::net::SocketAddr>() {
+            Ok(x) => x.ip().to_string(),
+            Err(_) => addr.to_string(),
+        }
+    }
+}
+
+/// API key of the request. It is only taken from the header,
+/// the query string ends up in the logs of the proxies and in the browser history
+fn api_key<S>(req: &Request<S>) -> Option<String> {
+    req.header("X-API-Key").map(|x| x.last().to_string())
+}
+
+/// Client of the request as it is throttled, `ip:<address>` or `key:<digest>`,
+/// attached to the request for the handlers that log their callers
+#[derive(Clone, Debug)]
+pub struct Client(pub String);
+
+/// `429 Too Many Requests` with the time until the next token
+fn throttled(retry_after: Duration, tier: &Tier, message: &str) -> tide::Result {
+    let mut res = error::failure(ApiError::rate_limited(message))?;
+    let secs = retry_after.as_secs_f64().ceil() as u64;
+    res.insert_header("Retry-After", secs.max(1).to_string());
+    res.insert_header("X-RateLimit-Limit", (tier.burst as u64).to_string());
+    res.insert_header("X-RateLimit-Remaining", "0");
+    Ok(res)
+}
+
+/// Throttles the API requests, replying with `429 Too Many Requests`
+/// and `Retry-After` when the bucket of the client is empty
+pub struct Middleware {
+    limiter: Arc<Limiter>,
+}
+
+impl Middleware {
+    pub fn new(limiter: Limiter) -> Self {
+        Self {
+            limiter: Arc::new(limiter),
+        }
+    }
+}
+
+#[tide::utils::async_trait]
+impl tide::Middleware<State> for Middleware {
+    async fn handle(&self, mut req: Request<State>, next: Next<'_, State>) -> tide::Result {
+        if !routes::is_api(req.url().path()) {
+            return Ok(next.run(req).await);
+        }
+        let limiter = &self.limiter;
+        let mut id = format!("ip:{}", limiter.client_ip(&req));
+        let mut tier = limiter.anonymous;
+        // the token is taken from the address bucket before the key is read
+        let mut charged = None;
+        if let Some(key) = api_key(&req) {
+            let digest = sha256::Hash::hash(key.as_bytes()).to_hex();
+            if let (None, Some(limit)) = (limiter.keys.cached(&digest), tier) {
+                match limiter.take(&id, &limit) {
+                    Ok(remaining) => charged = Some(remaining),
+                    Err(retry_after) => {
+                        return throttled(retry_after, &limit, "too many requests, slow down")
+                    }
+                }
+            }
+            match limiter.keys.tier_of(&digest).await {
+                Ok(Some(name)) => match limiter.tiers.get(&name) {
+                    Some(x) => {
+                        id = format!("key:{}", digest);
+                        tier = Some(*x);
+                        charged = None;
+                    }
+                    None => tracing::warn!("API key has unknown tier {}", name),
+                },
+                Ok(None) => return error::failure(ApiError::unauthorized("unknown API key")),
+                // the key can't be checked, the client is limited by its address
+                Err(e) => tracing::warn!("API key lookup failure {}", e),
+            }
+        }
+        if let Some(limit) = limiter.broadcast.filter(|_| broadcast::is_broadcast(&req)) {
+            if let Err(retry_after) = limiter.take(&format!("broadcast:{}", id), &limit) {
+                return throttled(retry_after, &limit, "too many broadcasts, slow down");
+            }
+        }
+        req.set_ext(Client(id.clone()));
+        let tier = match tier {
+            Some(x) => x,
+            None => return Ok(next.run(req).await),
+        };
+        match charged.map_or_else(|| limiter.take(&id, &tier), Ok) {
+            Ok(remaining) => {
+                let mut res = next.run(req).await;
+                res.insert_header("X-RateLimit-Limit", (tier.burst as u64).to_string());
+                res.insert_header("X-RateLimit-Remaining", (remaining as u64).to_string());
+                Ok(res)
+            }
+            Err(retry_after) => throttled(
+                retry_after,
+                &tier,
+                "too many requests, slow down or use an API key",
+            ),
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use crate::api;
+use crate::broadcast;
+use crate::decode;
+use crate::esplora;
+use crate::health;
+use crate::metrics;
+use crate::State;
+use std::future::Future;
+use std::pin::Pin;
+use tide::http::Method;
+use tide::Request;
+
+pub type Handler = fn(Request<State>) -> Pin<Box<dyn Future<Output = tide::Result> + Send>>;
+
+/// Parameter of the route, either in the path or in the query string
+#[derive(Clone)]
+pub struct Param {
+    pub name: &'static str,
+    pub in_path: bool,
+    pub description: &'static str,
+}
+
+const PAGER: [Param; 2] = [
+    Param {
+        name: "from",
+        in_path: false,
+        description: "cursor to start from, `pager.from` of the previous page",
+    },
+    Param {
+        name: "limit",
+        in_path: false,
+        description: "number of records on the page, 20 by default and 100 at most",
+    },
+];
+
+/// Route of the HTTP API, also used to build OpenAPI specification
+pub struct Route {
+    pub method: Method,
+    // path in tide syntax, i.e. `/api/tx/:tx`
+    pub path: &'static str,
+    pub summary: &'static str,
+    pub params: Vec<Param>,
+    // name of the response schema in `components/schemas`
+    pub response: &'static str,
+    pub handler: Handler,
+}
+
+pub fn all() -> Vec<Route> {
+    vec![
+        Route {
+            method: Method::Get,
+            path: "/api/address/:address",
+            summary: "History of the address",
+            params: vec![
+                Param {
+                    name: "address",
+                    in_path: true,
+                    description: "bitcoin address",
+                },
+                PAGER[0].clone(),
+                PAGER[1].clone(),
+            ],
+            response: "AddressResponse",
+            handler: |req| Box::pin(api::address(req)),
+        },
+        Route {
+            method: Method::Get,
+            path: "/api/tx/:tx",
+            summary: "Transaction details with the spent outputs, the spending of its outputs and the fee",
+            params: vec![Param {
+                name: "tx",
+                in_path: true,
+                description: "transaction id, hex",
+            }],
+            response: "TxResponse",
+            handler: |req| Box::pin(api::transaction(req)),
+        },
+        Route {
+            method: Method::Post,
+            path: "/api/tx",
+            summary: "Broadcast the raw transaction, given as hex or `{\"hex\": ...}` in the body",
+            params: vec![],
+            response: "BroadcastResponse",
+            handler: |req| Box::pin(broadcast::handler(req)),
+        },
+        Route {
+            method: Method::Post,
+            path: "/api/decode",
+            summary: "Decode the raw transaction or PSBT, given as hex or base64 in the body, with its fee where the spent outputs are known",
+            params: vec![],
+            response: "DecodeResponse",
+            handler: |req| Box::pin(decode::handler(req)),
+        },
+        Route {
+            method: Method::Get,
+            path: "/api/blocks/:block",
+            summary: "Block details with its stats",
+            params: vec![Param {
+                name: "block",
+                in_path: true,
+                description: "block hash, hex",
+            }],
+            response: "BlockResponse",
+            handler: |req| Box::pin(api::block(req)),
+        },
+        Route {
+            method: Method::Get,
+            path: "/api/blocks",
+            summary: "Latest blocks, starting from the tip",
+            params: vec![PAGER[0].clone(), PAGER[1].clone()],
+            response: "BlockListResponse",
+            handler: |req| Box::pin(api::blocks(req)),
+        },
+        Route {
+            method: Method::Post,
+            path: "/api/search",
+            summary: "Search for address, transaction or block",
+            params: vec![],
+            response: "StringMap",
+            handler: |req| Box::pin(api::search(req)),
+        },
+        Route {
+            method: Method::Get,
+            path: "/api/openapi.json",
+            summary: "OpenAPI specification of this API",
+            params: vec![],
+            response: "OpenApi",
+            handler: |req| Box::pin(crate::openapi::handler(req)),
+        },
+        Route {
+            method: Method::Get,
+            path: "/",
+            summary: "Chain state summary",
+            params: vec![],
+            response: "StringMap",
+            handler: |req| Box::pin(api::home(req)),
+        },
+    ]
+}
+
+/// paths served by the server itself, outside of the API and static files
+pub const SERVICE: &[&str] = &["/metrics", "/healthz", "/readyz"];
+
+pub fn is_service(path: &str) -> bool {
+    SERVICE.contains(&path)
+}
+
+/// whether the path belongs to our API or to Esplora compatibility layer
+pub fn is_api(path: &str) -> bool {
+    path.starts_with("/api/") || path.starts_with(esplora::PREFIX)
+}
+
+/// whether the path matches the route pattern in tide syntax
+pub fn matches(pattern: &str, path: &str) -> bool {
+    let pattern: Vec<&str> = pattern.split('/').collect();
+    let segments: Vec<&str> = path.split('/').collect();
+    pattern.len() == segments.len()
+        && pattern
+            .iter()
+            .zip(segments.iter())
+            .all(|(p, s)| p.starts_with(':') || p == s)
+}
+
+/// registers all routes of the API on the server
+pub fn register(app: &mut tide::Server<State>) {
+    for route in all() {
+        app.at(route.path).method(route.method, route.handler);
+    }
+    for route in esplora::routes() {
+        app.at(route.path).get(route.handler);
+    }
+    app.at("/metrics").get(metrics::handler);
+    app.at("/healthz").get(health::healthz);
+    app.at("/readyz").get(health::readyz);
+}
+
+/// serves the API of the network under `/api/{network}`, with its own node and index
+pub fn register_network(app: &mut tide::Server<State>, state: State) {
+    let prefix = format!("/api/{}", state.network);
+    let mut nested = tide::with_state(state);
+    for route in all() {
+        match route.path.strip_prefix("/api") {
+            Some(path) => nested.at(path).method(route.method, route.handler),
+            // chain summary is at the root of the network
+            None => nested.at("/").method(route.method, route.handler),
+        };
+    }
+    app.at(prefix.as_str()).nest(nested);
+}
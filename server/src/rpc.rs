@@ -1,196 +1,685 @@
+use crate::cache::{self, Cache, Kind};
+use crate::error::{self, ApiError, ErrorCode};
+use crate::metrics::Metrics;
+use crate::node::{self, Credentials, Node};
 use crate::pager;
-use crate::types::response;
-use crate::types::{Block, BlockStatsInfo, BlockStatsResponse, BlocksList};
+use crate::singleflight;
+use crate::telemetry;
+use crate::types::{Block, BlockDetails, BlockStatsInfo, BlocksList, TxList};
+use async_std::task::spawn_blocking;
 use bitcoin::hashes::hex::FromHex;
-use bitcoincore_rpc::RpcApi;
 use bitcoincore_rpc_json as json;
-use cached::proc_macro::cached;
+use explorer_types::MempoolAccept;
 use json::bitcoin;
-use std::hash::{Hash, Hasher};
-use std::time::Duration;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+use tracing_futures::Instrument;
 use ureq::{Agent, AgentBuilder};
 
+// time limit of the request to the node, unless set by `Client::with_timeout`
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+// idle connections to the node that are kept open
+const POOL_SIZE: usize = 16;
+// attempts of the node call before giving up on transient errors
+const RETRIES: u32 = 3;
+// delay before the first retry, doubled with every attempt
+const BACKOFF: Duration = Duration::from_millis(100);
+
 #[derive(Clone, Debug)]
 pub struct Client {
-    pub rpc_addr: String,
-    pub rpc_auth: bitcoincore_rpc::Auth,
+    pub nodes: node::Pool,
+    // keeps the pool of connections to the nodes
+    agent: Agent,
+    inflight: singleflight::Group,
+    pub cache: Cache,
+    pub metrics: Metrics,
 }
 impl Client {
+    /// `rpc_addr` is the list of the node addresses separated by commas,
+    /// each of them may carry its own credentials
     pub fn new(rpc_addr: &str, rpc_username: &str, rpc_password: &str) -> Self {
-        let rpc_auth = if rpc_username.len() > 0 {
-            bitcoincore_rpc::Auth::UserPass(rpc_username.to_string(), rpc_password.to_string())
+        let credentials = if !rpc_username.is_empty() {
+            Credentials::UserPass(rpc_username.to_string(), rpc_password.to_string())
         } else {
-            bitcoincore_rpc::Auth::None
+            Credentials::None
         };
+        let nodes = rpc_addr
+            .split(',')
+            .map(str::trim)
+            .filter(|x| !x.is_empty())
+            .map(|addr| Node::new(addr, credentials.clone()))
+            .collect();
+        Self {
+            nodes: node::Pool::new(nodes),
+            agent: Self::agent(DEFAULT_TIMEOUT),
+            inflight: singleflight::Group::default(),
+            cache: Cache::default(),
+            metrics: Metrics::default(),
+        }
+    }
+
+    /// authenticates with the `.cookie` file of the node,
+    /// at the nodes that have no credentials of their own
+    pub fn with_cookie(self, path: &str) -> Self {
+        let cookie = node::Cookie::new(path);
+        let nodes = self
+            .nodes
+            .nodes()
+            .iter()
+            .cloned()
+            .map(|mut n| {
+                if let Credentials::None = n.credentials {
+                    n.credentials = Credentials::Cookie(cookie.clone());
+                }
+                n
+            })
+            .collect();
         Self {
-            rpc_addr: rpc_addr.to_string(),
-            rpc_auth,
-        }
-    }
-    pub fn core_client(&self) -> bitcoincore_rpc::Client {
-        bitcoincore_rpc::Client::new(self.rpc_addr.clone(), self.rpc_auth.clone()).unwrap()
-    }
-
-    pub fn request(&self) -> ureq::Request {
-        let agent: Agent = AgentBuilder::new()
-            .timeout_read(Duration::from_secs(5))
-            .build();
-        match &self.rpc_auth {
-            bitcoincore_rpc::Auth::UserPass(user, pass) => {
-                let auth_token = format!("{}:{}", user, pass);
-                let auth_hdr = format!("Basic {}", base64::encode(auth_token));
-                agent
-                    .post(self.rpc_addr.as_str())
-                    .set("Authorization", auth_hdr.as_str())
-                    .set("Content-Type", "application/json")
+            nodes: node::Pool::new(nodes),
+            ..self
+        }
+    }
+
+    pub fn with_cache(mut self, cache: Cache) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// records the node calls in the given registry
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// limits the time of every request to the node
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.agent = Self::agent(timeout);
+        self
+    }
+
+    fn agent(timeout: Duration) -> Agent {
+        AgentBuilder::new()
+            .timeout_connect(Duration::from_secs(3))
+            .timeout(timeout)
+            .max_idle_connections_per_host(POOL_SIZE)
+            .build()
+    }
+
+    pub fn request(&self, node: &Node) -> Result<ureq::Request, ApiError> {
+        let mut req = self
+            .agent
+            .post(node.addr.as_str())
+            .set("Content-Type", "application/json");
+        if let Some(auth_hdr) = node.credentials.header()? {
+            req = req.set("Authorization", auth_hdr.as_str());
+        }
+        // the node ignores it, but a proxy in front of it may continue the trace
+        for (name, value) in telemetry::trace_headers() {
+            req = req.set(name.as_str(), value.as_str());
+        }
+        Ok(req)
+    }
+
+    /// whether all nodes are considered dead and calls are failing fast
+    pub fn is_unavailable(&self) -> bool {
+        self.nodes.nodes().iter().all(|n| n.breaker.is_open())
+    }
+
+    /// runs the node call through the circuit breakers of the nodes,
+    /// failing over to the next node and retrying transient failures
+    /// with exponential backoff once all nodes were tried.
+    /// Only `upstream_unavailable` is transient: the transport failures and the node warm-up
+    pub fn retry<T>(
+        &self,
+        read_only: bool,
+        mut f: impl FnMut(&Node) -> Result<T, ApiError>,
+    ) -> Result<T, ApiError> {
+        let candidates = self.nodes.candidates(read_only);
+        if candidates.is_empty() {
+            return Err(ApiError::upstream_unavailable(
+                "bitcoin node is unavailable",
+            ));
+        }
+        let attempts = std::cmp::max(RETRIES as usize, candidates.len());
+        let mut delay = BACKOFF;
+        let mut attempt = 1;
+        loop {
+            let node = &candidates[(attempt - 1) % candidates.len()];
+            // the slot of the half-open circuit is taken only by the node that is called,
+            // and it could have been taken by another request meanwhile
+            let called = node.breaker.allow();
+            let out = if called {
+                f(node)
+            } else {
+                Err(ApiError::upstream_unavailable(format!(
+                    "node {} circuit is open",
+                    node.addr
+                )))
+            };
+            match out {
+                Err(e) if e.code == ErrorCode::UpstreamUnavailable => {
+                    if called {
+                        node.breaker.failure();
+                    }
+                    if attempt >= attempts {
+                        return Err(e);
+                    }
+                    tracing::warn!("node {} call attempt {} failed: {}", node.addr, attempt, e);
+                    if attempt % candidates.len() == 0 {
+                        std::thread::sleep(delay);
+                        delay *= 2;
+                    }
+                    attempt += 1;
+                }
+                out => {
+                    // the node has replied, even if it was an error
+                    node.breaker.success();
+                    return out;
+                }
             }
-            _ => agent
-                .post(self.rpc_addr.as_str())
-                .set("Content-Type", "application/json"),
         }
     }
-}
 
-impl PartialEq for Client {
-    fn eq(&self, other: &Client) -> bool {
-        self.rpc_addr == other.rpc_addr
+    /// single HTTP exchange with the node, returns the body of the reply
+    fn post(&self, node: &Node, payload: &str) -> Result<String, ApiError> {
+        let res = match self.request(node)?.send_string(payload) {
+            Ok(x) => x,
+            Err(ureq::Error::Status(401, _)) => {
+                // the node was restarted with the new cookie
+                node.credentials.rejected();
+                return Err(ApiError::upstream_unavailable(format!(
+                    "node {} rejected the credentials",
+                    node.addr
+                )));
+            }
+            // node replies with 500 and the error object in the body
+            Err(ureq::Error::Status(_, x)) => x,
+            Err(e) => return Err(ApiError::upstream_unavailable(e.to_string())),
+        };
+        res.into_string()
+            .map_err(|e| ApiError::upstream_unavailable(e.to_string()))
     }
+
+    /// height of the node, asked regardless of its circuit breaker
+    fn probe(&self, node: &Node) -> Result<u64, ApiError> {
+        let payload = json!({
+            "jsonrpc": "1.0",
+            "id": "probe",
+            "method": "getblockcount",
+            "params": [],
+        })
+        .to_string();
+        let body = self.post(node, payload.as_str())?;
+        let reply: RpcReply = Self::parse(payload.as_str(), body.as_str())?;
+        decode("getblockcount", reply.into_result()?)
+    }
+
+    /// single call to the first node to be tried, without retries and failover,
+    /// limited to `timeout`. It doesn't count as a failure of the node
+    pub async fn call_once<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        timeout: Duration,
+    ) -> Result<T, ApiError> {
+        let node = match self.nodes.candidates(true).into_iter().next() {
+            Some(x) => x,
+            None => {
+                return Err(ApiError::upstream_unavailable(
+                    "bitcoin node is unavailable",
+                ))
+            }
+        };
+        let payload = json!({
+            "jsonrpc": "1.0",
+            "id": method,
+            "method": method,
+            "params": [],
+        })
+        .to_string();
+        let client = self.clone().with_timeout(timeout);
+        let out = spawn_blocking(move || {
+            let body = client.post(&node, payload.as_str())?;
+            Self::parse::<RpcReply>(payload.as_str(), body.as_str())?.into_result()
+        })
+        .await?;
+        decode(method, out)
+    }
+
+    fn parse<T: DeserializeOwned>(payload: &str, body: &str) -> Result<T, ApiError> {
+        serde_json::from_str(body).map_err(|e| {
+            tracing::info!("REQUEST >> {}", payload);
+            tracing::info!("RESPONSE {}", body);
+            tracing::error!("{}", e);
+            ApiError::internal_error("invalid node response")
+        })
+    }
+
+    /// blocking JSON-RPC call with the given payload
+    pub fn send(&self, payload: &str, read_only: bool) -> Result<Value, ApiError> {
+        self.retry(read_only, |node| {
+            let body = self.post(node, payload)?;
+            Self::parse::<RpcReply>(payload, body.as_str())?.into_result()
+        })
+    }
+
+    /// blocking JSON-RPC batch with the given payload,
+    /// results are returned as an array in the order of requests
+    pub fn send_batch(&self, payload: &str, read_only: bool) -> Result<Value, ApiError> {
+        self.retry(read_only, |node| {
+            let body = self.post(node, payload)?;
+            let mut replies: Vec<RpcReply> = Self::parse(payload, body.as_str())?;
+            // the node is free to reply in any order
+            replies.sort_by_key(|r| r.id.as_u64().unwrap_or(u64::MAX));
+            let results = replies
+                .into_iter()
+                .map(RpcReply::into_result)
+                .collect::<Result<Vec<Value>, ApiError>>()?;
+            Ok(Value::Array(results))
+        })
+    }
+
+    /// sends the payload on the blocking pool so the async executor is not stalled.
+    /// Concurrent identical payloads share a single request to the node.
+    async fn dispatch(
+        &self,
+        method: &str,
+        payload: String,
+        batch: bool,
+        read_only: bool,
+    ) -> Result<Value, ApiError> {
+        let client = self.clone();
+        let key = payload.clone();
+        let method = method.to_string();
+        let span = telemetry::rpc_span(method.as_str());
+        let blocking_span = span.clone();
+        self.inflight
+            .run(
+                key,
+                async move {
+                    let start = Instant::now();
+                    let metrics = client.metrics.clone();
+                    let out = spawn_blocking(move || {
+                        let _enter = blocking_span.enter();
+                        if batch {
+                            client.send_batch(payload.as_str(), read_only)
+                        } else {
+                            client.send(payload.as_str(), read_only)
+                        }
+                    })
+                    .await;
+                    metrics.observe_rpc(method.as_str(), out.is_ok(), start.elapsed());
+                    out
+                }
+                .instrument(span),
+            )
+            .await
+    }
+
+    /// JSON-RPC call
+    pub async fn call<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: Vec<Value>,
+    ) -> Result<T, ApiError> {
+        let payload = json!({
+            "jsonrpc": "1.0",
+            "id": method,
+            "method": method,
+            "params": params,
+        })
+        .to_string();
+        let read_only = node::is_read_only(method);
+        decode(
+            method,
+            self.dispatch(method, payload, false, read_only).await?,
+        )
+    }
+
+    /// JSON-RPC batch of calls in a single round trip to the node,
+    /// fails if any of the calls has failed
+    pub async fn call_batch(
+        &self,
+        calls: Vec<(&'static str, Vec<Value>)>,
+    ) -> Result<Vec<Value>, ApiError> {
+        if calls.is_empty() {
+            return Ok(vec![]);
+        }
+        let read_only = calls.iter().all(|(method, _)| node::is_read_only(method));
+        let count = calls.len();
+        let payload: Vec<Value> = calls
+            .into_iter()
+            .enumerate()
+            .map(|(id, (method, params))| {
+                json!({
+                    "jsonrpc": "1.0",
+                    "id": id,
+                    "method": method,
+                    "params": params,
+                })
+            })
+            .collect();
+        let payload = Value::Array(payload).to_string();
+        match self.dispatch("batch", payload, true, read_only).await? {
+            Value::Array(x) if x.len() == count => Ok(x),
+            Value::Array(x) => Err(ApiError::upstream_unavailable(format!(
+                "node replied to {} of {} batched calls",
+                x.len(),
+                count
+            ))),
+            _ => Err(ApiError::internal_error("invalid node response")),
+        }
+    }
+}
+
+/// decodes the result of the node call. The reply that doesn't match its type
+/// is not the failure of the node, so it is neither retried nor counted by the breaker
+pub fn decode<T: DeserializeOwned>(method: &str, value: Value) -> Result<T, ApiError> {
+    serde_json::from_value(value).map_err(|e| {
+        tracing::error!("{} response: {}", method, e);
+        ApiError::internal_error(format!("invalid {} response", method))
+    })
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcReplyError {
+    code: i32,
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcReply {
+    result: Option<Value>,
+    error: Option<RpcReplyError>,
+    #[serde(default)]
+    id: Value,
 }
-impl Eq for Client {}
-impl Hash for Client {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        self.rpc_addr.hash(state);
+
+impl RpcReply {
+    fn into_result(self) -> Result<Value, ApiError> {
+        match (self.result, self.error) {
+            (_, Some(e)) => Err(error::from_rpc_code(e.code, e.message)),
+            (Some(x), None) => Ok(x),
+            (None, None) => Err(ApiError::internal_error("empty node response")),
+        }
     }
 }
 
-#[cached(time = 60)]
-pub fn get_blockchain_info(rpcclient: Client) -> json::GetBlockchainInfoResult {
-    let out = rpcclient.core_client().get_blockchain_info().unwrap();
-    tracing::info!("get_blockchain_info: {:?}", out);
-    out
+pub async fn get_blockchain_info(
+    rpcclient: Client,
+) -> Result<json::GetBlockchainInfoResult, ApiError> {
+    let fetch = async {
+        let out: json::GetBlockchainInfoResult =
+            rpcclient.call("getblockchaininfo", vec![]).await?;
+        tracing::info!("get_blockchain_info: {:?}", out);
+        Ok(out)
+    };
+    let cache = &rpcclient.cache;
+    cache
+        .get_or_fetch(Kind::ChainInfo, String::new(), fetch, |_| None)
+        .await
+}
+
+/// confirmations of the block at the height as of the tip,
+/// the cached entries keep the height and the count is recomputed on read
+fn confirmations_at(tip: u64, height: u64) -> u64 {
+    (tip + 1).saturating_sub(height).max(1)
+}
+
+/// height of the best block, cached until the next block
+async fn tip_height(rpcclient: &Client) -> Result<u64, ApiError> {
+    Ok(get_blockchain_info(rpcclient.clone()).await?.blocks)
 }
 
-#[cached(time = 60)]
-pub fn get_latest_blocks(rpcclient: Client, pg: pager::Input) -> response::BlockList {
-    let client = rpcclient.clone().core_client();
-    let best_height_hash = match &pg.from {
+pub async fn get_latest_blocks(
+    rpcclient: Client,
+    pg: pager::Input,
+) -> Result<BlocksList, ApiError> {
+    let key = format!("{}:{}", pg.from.as_deref().unwrap_or("tip"), pg.limit);
+    let fetch = fetch_latest_blocks(&rpcclient, &pg);
+    let cache = &rpcclient.cache;
+    let mut out = cache
+        .get_or_fetch(Kind::Blocks, key, fetch, |out: &BlocksList| {
+            // the page from the tip changes with every block
+            pg.from.as_ref()?;
+            let first = out.list.first()?;
+            Some(first.header.confirmations.max(0) as u64)
+        })
+        .await?;
+    let tip = tip_height(&rpcclient).await?;
+    for block in out.list.iter_mut() {
+        let header = &mut block.header;
+        // blocks that are not in the best chain have -1
+        if header.confirmations > 0 {
+            header.confirmations = confirmations_at(tip, header.height as u64) as i32;
+        }
+    }
+    Ok(out)
+}
+
+async fn fetch_latest_blocks(
+    rpcclient: &Client,
+    pg: &pager::Input,
+) -> Result<BlocksList, ApiError> {
+    // height of the first block on the page
+    let top: u64 = match &pg.from {
         Some(block_hash_str) => {
             // take previous block, starting from "from" hash
             let hash = match bitcoin::BlockHash::from_hex(block_hash_str) {
                 Ok(x) => x,
                 Err(e) => {
                     let fe = format!("invalid from param {}", e);
-                    return response::BlockList::Failure(fe);
-                }
-            };
-            let block = match client.get_block_info(&hash) {
-                Ok(x) => x,
-                Err(e) => {
-                    let fe = format!("from param parsing error {}", e);
-                    return response::BlockList::Failure(fe);
+                    return Err(ApiError::invalid_param(fe));
                 }
             };
-            block.previousblockhash
+            let header: json::GetBlockHeaderResult = rpcclient
+                .call("getblockheader", vec![json!(hash.to_string()), json!(true)])
+                .await?;
+            match header.height.checked_sub(1) {
+                Some(x) => x as u64,
+                None => return Err(ApiError::invalid_param("invalid block offset")),
+            }
         }
         None => {
             // take the best block height
-            let chaininfo = match client.get_blockchain_info() {
-                Ok(x) => x,
-                Err(e) => {
-                    let fe = format!("cannot get chain state {}", e);
-                    return response::BlockList::Failure(fe);
-                }
-            };
-            Some(chaininfo.best_block_hash)
+            get_blockchain_info(rpcclient.clone()).await?.blocks
         }
     };
-    let mut ihash = match best_height_hash {
-        Some(x) => x,
-        None => return response::BlockList::Failure("invalid block offset".to_string()),
-    };
-    let mut i = pg.limit;
+    let count = std::cmp::min(pg.limit as u64, top + 1);
+
+    // hashes of the page in one round trip
+    let calls = (0..count)
+        .map(|i| ("getblockhash", vec![json!(top - i)]))
+        .collect();
+    let hashes = rpcclient.call_batch(calls).await?;
+
+    // headers and stats of the page in another one
+    let mut calls = vec![];
+    for hash in hashes {
+        calls.push(("getblockheader", vec![hash.clone(), json!(true)]));
+        calls.push(("getblockstats", vec![hash]));
+    }
+    let mut replies = rpcclient.call_batch(calls).await?.into_iter();
     let mut out = BlocksList::default();
-    while i > 0 {
-        let header = match client.get_block_header_info(&ihash) {
-            Ok(x) => x,
-            Err(e) => {
-                let fe = format!("error of getting block {}", e);
-                return response::BlockList::Failure(fe);
-            }
-        };
+    while let Some(header) = replies.next() {
+        let stats = replies
+            .next()
+            .ok_or_else(|| ApiError::upstream_unavailable("no getblockstats reply for header"))?;
         out.list.push(Block {
-            header: header.clone(),
-            stats: get_block_stats(rpcclient.clone(), ihash),
+            header: decode("getblockheader", header)?,
+            stats: decode("getblockstats", stats)?,
         });
-        i = i - 1;
-        match &header.previous_block_hash {
-            Some(prevhash) => {
-                ihash = prevhash.clone();
-                if i == 0 {
-                    out.pager = Some(pager::Output {
-                        from: Some(ihash.clone().to_string()),
-                    })
-                }
-            }
-            None => i = 0,
+    }
+    if let Some(last) = out.list.last() {
+        if count == pg.limit as u64 && last.header.previous_block_hash.is_some() {
+            out.pager = Some(pager::Output {
+                from: Some(last.header.hash.to_string()),
+            })
         }
     }
-    response::BlockList::Blocks(out)
+    Ok(out)
 }
 
-#[cached(time = 60)]
-pub fn get_block_info(rpcclient: Client, hash: bitcoin::BlockHash, pg: pager::Input) -> response::Block {
-    let out = rpcclient.core_client().get_block_info(&hash);
-    tracing::info!("get_block_info: {:?}", out);
-    match out {
-        Ok(block) => {
-            let stats = get_block_stats(rpcclient, hash);
-            tracing::info!("get_block_stats: {:?}", stats);
-            response::Block::Block { block, stats }
-        }
-        Err(e) => response::Block::Failure(e.to_string()),
+pub async fn get_block_info(
+    rpcclient: Client,
+    hash: bitcoin::BlockHash,
+    _pg: pager::Input,
+) -> Result<BlockDetails, ApiError> {
+    let fetch = async {
+        let block: json::GetBlockResult = rpcclient
+            .call("getblock", vec![json!(hash.to_string()), json!(1)])
+            .await?;
+        tracing::info!("get_block_info: {:?}", block);
+        let stats = get_block_stats(rpcclient.clone(), hash).await?;
+        tracing::info!("get_block_stats: {:?}", stats);
+        Ok(BlockDetails { block, stats })
+    };
+    let cache = &rpcclient.cache;
+    let mut out = cache
+        .get_or_fetch(
+            Kind::Block,
+            hash.to_string(),
+            fetch,
+            |out: &BlockDetails| Some(out.block.confirmations.max(0) as u64),
+        )
+        .await?;
+    if out.block.confirmations > 0 {
+        let tip = tip_height(&rpcclient).await?;
+        out.block.confirmations = confirmations_at(tip, out.block.height as u64) as i32;
     }
+    Ok(out)
+}
+
+/// transaction as it is cached, with the height of its block.
+/// The reply of the node is kept as is, `GetRawTransactionResult` does not read back
+/// the `null` fields it writes
+#[derive(Serialize, Deserialize)]
+struct CachedTx {
+    tx: Value,
+    // none while the transaction is in the mempool
+    height: Option<u64>,
 }
 
-#[cached(time = 60)]
-pub fn get_raw_transaction_info(rpcclient: Client, hash: bitcoin::Txid) -> response::Tx {
-    let out = rpcclient
-        .core_client()
-        .get_raw_transaction_info(&hash, None);
-    tracing::info!("get_raw_transaction_info: {:?}", out);
-    match out {
-        Ok(res) => response::Tx::Tx(res),
-        Err(e) => response::Tx::Failure(e.to_string()),
+pub async fn get_raw_transaction_info(
+    rpcclient: Client,
+    hash: bitcoin::Txid,
+) -> Result<json::GetRawTransactionResult, ApiError> {
+    let fetch = async {
+        let tx: Value = rpcclient
+            .call(
+                "getrawtransaction",
+                vec![json!(hash.to_string()), json!(true)],
+            )
+            .await?;
+        let height = match tx["blockhash"].as_str() {
+            Some(blockhash) => {
+                let header: json::GetBlockHeaderResult = rpcclient
+                    .call("getblockheader", vec![json!(blockhash), json!(true)])
+                    .await?;
+                Some(header.height as u64)
+            }
+            None => None,
+        };
+        Ok(CachedTx { tx, height })
+    };
+    let cache = &rpcclient.cache;
+    let cached = cache
+        .get_or_fetch(Kind::Tx, hash.to_string(), fetch, |out: &CachedTx| {
+            out.tx["confirmations"].as_u64()
+        })
+        .await?;
+    let mut tx: json::GetRawTransactionResult = decode("getrawtransaction", cached.tx)?;
+    // transactions of the blocks that are not in the best chain have 0
+    if let (Some(height), Some(x)) = (cached.height, tx.confirmations) {
+        if x > 0 {
+            let tip = tip_height(&rpcclient).await?;
+            tx.confirmations = Some(confirmations_at(tip, height) as u32);
+        }
     }
+    Ok(tx)
 }
 
-#[cached(time = 60)]
-pub fn get_address_history(_rpcclient: Client, _hash: bitcoin::Address, _pg: pager::Input) -> response::Address {
+pub async fn get_address_history(
+    _rpcclient: Client,
+    _hash: bitcoin::Address,
+    _pg: pager::Input,
+) -> Result<TxList, ApiError> {
     // TODO:
-    response::Address::Failure("not implemented".to_string())
+    Err(ApiError::not_indexed("address index is not available"))
+}
+
+/// checks whether the node would accept the raw transaction into its mempool, without relaying it
+pub async fn test_mempool_accept(rpcclient: Client, hex: &str) -> Result<MempoolAccept, ApiError> {
+    let out: Vec<MempoolAccept> = rpcclient
+        .call("testmempoolaccept", vec![json!([hex])])
+        .await?;
+    out.into_iter()
+        .next()
+        .ok_or_else(|| ApiError::upstream_unavailable("empty testmempoolaccept reply"))
+}
+
+/// relays the raw transaction to the network, returns its txid
+pub async fn send_raw_transaction(rpcclient: Client, hex: &str) -> Result<String, ApiError> {
+    rpcclient.call("sendrawtransaction", vec![json!(hex)]).await
 }
 
 /// this method is not in the library yet
-#[cached(time = 600)]
-pub fn get_block_stats(rpcclient: Client, hash: bitcoin::BlockHash) -> BlockStatsInfo {
-    let payload = format!(
-        "{{\"jsonrpc\":\"1.0\",\"id\":\"{}\",\"method\":\"getblockstats\",\"params\":[\"{}\"]}}",
-        hash, hash,
-    );
-    let stats_str: String = rpcclient
-        .request()
-        .send_string(payload.as_str())
-        .unwrap()
-        .into_string()
-        .unwrap();
-    let response: BlockStatsResponse = match serde_json::from_str(stats_str.as_str()) {
-        Ok(x) => x,
-        Err(e) => {
-            tracing::info!("REQUEST >> {}", payload);
-            tracing::info!("RESPONSE {}", stats_str);
-            tracing::error!("{}", e);
-            BlockStatsResponse::default()
+pub async fn get_block_stats(
+    rpcclient: Client,
+    hash: bitcoin::BlockHash,
+) -> Result<BlockStatsInfo, ApiError> {
+    let fetch = rpcclient.call("getblockstats", vec![json!(hash.to_string())]);
+    let cache = &rpcclient.cache;
+    // stats of the block with the given hash never change
+    cache
+        .get_or_fetch(Kind::BlockStats, hash.to_string(), fetch, |_| {
+            Some(cache::DEEP_CONFIRMATIONS)
+        })
+        .await
+}
+
+/// probes every node, so the dead ones are brought back
+/// and the ones behind the others are not asked for reads
+pub async fn watch_nodes(rpcclient: Client, every: Duration) {
+    loop {
+        for node in rpcclient.nodes.nodes().iter() {
+            let (client, probed) = (rpcclient.clone(), node.clone());
+            match spawn_blocking(move || client.probe(&probed)).await {
+                Ok(height) => {
+                    if node.is_down() {
+                        tracing::info!("node {} is back at height {}", node.addr, height);
+                    }
+                    node.breaker.success();
+                    node.set_height(height);
+                }
+                Err(e) => {
+                    node.breaker.failure();
+                    tracing::warn!("node {} probe failed: {}", node.addr, e);
+                }
+            }
         }
-    };
-    response.result
+        async_std::task::sleep(every).await;
+    }
+}
+
+/// watches the tip of the chain and drops volatile cache entries on a new block
+pub async fn watch_tip(rpcclient: Client, every: Duration) {
+    let mut tip: Option<String> = None;
+    loop {
+        async_std::task::sleep(every).await;
+        match rpcclient.call::<String>("getbestblockhash", vec![]).await {
+            Ok(hash) => {
+                if tip.as_ref() == Some(&hash) {
+                    continue;
+                }
+                if tip.is_some() {
+                    tracing::info!("new block {}, invalidating cache", hash);
+                    rpcclient.cache.invalidate_tip().await;
+                }
+                tip = Some(hash);
+            }
+            Err(e) => tracing::warn!("cannot watch the tip: {}", e),
+        }
+    }
 }
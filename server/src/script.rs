@@ -0,0 +1,163 @@
+//! Kind of the output script, the same classification under the names
+//! of Bitcoin Core for `/api` and of Esplora for `/esplora`,
+//! and the assembly notation of the scripts as Bitcoin Core writes it
+
+use bitcoin::blockdata::script::Instruction;
+use bitcoin::hashes::hex::ToHex;
+use bitcoincore_rpc_json::bitcoin;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScriptType {
+    P2pk,
+    P2pkh,
+    P2sh,
+    V0P2wpkh,
+    V0P2wsh,
+    V1P2tr,
+    OpReturn,
+    Unknown,
+}
+
+impl ScriptType {
+    pub fn of(script: &bitcoin::Script) -> Self {
+        let bytes = script.as_bytes();
+        if script.is_p2pkh() {
+            Self::P2pkh
+        } else if script.is_p2sh() {
+            Self::P2sh
+        } else if script.is_v0_p2wpkh() {
+            Self::V0P2wpkh
+        } else if script.is_v0_p2wsh() {
+            Self::V0P2wsh
+        } else if bytes.len() == 34 && bytes[0] == 0x51 && bytes[1] == 0x20 {
+            // OP_1 and the 32-byte key
+            Self::V1P2tr
+        } else if script.is_p2pk() {
+            Self::P2pk
+        } else if script.is_op_return() {
+            Self::OpReturn
+        } else {
+            Self::Unknown
+        }
+    }
+
+    /// name in `scriptPubKey.type` of Bitcoin Core
+    pub fn core_name(self) -> &'static str {
+        match self {
+            Self::P2pk => "pubkey",
+            Self::P2pkh => "pubkeyhash",
+            Self::P2sh => "scripthash",
+            Self::V0P2wpkh => "witness_v0_keyhash",
+            Self::V0P2wsh => "witness_v0_scripthash",
+            Self::V1P2tr => "witness_v1_taproot",
+            Self::OpReturn => "nulldata",
+            Self::Unknown => "nonstandard",
+        }
+    }
+
+    /// name in `scriptpubkey_type` of Esplora
+    pub fn esplora_name(self) -> &'static str {
+        match self {
+            Self::P2pk => "p2pk",
+            Self::P2pkh => "p2pkh",
+            Self::P2sh => "p2sh",
+            Self::V0P2wpkh => "v0_p2wpkh",
+            Self::V0P2wsh => "v0_p2wsh",
+            Self::V1P2tr => "v1_p2tr",
+            Self::OpReturn => "op_return",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+// longest script that can be spent, longer ones are unspendable as `OP_RETURN` ones
+const MAX_SCRIPT_SIZE: usize = 10_000;
+
+/// number of the push of up to 4 bytes, little-endian with the sign bit
+fn script_num(bytes: &[u8]) -> i64 {
+    let mut out: i64 = 0;
+    for (i, b) in bytes.iter().enumerate() {
+        out |= (*b as i64) << (8 * i);
+    }
+    match bytes.last() {
+        Some(last) if last & 0x80 != 0 => -(out & !(0x80 << (8 * (bytes.len() - 1)))),
+        _ => out,
+    }
+}
+
+/// name of the opcode that doesn't push data
+fn op_name(op: bitcoin::blockdata::opcodes::All) -> String {
+    match op.into_u8() {
+        0x4f => "-1".to_string(),
+        x @ 0x51..=0x60 => (x - 0x50).to_string(),
+        0xb1 => "OP_CHECKLOCKTIMEVERIFY".to_string(),
+        0xb2 => "OP_CHECKSEQUENCEVERIFY".to_string(),
+        0xba => "OP_CHECKSIGADD".to_string(),
+        0xbb..=0xfe => "OP_UNKNOWN".to_string(),
+        0xff => "OP_INVALIDOPCODE".to_string(),
+        _ => format!("{:?}", op),
+    }
+}
+
+/// strict DER encoding of the signature followed by the sighash type, as of BIP 66
+fn is_der_signature(sig: &[u8]) -> bool {
+    if sig.len() < 9 || sig.len() > 73 || sig[0] != 0x30 || sig[1] as usize != sig.len() - 3 {
+        return false;
+    }
+    let len_r = sig[3] as usize;
+    if 5 + len_r >= sig.len() {
+        return false;
+    }
+    let len_s = sig[5 + len_r] as usize;
+    if len_r + len_s + 7 != sig.len() || sig[2] != 0x02 || len_r == 0 || sig[4] & 0x80 != 0 {
+        return false;
+    }
+    if len_r > 1 && sig[4] == 0x00 && sig[5] & 0x80 == 0 {
+        return false;
+    }
+    if sig[len_r + 4] != 0x02 || len_s == 0 || sig[len_r + 6] & 0x80 != 0 {
+        return false;
+    }
+    !(len_s > 1 && sig[len_r + 6] == 0x00 && sig[len_r + 7] & 0x80 == 0)
+}
+
+/// name of the sighash type of the signature
+fn sighash_name(sighash: u8) -> Option<&'static str> {
+    match sighash {
+        0x01 => Some("ALL"),
+        0x02 => Some("NONE"),
+        0x03 => Some("SINGLE"),
+        0x81 => Some("ALL|ANYONECANPAY"),
+        0x82 => Some("NONE|ANYONECANPAY"),
+        0x83 => Some("SINGLE|ANYONECANPAY"),
+        _ => None,
+    }
+}
+
+/// script in the assembly notation of `asm` fields of Bitcoin Core.
+/// With `sighash`, the signatures of the input script end with their sighash type, i.e. `[ALL]`
+pub fn asm(script: &bitcoin::Script, sighash: bool) -> String {
+    let bytes = script.as_bytes();
+    let unspendable = bytes.first() == Some(&0x6a) || bytes.len() > MAX_SCRIPT_SIZE;
+    let mut out: Vec<String> = vec![];
+    for instruction in script.instructions() {
+        let text = match instruction {
+            Ok(Instruction::PushBytes(data)) if data.len() <= 4 => script_num(data).to_string(),
+            Ok(Instruction::PushBytes(data)) if sighash && !unspendable => {
+                let (last, sig) = data.split_last().unwrap_or((&0, data));
+                match sighash_name(*last).filter(|_| is_der_signature(data)) {
+                    Some(name) => format!("{}[{}]", sig.to_hex(), name),
+                    None => data.to_hex(),
+                }
+            }
+            Ok(Instruction::PushBytes(data)) => data.to_hex(),
+            Ok(Instruction::Op(op)) => op_name(op),
+            Err(_) => {
+                out.push("[error]".to_string());
+                break;
+            }
+        };
+        out.push(text);
+    }
+    out.join(" ")
+}
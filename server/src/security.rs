@@ -0,0 +1,108 @@
+use crate::args::Args;
+use tide::http::headers::HeaderValue;
+use tide::http::proxies::Forwarded;
+use tide::security::{CorsMiddleware, Origin};
+use tide::{Next, Request};
+
+// headers of the requests that the API clients are allowed to send
+const ALLOW_HEADERS: &str = "Content-Type, If-None-Match, X-API-Key, traceparent, tracestate";
+// headers of the replies that the scripts of other origins may read
+const EXPOSE_HEADERS: &str =
+    "ETag, Last-Modified, Retry-After, X-RateLimit-Limit, X-RateLimit-Remaining";
+
+fn header(value: &str) -> anyhow::Result<HeaderValue> {
+    value
+        .parse()
+        .map_err(|e: tide::Error| anyhow::anyhow!("invalid header value {}: {}", value, e))
+}
+
+/// CORS for the frontends on other origins, none if `--cors-origins` is empty
+pub fn cors(src: &Args) -> anyhow::Result<Option<CorsMiddleware>> {
+    let origins: Vec<&str> = src
+        .cors_origins
+        .split(',')
+        .map(str::trim)
+        .filter(|x| !x.is_empty())
+        .collect();
+    if origins.is_empty() {
+        return Ok(None);
+    }
+    let cors = CorsMiddleware::new()
+        .allow_origin(Origin::from(origins))
+        .allow_methods(header(&src.cors_methods)?)
+        .allow_headers(header(ALLOW_HEADERS)?)
+        .expose_headers(header(EXPOSE_HEADERS)?)
+        .max_age(header(&src.cors_max_age.to_string())?);
+    Ok(Some(cors))
+}
+
+/// Adds security headers to every response
+/// unless the handler has set them already
+#[derive(Debug, Clone)]
+pub struct Headers {
+    csp: Option<String>,
+    hsts: Option<String>,
+    // the server terminates TLS itself
+    tls: bool,
+    // the scheme of the request is taken from `Forwarded` and `X-Forwarded-Proto`
+    trust_proxy: bool,
+}
+
+impl Headers {
+    pub fn from_args(src: &Args) -> Self {
+        let csp = if src.csp.is_empty() {
+            None
+        } else {
+            Some(src.csp.clone())
+        };
+        let hsts = if src.hsts_max_age > 0 {
+            Some(format!("max-age={}; includeSubDomains", src.hsts_max_age))
+        } else {
+            None
+        };
+        Self {
+            csp,
+            hsts,
+            tls: !src.tls_cert.is_empty(),
+            trust_proxy: src.trust_proxy,
+        }
+    }
+
+    /// whether the client has reached the server over HTTPS
+    fn is_https<State>(&self, req: &Request<State>) -> bool {
+        if self.tls || req.url().scheme() == "https" {
+            return true;
+        }
+        if !self.trust_proxy {
+            return false;
+        }
+        match Forwarded::from_headers(req) {
+            Ok(Some(x)) => x.proto() == Some("https"),
+            _ => false,
+        }
+    }
+}
+
+#[tide::utils::async_trait]
+impl<State: Clone + Send + Sync + 'static> tide::Middleware<State> for Headers {
+    async fn handle(&self, req: Request<State>, next: Next<'_, State>) -> tide::Result {
+        // HSTS over plain HTTP is ignored by the browsers at best
+        let hsts = self.hsts.as_ref().filter(|_| self.is_https(&req));
+        let mut res = next.run(req).await;
+        let mut set = |name: &str, value: &str| {
+            if res.header(name).is_none() {
+                res.insert_header(name, value);
+            }
+        };
+        set("X-Content-Type-Options", "nosniff");
+        set("X-Frame-Options", "DENY");
+        set("Referrer-Policy", "strict-origin-when-cross-origin");
+        if let Some(csp) = &self.csp {
+            set("Content-Security-Policy", csp.as_str());
+        }
+        if let Some(hsts) = hsts {
+            set("Strict-Transport-Security", hsts.as_str());
+        }
+        Ok(res)
+    }
+}
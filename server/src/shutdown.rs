@@ -0,0 +1,138 @@
+use async_std::channel::{bounded, Receiver, Sender};
+use signal_hook::consts::{SIGINT, SIGTERM};
+use signal_hook::iterator::Signals;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tide::{Next, Request};
+
+struct Inner {
+    draining: AtomicBool,
+    in_flight: AtomicUsize,
+    // dropped on shutdown, which wakes up all receivers
+    trigger: Mutex<Option<Sender<()>>>,
+    done: Receiver<()>,
+}
+
+/// Shutdown of the server: readiness check fails first,
+/// then new connections are refused and requests in flight are drained
+#[derive(Clone)]
+pub struct Shutdown {
+    inner: Arc<Inner>,
+}
+
+impl std::fmt::Debug for Shutdown {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Shutdown")
+            .field("draining", &self.is_draining())
+            .field("in_flight", &self.in_flight())
+            .finish()
+    }
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        let (tx, rx) = bounded(1);
+        Self {
+            inner: Arc::new(Inner {
+                draining: AtomicBool::new(false),
+                in_flight: AtomicUsize::new(0),
+                trigger: Mutex::new(Some(tx)),
+                done: rx,
+            }),
+        }
+    }
+}
+
+impl Shutdown {
+    /// starts the shutdown on SIGTERM or SIGINT,
+    /// the second signal terminates the process immediately
+    pub fn listen_signals(&self) -> anyhow::Result<()> {
+        let mut signals = Signals::new([SIGTERM, SIGINT])?;
+        let this = self.clone();
+        std::thread::spawn(move || {
+            for signal in signals.forever() {
+                if this.is_draining() {
+                    tracing::warn!(signal, "terminating without draining");
+                    std::process::exit(1);
+                }
+                tracing::info!(signal, "shutting down");
+                this.trigger();
+            }
+        });
+        Ok(())
+    }
+
+    pub fn trigger(&self) {
+        self.inner.draining.store(true, Ordering::SeqCst);
+        self.inner.trigger.lock().unwrap().take();
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.inner.draining.load(Ordering::SeqCst)
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.inner.in_flight.load(Ordering::SeqCst)
+    }
+
+    /// completes when the shutdown is started
+    pub async fn wait(&self) {
+        // nothing is ever sent, the channel is closed on shutdown
+        let _ = self.inner.done.recv().await;
+    }
+
+    /// waits until all requests in flight are finished or the deadline is reached
+    pub async fn drain(&self, deadline: Duration) {
+        let start = Instant::now();
+        while self.in_flight() > 0 {
+            if start.elapsed() >= deadline {
+                tracing::warn!(
+                    in_flight = self.in_flight(),
+                    "drain deadline reached, dropping requests"
+                );
+                return;
+            }
+            async_std::task::sleep(Duration::from_millis(100)).await;
+        }
+        tracing::info!(
+            took_ms = start.elapsed().as_millis() as u64,
+            "requests drained"
+        );
+    }
+}
+
+struct Guard<'a>(&'a AtomicUsize);
+
+impl Drop for Guard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Counts requests in flight and asks clients to close
+/// keep-alive connections once the shutdown is started
+#[derive(Clone)]
+pub struct Middleware {
+    shutdown: Shutdown,
+}
+
+impl Middleware {
+    pub fn new(shutdown: Shutdown) -> Self {
+        Self { shutdown }
+    }
+}
+
+#[tide::utils::async_trait]
+impl<State: Clone + Send + Sync + 'static> tide::Middleware<State> for Middleware {
+    async fn handle(&self, req: Request<State>, next: Next<'_, State>) -> tide::Result {
+        let counter = &self.shutdown.inner.in_flight;
+        counter.fetch_add(1, Ordering::SeqCst);
+        let _guard = Guard(counter);
+        let mut res = next.run(req).await;
+        if self.shutdown.is_draining() {
+            res.insert_header("Connection", "close");
+        }
+        Ok(res)
+    }
+}
@@ -0,0 +1,57 @@
+use crate::error::ApiError;
+use futures::future::{BoxFuture, FutureExt, Shared};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+type Call = Shared<BoxFuture<'static, Result<Value, ApiError>>>;
+
+/// Deduplication of concurrent identical calls:
+/// while a call with the same key is in flight, callers wait for its result
+/// instead of sending another request to the node.
+#[derive(Clone, Default)]
+pub struct Group {
+    inflight: Arc<Mutex<HashMap<String, Call>>>,
+}
+
+impl fmt::Debug for Group {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let inflight = self.inflight.lock().map(|m| m.len()).unwrap_or_default();
+        f.debug_struct("Group")
+            .field("inflight", &inflight)
+            .finish()
+    }
+}
+
+impl Group {
+    pub async fn run<F>(&self, key: String, f: F) -> Result<Value, ApiError>
+    where
+        F: Future<Output = Result<Value, ApiError>> + Send + 'static,
+    {
+        let call = {
+            let mut inflight = self.inflight.lock().unwrap_or_else(|e| e.into_inner());
+            match inflight.get(&key) {
+                Some(call) => call.clone(),
+                None => {
+                    let registry = self.inflight.clone();
+                    let k = key.clone();
+                    let call = async move {
+                        let out = f.await;
+                        registry
+                            .lock()
+                            .unwrap_or_else(|e| e.into_inner())
+                            .remove(&k);
+                        out
+                    }
+                    .boxed()
+                    .shared();
+                    inflight.insert(key, call.clone());
+                    call
+                }
+            }
+        };
+        call.await
+    }
+}
@@ -1,14 +1,116 @@
+use std::collections::HashMap;
 use std::time::Instant;
 
+use crate::args::{Args, LogFormat, TraceSampler};
+use opentelemetry::sdk::propagation::TraceContextPropagator;
+use opentelemetry::sdk::trace::{self, Sampler};
+use opentelemetry::sdk::Resource;
+use opentelemetry::KeyValue;
 use tide::{Middleware, Next, Request};
-use tracing::{error, error_span, field, info, info_span, warn, warn_span};
+use tracing::{error, error_span, field, info, info_span, warn, warn_span, Span};
 use tracing_futures::Instrument;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
 
-/// Log all incoming requests and responses with tracing spans.
+// log filter used when `RUST_LOG` is not set
+const DEFAULT_FILTER: &str = "sqlx=warn,info";
+
+/// Installs the subscriber writing logs to stdout and,
+/// if the collector endpoint is configured, exporting spans with OTLP
+pub fn init(args: &Args) -> anyhow::Result<()> {
+    opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+    let filter = tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new(DEFAULT_FILTER));
+    let (text, json) = match args.log_format {
+        LogFormat::TEXT => (Some(tracing_subscriber::fmt::layer()), None),
+        LogFormat::JSON => (None, Some(tracing_subscriber::fmt::layer().json())),
+    };
+    let otel = if args.otlp_endpoint.is_empty() {
+        None
+    } else {
+        let config = trace::config()
+            .with_sampler(sampler(&args.trace_sampler, args.trace_ratio))
+            .with_resource(Resource::new(vec![KeyValue::new(
+                "service.name",
+                "bitcoin-explorer",
+            )]));
+        let tracer = opentelemetry_otlp::new_pipeline()
+            .with_endpoint(args.otlp_endpoint.as_str())
+            .with_trace_config(config)
+            .with_tonic()
+            .install_batch(opentelemetry::runtime::AsyncStd)?;
+        Some(tracing_opentelemetry::layer().with_tracer(tracer))
+    };
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(text)
+        .with(json)
+        .with(otel)
+        .try_init()?;
+    Ok(())
+}
+
+/// Flushes the spans that were not exported yet
+pub fn shutdown() {
+    opentelemetry::global::shutdown_tracer_provider();
+}
+
+fn sampler(kind: &TraceSampler, ratio: f64) -> Sampler {
+    match kind {
+        TraceSampler::ALWAYS => Sampler::AlwaysOn,
+        TraceSampler::NEVER => Sampler::AlwaysOff,
+        TraceSampler::RATIO => Sampler::TraceIdRatioBased(ratio),
+        // follows the decision of the caller, when there is `traceparent`
+        TraceSampler::PARENT => Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(ratio))),
+    }
+}
+
+/// Span of the JSON-RPC call to the node
+pub fn rpc_span(method: &str) -> Span {
+    info_span!(
+        "rpc",
+        otel.name = %format!("rpc {}", method),
+        otel.kind = "client",
+        rpc.system = "jsonrpc",
+        rpc.method = %method,
+    )
+}
+
+/// Span of the SQL statement
+pub fn sql_span(statement: &'static str) -> Span {
+    info_span!(
+        "sql",
+        otel.kind = "client",
+        db.system = "postgresql",
+        db.statement = statement,
+    )
+}
+
+/// W3C trace context headers of the current span, to be sent with the outgoing request
+pub fn trace_headers() -> HashMap<String, String> {
+    let cx = Span::current().context();
+    let mut headers = HashMap::new();
+    opentelemetry::global::get_text_map_propagator(|p| p.inject_context(&cx, &mut headers));
+    headers
+}
+
+/// continues the trace of the caller, given by `traceparent` header
+fn link_parent<State>(req: &Request<State>, span: &Span) {
+    let mut headers = HashMap::new();
+    for (name, values) in req.iter() {
+        headers.insert(name.as_str().to_lowercase(), values.last().to_string());
+    }
+    let cx = opentelemetry::global::get_text_map_propagator(|p| p.extract(&headers));
+    span.set_parent(cx);
+}
+
+/// Log all incoming requests and responses with tracing spans,
+/// continuing the trace of the caller given by `traceparent`.
 ///
 /// ```
-/// let mut app = tide::Server::new();
-/// app.with(tide_tracing::TraceMiddleware::new());
+/// let mut app = tide::new();
+/// app.with(bitcoin_explorer::telemetry::TraceMiddleware::new());
 /// ```
 #[derive(Debug, Default, Clone)]
 pub struct TraceMiddleware;
@@ -35,6 +137,9 @@ impl TraceMiddleware {
             None => "".to_owned(),
         };
 
+        let span = info_span!("Request", otel.name = %format!("{} {}", method, path), otel.kind = "server", http.method = %method, http.url = %path, remote = %remote, agent = %ua);
+        link_parent(&ctx, &span);
+
         Ok(async {
             let start = Instant::now();
             let response = next.run(ctx).await;
@@ -57,7 +162,7 @@ impl TraceMiddleware {
                 });
             response
         }
-        .instrument(info_span!("Request", http.method = %method, http.url = %path, remote = %remote, agent = %ua))
+        .instrument(span)
         .await)
     }
 }
@@ -0,0 +1,188 @@
+use crate::args::Args;
+use crate::State;
+use async_dup::{Arc as DupArc, Mutex as DupMutex};
+use async_std::net::{TcpListener, TcpStream};
+use async_std::stream::StreamExt;
+use async_tls::TlsAcceptor;
+use rustls::internal::pemfile;
+use rustls::sign::{self, CertifiedKey};
+use rustls::{ClientHello, NoClientAuth, ResolvesServerCert, ServerConfig};
+use std::fs::File;
+use std::io::BufReader;
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+// how often the certificate files are checked for changes
+const RELOAD_INTERVAL: Duration = Duration::from_secs(30);
+
+/// reads the certificate chain and the private key from PEM files
+fn load(cert_path: &str, key_path: &str) -> anyhow::Result<CertifiedKey> {
+    let certs = pemfile::certs(&mut BufReader::new(File::open(cert_path)?))
+        .map_err(|_| anyhow::anyhow!("invalid certificate {}", cert_path))?;
+    if certs.is_empty() {
+        anyhow::bail!("no certificates in {}", cert_path);
+    }
+    let mut keys = pemfile::pkcs8_private_keys(&mut BufReader::new(File::open(key_path)?))
+        .map_err(|_| anyhow::anyhow!("invalid private key {}", key_path))?;
+    if keys.is_empty() {
+        keys = pemfile::rsa_private_keys(&mut BufReader::new(File::open(key_path)?))
+            .map_err(|_| anyhow::anyhow!("invalid private key {}", key_path))?;
+    }
+    let key = match keys.first() {
+        Some(x) => sign::any_supported_type(x)
+            .map_err(|_| anyhow::anyhow!("unsupported private key {}", key_path))?,
+        None => anyhow::bail!("no private key in {}", key_path),
+    };
+    Ok(CertifiedKey::new(certs, Arc::new(key)))
+}
+
+fn modified(path: &str) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Certificate that is replaced when its files change on disk,
+/// so renewed certificates are picked up without restart
+pub struct Reloading {
+    cert_path: String,
+    key_path: String,
+    current: RwLock<(CertifiedKey, Option<SystemTime>, Option<SystemTime>)>,
+}
+
+impl Reloading {
+    pub fn new(cert_path: &str, key_path: &str) -> anyhow::Result<Self> {
+        let key = load(cert_path, key_path)?;
+        Ok(Self {
+            cert_path: cert_path.to_string(),
+            key_path: key_path.to_string(),
+            current: RwLock::new((key, modified(cert_path), modified(key_path))),
+        })
+    }
+
+    /// reloads the certificate if any of the files was modified.
+    /// Keeps the previous one when the new files can't be loaded,
+    /// i.e. while they are half-written
+    pub fn reload(&self) {
+        let (cert_mtime, key_mtime) = (modified(&self.cert_path), modified(&self.key_path));
+        {
+            let current = self.current.read().unwrap();
+            if current.1 == cert_mtime && current.2 == key_mtime {
+                return;
+            }
+        }
+        match load(&self.cert_path, &self.key_path) {
+            Ok(key) => {
+                *self.current.write().unwrap() = (key, cert_mtime, key_mtime);
+                tracing::info!("TLS certificate reloaded from {}", self.cert_path);
+            }
+            Err(e) => tracing::warn!("TLS certificate reload failure: {}", e),
+        }
+    }
+}
+
+impl ResolvesServerCert for Reloading {
+    fn resolve(&self, _: ClientHello) -> Option<CertifiedKey> {
+        Some(self.current.read().unwrap().0.clone())
+    }
+}
+
+async fn watch(certs: Arc<Reloading>) {
+    loop {
+        async_std::task::sleep(RELOAD_INTERVAL).await;
+        certs.reload();
+    }
+}
+
+async fn serve_conn(
+    app: tide::Server<State>,
+    acceptor: TlsAcceptor,
+    stream: TcpStream,
+) -> anyhow::Result<()> {
+    let peer = stream.peer_addr().ok();
+    let local = stream.local_addr().ok();
+    let stream = acceptor.accept(stream).await?;
+    // `async_h1` reads and writes the connection through clones
+    let stream = DupArc::new(DupMutex::new(stream));
+    async_h1::accept(stream, |mut req| {
+        let app = app.clone();
+        req.set_peer_addr(peer);
+        req.set_local_addr(local);
+        req.url_mut().set_scheme("https").ok();
+        async move { app.respond(req).await }
+    })
+    .await
+    .map_err(|e| anyhow::anyhow!("{}", e))
+}
+
+/// serves the app over HTTPS on `--listen` address
+pub async fn listen(app: tide::Server<State>, src: &Args) -> anyhow::Result<()> {
+    let certs = Arc::new(Reloading::new(&src.tls_cert, &src.tls_key)?);
+    async_std::task::spawn(watch(certs.clone()));
+    let mut config = ServerConfig::new(NoClientAuth::new());
+    config.cert_resolver = certs;
+    config.set_protocols(&[b"http/1.1".to_vec()]);
+    let acceptor = TlsAcceptor::from(Arc::new(config));
+
+    let listener = TcpListener::bind(src.listen.as_str()).await?;
+    tracing::info!("Server listening on https://{}", listener.local_addr()?);
+    let mut incoming = listener.incoming();
+    while let Some(stream) = incoming.next().await {
+        let stream = match stream {
+            Ok(x) => x,
+            Err(e) => {
+                tracing::warn!("accept failure: {}", e);
+                continue;
+            }
+        };
+        let (app, acceptor) = (app.clone(), acceptor.clone());
+        async_std::task::spawn(async move {
+            if let Err(e) = serve_conn(app, acceptor, stream).await {
+                tracing::debug!("TLS connection error: {}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+/// location on the HTTPS listener for the plain HTTP request
+fn https_location(req: &tide::Request<()>, https_port: u16) -> String {
+    let host = req
+        .host()
+        .or_else(|| req.url().host_str())
+        .unwrap_or("localhost");
+    // strip the port of the plain listener, keeping IPv6 literals
+    let host = match host.rfind(':') {
+        Some(i) if !host[i..].contains(']') => &host[..i],
+        _ => host,
+    };
+    let mut location = if https_port == 443 {
+        format!("https://{}{}", host, req.url().path())
+    } else {
+        format!("https://{}:{}{}", host, https_port, req.url().path())
+    };
+    if let Some(query) = req.url().query() {
+        location.push('?');
+        location.push_str(query);
+    }
+    location
+}
+
+/// redirects all plain HTTP requests on `--http-redirect` address to HTTPS
+pub async fn redirect(src: Args) -> anyhow::Result<()> {
+    let https_port = src
+        .listen
+        .parse::<SocketAddr>()
+        .map(|x| x.port())
+        .unwrap_or(443);
+    let mut app = tide::new();
+    let handler = move |req: tide::Request<()>| async move {
+        let location = https_location(&req, https_port);
+        Ok(tide::Response::builder(tide::StatusCode::PermanentRedirect)
+            .header("Location", location.as_str())
+            .build())
+    };
+    app.at("/").all(handler);
+    app.at("*").all(handler);
+    app.listen(src.http_redirect.as_str()).await?;
+    Ok(())
+}
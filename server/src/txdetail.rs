@@ -0,0 +1,163 @@
+//! Transaction detail of `/api/tx/:tx` and `/api/decode`, the reply of the node
+//! joined with the outputs index: the outputs spent by the inputs, the spending
+//! of the outputs and the position of the transaction in the chain.
+
+use crate::decode;
+use crate::error::ApiError;
+use crate::index::{self, Prevout};
+use crate::network;
+use crate::script::ScriptType;
+use crate::types::{TxDetail, TxDetailVin, TxDetailVout, TxScriptPubKey, TxScriptSig, TxSpending};
+use crate::State;
+use bitcoin::hashes::hex::{FromHex, ToHex};
+use bitcoincore_rpc_json as json;
+use json::bitcoin;
+use serde_json::json;
+use std::collections::HashMap;
+
+fn vout(
+    out: &json::GetRawTransactionResultVout,
+    chain: bitcoin::Network,
+    spendings: &HashMap<u32, Option<index::Spending>>,
+) -> TxDetailVout {
+    let script = bitcoin::Script::from(out.script_pub_key.hex.clone());
+    let spending = spendings.get(&out.n);
+    TxDetailVout {
+        value_sat: out.value.as_sat(),
+        n: out.n,
+        script_pub_key: TxScriptPubKey {
+            asm: out.script_pub_key.asm.clone(),
+            hex: out.script_pub_key.hex.to_hex(),
+            req_sigs: out.script_pub_key.req_sigs.map(|x| x as u32),
+            script_type: ScriptType::of(&script).core_name().to_string(),
+            addresses: None,
+            address: bitcoin::Address::from_script(&script, chain).map(|a| a.to_string()),
+        },
+        spent: spending.map(Option::is_some),
+        spending: spending.cloned().flatten().map(|x| TxSpending {
+            txid: x.tx_hash,
+            vin: x.vin as u32,
+            height: x.height.map(|h| h as u32),
+        }),
+    }
+}
+
+/// height and index in the block of the confirmed transaction
+async fn position(
+    state: &State,
+    raw: &json::GetRawTransactionResult,
+    txhash: &[u8],
+) -> Result<(Option<u32>, Option<u32>), ApiError> {
+    let hash = match raw.blockhash {
+        Some(x) => x,
+        None => return Ok((None, None)),
+    };
+    match index::tx_position(&state.pool, txhash).await {
+        Ok(Some((height, txindex))) => return Ok((Some(height as u32), Some(txindex as u32))),
+        Ok(None) => {}
+        Err(e) => tracing::warn!("tx position of {}: {}", raw.txid, e),
+    }
+    // recent blocks are not final in the index yet
+    let header: json::GetBlockHeaderResult = state
+        .rpc_client
+        .call("getblockheader", vec![json!(hash.to_string()), json!(true)])
+        .await?;
+    Ok((Some(header.height as u32), None))
+}
+
+/// detail of the transaction, `known` are the spent outputs that are not to be looked up in the index
+pub async fn build(
+    state: &State,
+    raw: &json::GetRawTransactionResult,
+    mut known: HashMap<(String, u32), Prevout>,
+) -> Result<TxDetail, ApiError> {
+    let missing: Vec<(Vec<u8>, u32)> = raw
+        .vin
+        .iter()
+        .filter_map(|vin| match (vin.txid, vin.vout) {
+            (Some(txid), Some(vout)) if !known.contains_key(&(txid.to_string(), vout)) => {
+                Some((Vec::from_hex(&txid.to_string()).ok()?, vout))
+            }
+            _ => None,
+        })
+        .collect();
+    // the transaction is still served without the index data while the index is down
+    match index::prevouts(&state.pool, &missing).await {
+        Ok(x) => known.extend(x),
+        Err(e) => tracing::warn!("prevouts of {}: {}", raw.txid, e),
+    }
+    let txhash = Vec::from_hex(&raw.txid.to_string()).unwrap_or_default();
+    let spendings = match index::spendings(&state.pool, &txhash).await {
+        Ok(x) => x,
+        Err(e) => {
+            tracing::warn!("spendings of {}: {}", raw.txid, e);
+            Default::default()
+        }
+    };
+    let (blockheight, txindex) = position(state, raw, &txhash).await?;
+
+    let vin: Vec<TxDetailVin> = raw
+        .vin
+        .iter()
+        .map(|vin| {
+            let prevout = match (vin.txid, vin.vout) {
+                (Some(txid), Some(vout)) => known.get(&(txid.to_string(), vout)),
+                _ => None,
+            };
+            TxDetailVin {
+                txid: vin.txid.map(|x| x.to_string()),
+                vout: vin.vout,
+                coinbase: vin.coinbase.as_ref().map(|x| x.to_hex()),
+                script_sig: vin.script_sig.as_ref().map(|x| TxScriptSig {
+                    asm: x.asm.clone(),
+                    hex: x.hex.to_hex(),
+                }),
+                txinwitness: vin
+                    .txinwitness
+                    .as_ref()
+                    .map(|w| w.iter().map(|x| x.to_hex()).collect()),
+                sequence: vin.sequence as u64,
+                address: prevout.and_then(|p| p.address.clone()),
+                value_sat: prevout.map(|p| p.value as u64),
+            }
+        })
+        .collect();
+    let chain = network::address_network(state.network);
+    let vout: Vec<TxDetailVout> = raw
+        .vout
+        .iter()
+        .map(|x| vout(x, chain, &spendings))
+        .collect();
+
+    let weight = bitcoin::consensus::encode::deserialize::<bitcoin::Transaction>(&raw.hex)
+        .map(|t| decode::weight(&t))
+        .unwrap_or(raw.vsize * 4);
+    let out_total: u64 = vout.iter().map(|x| x.value_sat).sum();
+    let fee = if vin.iter().any(|x| x.coinbase.is_some()) {
+        None
+    } else {
+        vin.iter()
+            .map(|x| x.value_sat)
+            .sum::<Option<u64>>()
+            .map(|in_total| in_total.saturating_sub(out_total))
+    };
+    let feerate = fee.map(|x| (x as f64 / raw.vsize as f64 * 1000.0).round() / 1000.0);
+    Ok(TxDetail {
+        txid: raw.txid.to_string(),
+        hash: raw.hash.to_string(),
+        version: raw.version,
+        size: raw.size as u32,
+        vsize: raw.vsize as u32,
+        weight: weight as u32,
+        locktime: raw.locktime,
+        vin,
+        vout,
+        fee,
+        feerate,
+        blockhash: raw.blockhash.map(|x| x.to_string()),
+        blockheight,
+        txindex,
+        blocktime: raw.blocktime.map(|x| x as u64),
+        hex: raw.hex.to_hex(),
+    })
+}
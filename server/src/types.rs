@@ -3,51 +3,26 @@ use bitcoincore_rpc_json as json;
 // use json::bitcoin;
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, Default, Serialize, Deserialize)]
-pub struct BlockStatsInfo {
-    pub avgfee: u32,
-    pub avgfeerate: u32,
-    pub avgtxsize: u64,
-    pub blockhash: String,
-    pub feerate_percentiles: Vec<u32>,
-    pub height: u32,
-    pub ins: u32,
-    pub maxfee: u64,
-    pub maxfeerate: u64,
-    pub maxtxsize: u32,
-    pub medianfee: u32,
-    pub mediantime: u64,
-    pub mediantxsize: u32,
-    pub minfee: u64,
-    pub minfeerate: u64,
-    pub mintxsize: u32,
-    pub outs: u32,
-    pub subsidy: u64,
-    pub swtotal_size: u32,
-    pub swtotal_weight: u64,
-    pub swtxs: u32,
-    pub time: u64,
-    pub total_out: u64,
-    pub total_size: u64,
-    pub total_weight: u64,
-    pub totalfee: u64,
-    pub txs: u32,
-    pub utxo_increase: i32,
-    pub utxo_size_inc: i32,
-}
+pub use explorer_types::{BlockStatsInfo, Broadcast, Rejection};
+pub use explorer_types::{
+    TxDetail, TxDetailVin, TxDetailVout, TxScriptPubKey, TxScriptSig, TxSpending,
+};
 
-#[derive(Clone, Debug, Default, Serialize, Deserialize)]
-pub struct BlockStatsResponse {
-    pub result: BlockStatsInfo,
-}
+pub type BlockStatsResponse = explorer_types::RpcResponse<BlockStatsInfo>;
 
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Block {
     pub header: json::GetBlockHeaderResult,
     pub stats: BlockStatsInfo,
 }
 
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BlockDetails {
+    pub block: json::GetBlockResult,
+    pub stats: BlockStatsInfo,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct BlocksList {
     pub list: Vec<Block>,
     pub pager: Option<pager::Output>,
@@ -62,7 +37,16 @@ impl std::default::Default for BlocksList {
 }
 
 
+/// transaction decoded by the server, in the shape of `/api/tx/:tx` reply
 #[derive(Clone, Debug, Serialize)]
+pub struct DecodedTx {
+    #[serde(flatten)]
+    pub tx: TxDetail,
+    /// whether it was decoded from PSBT
+    pub psbt: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct TxList {
     pub list: Vec<json::GetRawTransactionResult>,
     pub pager: Option<pager::Output>,
@@ -79,75 +63,195 @@ impl std::default::Default for TxList {
 
 pub mod response {
     use super::*;
-    use serde::Serialize;
+    use crate::error::ApiError;
+    use crate::httpcache::Freshness;
+    use serde::ser::Error;
+    use serde::{Serialize, Serializer};
+    use serde_json::Value;
+
+    fn strip_confirmations(value: &mut Value) {
+        match value {
+            Value::Object(x) => {
+                x.remove("confirmations");
+                x.values_mut().for_each(strip_confirmations);
+            }
+            Value::Array(x) => x.iter_mut().for_each(strip_confirmations),
+            _ => {}
+        }
+    }
+
+    /// the blocks as they are cached, without the confirmations that change with every block,
+    /// so the deep ones can be cached for long
+    fn without_confirmations<T: Serialize, S: Serializer>(
+        value: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut value = serde_json::to_value(value).map_err(S::Error::custom)?;
+        strip_confirmations(&mut value);
+        value.serialize(serializer)
+    }
 
     #[derive(Clone, Debug, Serialize)]
     pub enum Address {
         #[serde(rename = "error")]
-        Failure(String),
+        Failure(ApiError),
         #[serde(rename = "list")]
         Tx(super::TxList),
     }
+    impl From<Result<super::TxList, ApiError>> for Address {
+        fn from(res: Result<super::TxList, ApiError>) -> Self {
+            match res {
+                Ok(x) => Self::Tx(x),
+                Err(e) => Self::Failure(e),
+            }
+        }
+    }
     impl Address {
-        pub fn is_invalid(&self) -> bool {
-            if let Self::Failure(_) = self {
-                return true;
+        pub fn status(&self) -> u16 {
+            match self {
+                Self::Failure(e) => e.status(),
+                _ => 200,
             }
-            false
         }
     }
 
     #[derive(Clone, Debug, Serialize)]
     pub enum BlockList {
         #[serde(rename = "error")]
-        Failure(String),
+        Failure(ApiError),
         #[serde(rename = "blocks")]
-        Blocks(super::BlocksList),
+        Blocks(#[serde(serialize_with = "without_confirmations")] super::BlocksList),
+    }
+    impl From<Result<super::BlocksList, ApiError>> for BlockList {
+        fn from(res: Result<super::BlocksList, ApiError>) -> Self {
+            match res {
+                Ok(x) => Self::Blocks(x),
+                Err(e) => Self::Failure(e),
+            }
+        }
     }
     impl BlockList {
-        pub fn is_invalid(&self) -> bool {
-            if let Self::Failure(_) = self {
-                return true;
+        pub fn freshness(&self, pg: &pager::Input) -> Freshness {
+            match self {
+                // the page from the tip changes with every block
+                Self::Blocks(x) if pg.from.is_some() => match x.list.first() {
+                    Some(b) => Freshness::new(
+                        Some(b.header.confirmations.max(0) as u64),
+                        Some(b.header.time as u64),
+                    ),
+                    None => Freshness::default(),
+                },
+                _ => Freshness::default(),
+            }
+        }
+        pub fn status(&self) -> u16 {
+            match self {
+                Self::Failure(e) => e.status(),
+                _ => 200,
             }
-            false
         }
     }
 
-    /// wrapper of the esponse that can be cached
+    /// wrapper of the response that can be cached
     #[derive(Clone, Debug, Serialize)]
     pub enum Tx {
         #[serde(rename = "error")]
-        Failure(String),
+        Failure(ApiError),
         #[serde(rename = "tx")]
-        Tx(json::GetRawTransactionResult),
+        Tx(super::TxDetail),
+    }
+    impl From<Result<super::TxDetail, ApiError>> for Tx {
+        fn from(res: Result<super::TxDetail, ApiError>) -> Self {
+            match res {
+                Ok(x) => Self::Tx(x),
+                Err(e) => Self::Failure(e),
+            }
+        }
     }
     impl Tx {
-        pub fn is_invalid(&self) -> bool {
-            if let Self::Failure(_) = self {
-                return true;
+        /// `confirmations` of the transaction, they are not in the body
+        pub fn freshness(&self, confirmations: Option<u32>) -> Freshness {
+            match self {
+                // spending of the outputs is yet to come
+                Self::Tx(tx) if !tx.is_spent() => Freshness::new(None, tx.blocktime),
+                Self::Tx(tx) => Freshness::new(confirmations.map(|x| x as u64), tx.blocktime),
+                _ => Freshness::default(),
+            }
+        }
+        pub fn status(&self) -> u16 {
+            match self {
+                Self::Failure(e) => e.status(),
+                _ => 200,
             }
-            false
         }
     }
 
     #[derive(Clone, Debug, Serialize)]
     pub enum Block {
         #[serde(rename = "error")]
-        Failure(String),
+        Failure(ApiError),
         #[serde(rename = "block")]
-        Block {
-            block: json::GetBlockResult,
-            stats: super::BlockStatsInfo,
-        },
+        Block(#[serde(serialize_with = "without_confirmations")] super::BlockDetails),
     }
 
+    impl From<Result<super::BlockDetails, ApiError>> for Block {
+        fn from(res: Result<super::BlockDetails, ApiError>) -> Self {
+            match res {
+                Ok(x) => Self::Block(x),
+                Err(e) => Self::Failure(e),
+            }
+        }
+    }
     impl Block {
-        pub fn is_invalid(&self) -> bool {
-            if let Self::Failure(_) = self {
-                return true;
+        pub fn freshness(&self) -> Freshness {
+            match self {
+                Self::Block(x) => Freshness::new(
+                    Some(x.block.confirmations.max(0) as u64),
+                    Some(x.block.time as u64),
+                ),
+                _ => Freshness::default(),
+            }
+        }
+        pub fn status(&self) -> u16 {
+            match self {
+                Self::Failure(e) => e.status(),
+                _ => 200,
+            }
+        }
+    }
+
+    #[derive(Clone, Debug, Serialize)]
+    pub enum Broadcast {
+        #[serde(rename = "error")]
+        Failure(ApiError),
+        #[serde(rename = "rejected")]
+        Rejected(super::Rejection),
+        #[serde(rename = "tx")]
+        Sent(super::Broadcast),
+    }
+    impl Broadcast {
+        pub fn status(&self) -> u16 {
+            match self {
+                Self::Failure(e) => e.status(),
+                Self::Rejected(_) => 422,
+                _ => 200,
             }
-            false
         }
     }
 
+    #[derive(Clone, Debug, Serialize)]
+    pub enum Decode {
+        #[serde(rename = "error")]
+        Failure(ApiError),
+        #[serde(rename = "tx")]
+        Tx(super::DecodedTx),
+    }
+    impl Decode {
+        pub fn status(&self) -> u16 {
+            match self {
+                Self::Failure(e) => e.status(),
+                _ => 200,
+            }
+        }
+    }
 }
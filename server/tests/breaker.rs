@@ -0,0 +1,93 @@
+//! Circuit breaker of the node connection, the choice of the nodes to call
+//! and the retries of the calls
+
+mod common;
+
+use bitcoin_explorer::breaker::Breaker;
+use bitcoin_explorer::node::{Credentials, Node, Pool};
+use common::{Server, BLOCK_170};
+use serde_json::json;
+use std::time::Duration;
+
+const COOLDOWN: Duration = Duration::from_millis(50);
+
+fn tripped() -> Breaker {
+    let breaker = Breaker::new(1, COOLDOWN);
+    breaker.failure();
+    breaker
+}
+
+#[test]
+fn half_open_lets_one_call_through() {
+    let breaker = tripped();
+    assert!(breaker.is_open());
+    assert!(!breaker.allow());
+    std::thread::sleep(COOLDOWN);
+    // asking does not take the slot
+    assert!(!breaker.is_open());
+    assert!(!breaker.is_open());
+    assert!(breaker.allow());
+    assert!(!breaker.allow());
+    assert!(breaker.is_open());
+    breaker.success();
+    assert!(!breaker.is_tripped());
+    assert!(breaker.allow());
+}
+
+#[test]
+fn candidates_keep_probe_slot() {
+    let nodes: Vec<Node> = ["http://127.0.0.1:1/", "http://127.0.0.1:2/"]
+        .iter()
+        .map(|addr| {
+            let mut node = Node::new(addr, Credentials::None);
+            node.breaker = Breaker::new(1, COOLDOWN);
+            node
+        })
+        .collect();
+    let pool = Pool::new(nodes);
+    let down = pool.nodes()[1].clone();
+    down.breaker.failure();
+    assert_eq!(pool.candidates(false).len(), 1);
+    std::thread::sleep(COOLDOWN);
+    // the half-open node is offered to every caller, the first one to call it takes the slot
+    for _ in 0..3 {
+        assert_eq!(pool.candidates(true).len(), 2);
+    }
+    assert!(down.breaker.allow());
+    assert_eq!(pool.candidates(true).len(), 1);
+}
+
+#[test]
+fn mistyped_reply_is_not_retried() {
+    let server = Server::start();
+    server.node.on("getblock", |_| Ok(json!("not a block")));
+    let (status, body) = server.get(&format!("/api/blocks/{}", BLOCK_170));
+    assert_eq!(status, 500, "{}", body);
+    assert_eq!(body["error"]["code"], "internal_error");
+    assert_eq!(server.node.calls("getblock"), 1);
+    let node = &server.state.rpc_client.nodes.nodes()[0];
+    assert!(!node.breaker.is_tripped());
+}
+
+#[test]
+fn node_error_is_not_retried() {
+    let server = Server::start();
+    server
+        .node
+        .on("getblock", |_| Err((-1, "unexpected".to_string())));
+    let (status, body) = server.get(&format!("/api/blocks/{}", BLOCK_170));
+    assert_eq!(status, 500, "{}", body);
+    assert_eq!(server.node.calls("getblock"), 1);
+}
+
+#[test]
+fn warm_up_is_retried() {
+    let server = Server::start();
+    server.node.on("getblock", |_| {
+        Err((-28, "Loading block index...".to_string()))
+    });
+    let (status, body) = server.get(&format!("/api/blocks/{}", BLOCK_170));
+    assert_eq!(status, 502, "{}", body);
+    assert_eq!(body["error"]["code"], "upstream_unavailable");
+    assert_eq!(server.node.calls("getblock"), 3);
+}
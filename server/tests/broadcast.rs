@@ -0,0 +1,75 @@
+//! `POST /api/tx`, the test of the node decides whether the transaction is relayed
+
+mod common;
+
+use common::{Server, TX_SPEND_170};
+use serde_json::json;
+
+fn hex() -> String {
+    common::fixture("getrawtransaction_f4184f.json")["hex"]
+        .as_str()
+        .unwrap()
+        .to_string()
+}
+
+/// server whose node answers `testmempoolaccept` with the reason of the rejection
+fn server(reject_reason: Option<&'static str>) -> Server {
+    let server = Server::start();
+    server.node.on("testmempoolaccept", move |_| {
+        let mut out = json!({ "txid": TX_SPEND_170, "allowed": reject_reason.is_none() });
+        match reject_reason {
+            Some(reason) => out["reject-reason"] = json!(reason),
+            None => {
+                out["vsize"] = json!(275);
+                out["fees"] = json!({ "base": 0.0001 });
+            }
+        }
+        Ok(json!([out]))
+    });
+    server
+        .node
+        .on("sendrawtransaction", |_| Ok(json!(TX_SPEND_170)));
+    server
+}
+
+#[test]
+fn sent() {
+    let server = server(None);
+    let (status, body) = server.post("/api/tx", &hex());
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["tx"]["txid"], TX_SPEND_170);
+    assert_eq!(body["tx"]["vsize"], 275);
+    assert_eq!(body["tx"]["fee"], 10_000);
+    assert_eq!(server.node.calls("sendrawtransaction"), 1);
+}
+
+#[test]
+fn rejected_by_test() {
+    let server = server(Some("min relay fee not met"));
+    let (status, body) = server.post("/api/tx", &hex());
+    assert_eq!(status, 422, "{}", body);
+    assert_eq!(body["rejected"]["reason"], "min relay fee not met");
+    assert_eq!(body["rejected"]["txid"], TX_SPEND_170);
+    assert_eq!(server.node.calls("sendrawtransaction"), 0);
+}
+
+#[test]
+fn already_in_mempool_is_rebroadcast() {
+    let server = server(Some("txn-already-in-mempool"));
+    let (status, body) = server.post("/api/tx", &hex());
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["tx"]["txid"], TX_SPEND_170);
+    assert_eq!(server.node.calls("sendrawtransaction"), 1);
+}
+
+#[test]
+fn rejected_on_send() {
+    let server = server(None);
+    // an input is spent between the test and the send
+    server.node.on("sendrawtransaction", |_| {
+        Err((-26, "bad-txns-inputs-missingorspent".to_string()))
+    });
+    let (status, body) = server.post("/api/tx", &hex());
+    assert_eq!(status, 422, "{}", body);
+    assert_eq!(body["rejected"]["reason"], "bad-txns-inputs-missingorspent");
+}
@@ -0,0 +1,59 @@
+//! Cached replies of the node keep up with the tip of the chain
+
+mod common;
+
+use common::{Server, BLOCK_170, TX_SPEND_170};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// node whose tip is at the given height, block 170 and its transactions are deep
+fn moving_tip(server: &Server, tip: Arc<AtomicU64>) {
+    let at = tip.clone();
+    server.node.on("getblockchaininfo", move |params| {
+        let mut out = common::default_handler("getblockchaininfo", params)?;
+        out["blocks"] = json!(at.load(Ordering::SeqCst));
+        Ok(out)
+    });
+    let at = tip.clone();
+    server.node.on("getblock", move |params| {
+        let mut out = common::default_handler("getblock", params)?;
+        out["confirmations"] = json!(at.load(Ordering::SeqCst) - 170 + 1);
+        Ok(out)
+    });
+    server.node.on("getrawtransaction", move |params| {
+        let mut out = common::default_handler("getrawtransaction", params)?;
+        out["confirmations"] = json!(tip.load(Ordering::SeqCst) - 170 + 1);
+        Ok(out)
+    });
+}
+
+fn get(server: &Server, path: &str) -> Value {
+    let (status, reply) = server.get(path);
+    assert_eq!(status, 200, "{}", reply);
+    reply
+}
+
+#[test]
+fn deep_entries_outlive_the_tip() {
+    let server = Server::start();
+    let tip = Arc::new(AtomicU64::new(300));
+    moving_tip(&server, tip.clone());
+    let tx = format!("/api/tx/{}", TX_SPEND_170);
+    let block = format!("/api/blocks/{}", BLOCK_170);
+    let first_tx = get(&server, &tx);
+    let first_block = get(&server, &block);
+    // the confirmations are left to the client, from the tip and the height
+    assert!(first_tx["tx"]["confirmations"].is_null());
+    assert_eq!(first_tx["tx"]["blockheight"], json!(170));
+    assert!(first_block["block"]["block"]["confirmations"].is_null());
+    assert_eq!(first_block["block"]["block"]["height"], json!(170));
+
+    tip.store(310, Ordering::SeqCst);
+    async_std::task::block_on(server.state.rpc_client.cache.invalidate_tip());
+    assert_eq!(get(&server, &tx), first_tx);
+    assert_eq!(get(&server, &block), first_block);
+    // deep entries are still served from the cache
+    assert_eq!(server.node.calls("getrawtransaction"), 1);
+    assert_eq!(server.node.calls("getblock"), 1);
+}
@@ -0,0 +1,84 @@
+//! `POST /api/decode`, the raw transaction as hex and the PSBT as base64
+
+mod common;
+
+use bitcoin::blockdata::script::Builder;
+use bitcoin::consensus::encode::{deserialize, serialize};
+use bitcoin::hashes::hex::{FromHex, ToHex};
+use bitcoin::util::psbt::PartiallySignedTransaction;
+use bitcoin::{Script, Transaction, TxOut};
+use bitcoincore_rpc_json::bitcoin;
+use common::{Server, TX_SPEND_170};
+use serde_json::json;
+
+// output of the coinbase of block 9 spent by the transaction
+const SPENT_SCRIPT: &str = "410411db93e1dcdb8a016b49840f8c53bc1eb68a382e97b1482ecad7b148a6909a5cb2e0eaddfb84ccf9744464f82e160bfa9b8b64f9d4c03f999b8643f656b412a3ac";
+const SPENT_VALUE: u64 = 5_000_000_000;
+
+fn raw() -> serde_json::Value {
+    common::fixture("getrawtransaction_f4184f.json")
+}
+
+#[test]
+fn hex_as_the_node_decodes_it() {
+    let server = Server::start();
+    let raw = raw();
+    let (status, reply) = server.post("/api/decode", raw["hex"].as_str().unwrap());
+    assert_eq!(status, 200, "{}", reply);
+    let body = &reply["tx"];
+    assert_eq!(body["psbt"], false);
+    assert_eq!(body["txid"], TX_SPEND_170);
+    assert_eq!(body["vin"][0]["scriptSig"], raw["vin"][0]["scriptSig"]);
+    for n in 0..2 {
+        assert_eq!(
+            body["vout"][n]["scriptPubKey"]["asm"],
+            raw["vout"][n]["scriptPubKey"]["asm"]
+        );
+    }
+    // the spent output is not indexed
+    assert_eq!(body["vin"][0]["valueSat"], json!(null));
+    assert_eq!(body["fee"], json!(null));
+}
+
+#[test]
+fn psbt_carries_the_spent_outputs() {
+    let server = Server::start();
+    let bytes = Vec::<u8>::from_hex(raw()["hex"].as_str().unwrap()).unwrap();
+    let mut tx: Transaction = deserialize(&bytes).unwrap();
+    tx.input[0].script_sig = Script::new();
+    let mut psbt = PartiallySignedTransaction::from_unsigned_tx(tx.clone()).unwrap();
+    psbt.inputs[0].witness_utxo = Some(TxOut {
+        value: SPENT_VALUE,
+        script_pubkey: Script::from(Vec::from_hex(SPENT_SCRIPT).unwrap()),
+    });
+    let body = json!({ "psbt": base64::encode(serialize(&psbt)) });
+    let (status, reply) = server.post("/api/decode", &body.to_string());
+    assert_eq!(status, 200, "{}", reply);
+    let body = &reply["tx"];
+    assert_eq!(body["psbt"], true);
+    assert_eq!(body["txid"], tx.txid().to_string());
+    assert_eq!(body["vin"][0]["txid"], raw()["vin"][0]["txid"]);
+    assert_eq!(body["vin"][0]["scriptSig"]["hex"], "");
+    assert_eq!(body["vin"][0]["valueSat"], SPENT_VALUE);
+    // both outputs add up to the spent one
+    assert_eq!(body["fee"], 0);
+}
+
+#[test]
+fn asm_of_numbers_and_opcodes() {
+    let script = Builder::new()
+        .push_int(0)
+        .push_int(-1)
+        .push_int(16)
+        .push_int(1000)
+        .push_opcode(bitcoin::blockdata::opcodes::all::OP_CLTV)
+        .push_slice(&[0xab; 20])
+        .into_script();
+    assert_eq!(
+        bitcoin_explorer::script::asm(&script, false),
+        format!(
+            "0 -1 16 1000 OP_CHECKLOCKTIMEVERIFY {}",
+            [0xab; 20].to_hex()
+        )
+    );
+}
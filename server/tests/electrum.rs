@@ -0,0 +1,324 @@
+//! Electrum protocol over TCP, with the stand-in node and the index of `tests/db`
+
+mod common;
+mod db;
+
+use bitcoin::hashes::hex::{FromHex, ToHex};
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin_explorer::{electrum, ratelimit::Limiter, State};
+use bitcoincore_rpc_json::bitcoin;
+use common::{Node, DATABASE_URL, TIP, TX_SPEND_170};
+use db::TestDb;
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// pay-to-pubkey output of the coinbase of block 9, spent in block 170 with the change back to it
+const SATOSHI: &str = "410411db93e1dcdb8a016b49840f8c53bc1eb68a382e97b1482ecad7b148a6909a5cb2e0eaddfb84ccf9744464f82e160bfa9b8b64f9d4c03f999b8643f656b412a3ac";
+// the other output of the spending transaction
+const HAL: &str = "4104ae1a62fe09c5f51b13905f07f06b99a2f7159b2225f374cd378d71302fa28414e7aab37397f554a7df5f142c21c1b7303b8a0626f1baded5c72a704f7e6cd84cac";
+const COINBASE_9: &str = "0437cd7f8525ceed2324359c2d0ba26006d92d856a9c20fa0241106ee5a597c9";
+// synthetic transactions of block 100, the first one in the block has the higher hash
+const FIRST_100: &str = "eeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeee";
+const SECOND_100: &str = "1111111111111111111111111111111111111111111111111111111111111111";
+// synthetic transaction of the block indexed while the wallet is subscribed
+const NEW_172: &str = "2222222222222222222222222222222222222222222222222222222222222222";
+// the session checks for the changes every 5 seconds
+const NOTIFY_WITHIN: Duration = Duration::from_secs(15);
+
+/// scripthash of the protocol, the reversed SHA-256 of the script
+fn scripthash(script: &str) -> String {
+    let mut hash = sha256::Hash::hash(&Vec::<u8>::from_hex(script).unwrap()).into_inner();
+    hash.reverse();
+    hash.to_hex()
+}
+
+/// status of the history, as `txid:height:` pairs
+fn status(history: &[(&str, u64)]) -> String {
+    let text: String = history
+        .iter()
+        .map(|(txid, height)| format!("{}:{}:", txid, height))
+        .collect();
+    sha256::Hash::hash(text.as_bytes()).into_inner().to_hex()
+}
+
+/// block with the transactions and their outputs in the index
+fn index_block(db: &TestDb, height: u64, txs: &[&str], outputs: &[(&str, u32, &str, u64)]) {
+    let mut sql = format!(
+        "INSERT INTO final_blocks (blockheight, blockhash) VALUES ({}, decode('{}', 'hex'));",
+        height,
+        common::block_hash(height)
+    );
+    for (txindex, txid) in txs.iter().enumerate() {
+        sql += &format!(
+            "INSERT INTO final_tx (txhash, txindex, blockheight) \
+                VALUES (decode('{}', 'hex'), {}, {});",
+            txid, txindex, height
+        );
+    }
+    for (txid, vout, script, value) in outputs {
+        let txindex = txs.iter().position(|x| x == txid).unwrap();
+        sql += &format!(
+            "INSERT INTO final_outputs (txhash, vout, blockheight, txindex, scripthash, value) \
+                VALUES (decode('{}', 'hex'), {}, {}, {}, sha256(decode('{}', 'hex')), {});",
+            txid, vout, height, txindex, script, value
+        );
+    }
+    db.seed(&sql);
+}
+
+/// the history of `SATOSHI` in blocks 9, 100 and 170
+fn seed(db: &TestDb) {
+    index_block(
+        db,
+        9,
+        &[COINBASE_9],
+        &[(COINBASE_9, 0, SATOSHI, 5_000_000_000)],
+    );
+    index_block(
+        db,
+        100,
+        &[FIRST_100, SECOND_100],
+        &[
+            (FIRST_100, 0, SATOSHI, 1000),
+            (SECOND_100, 0, SATOSHI, 2000),
+        ],
+    );
+    index_block(
+        db,
+        170,
+        &[common::TX_COINBASE_170, TX_SPEND_170],
+        &[
+            (TX_SPEND_170, 0, HAL, 1_000_000_000),
+            (TX_SPEND_170, 1, SATOSHI, 4_000_000_000),
+        ],
+    );
+    db.seed(&format!(
+        "UPDATE final_outputs SET spent_txhash = decode('{}', 'hex'), spent_vin = 0, \
+            spent_height = 170 WHERE txhash = decode('{}', 'hex')",
+        TX_SPEND_170, COINBASE_9
+    ));
+}
+
+/// Wallet connected to the Electrum server
+struct Wallet {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    next_id: u64,
+}
+
+impl Wallet {
+    /// connection to the new server, using the index when it is given
+    fn connect(node: &Node, db: Option<&TestDb>) -> Self {
+        let database_url = db.map_or(DATABASE_URL, |x| x.url.as_str());
+        let args = common::args(
+            node,
+            &["--database-url", database_url, "--cache-tip-ttl", "1"],
+        );
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        async_std::task::block_on(async {
+            let state = State::from_args(&args).await.unwrap();
+            let limiter = Limiter::from_args(&args, state.pool.clone()).unwrap();
+            async_std::task::spawn(electrum::listen(state, addr.to_string(), limiter));
+        });
+        let started = Instant::now();
+        let writer = loop {
+            match TcpStream::connect(addr) {
+                Ok(x) => break x,
+                Err(e) if started.elapsed() > Duration::from_secs(5) => panic!("{}", e),
+                Err(_) => std::thread::sleep(Duration::from_millis(20)),
+            }
+        };
+        writer.set_read_timeout(Some(NOTIFY_WITHIN)).unwrap();
+        Self {
+            reader: BufReader::new(writer.try_clone().unwrap()),
+            writer,
+            next_id: 0,
+        }
+    }
+
+    /// next line sent by the server
+    fn read(&mut self) -> Value {
+        let mut line = String::new();
+        self.reader
+            .read_line(&mut line)
+            .expect("no line from the server");
+        serde_json::from_str(&line).unwrap()
+    }
+
+    /// reply to the call, either the result or the error object
+    fn call(&mut self, method: &str, params: Value) -> Value {
+        self.next_id += 1;
+        let req =
+            json!({ "jsonrpc": "2.0", "id": self.next_id, "method": method, "params": params });
+        writeln!(self.writer, "{}", req).unwrap();
+        let reply = self.read();
+        assert_eq!(reply["id"], self.next_id, "{}", reply);
+        match reply.get("error") {
+            Some(e) if !e.is_null() => json!({ "error": e }),
+            _ => reply["result"].clone(),
+        }
+    }
+
+    /// parameters of the next notification of the method
+    fn notification(&mut self, method: &str) -> Value {
+        let msg = self.read();
+        assert_eq!(msg["method"], method, "{}", msg);
+        msg["params"].clone()
+    }
+}
+
+#[test]
+fn history_balance_and_unspent() {
+    let db = match TestDb::create() {
+        Some(x) => x,
+        None => return,
+    };
+    seed(&db);
+    let node = Node::start();
+    let mut wallet = Wallet::connect(&node, Some(&db));
+    let satoshi = json!([scripthash(SATOSHI)]);
+
+    // in the order of the chain, not of the hashes
+    let history = wallet.call("blockchain.scripthash.get_history", satoshi.clone());
+    assert_eq!(
+        history,
+        json!([
+            { "tx_hash": COINBASE_9, "height": 9 },
+            { "tx_hash": FIRST_100, "height": 100 },
+            { "tx_hash": SECOND_100, "height": 100 },
+            { "tx_hash": TX_SPEND_170, "height": 170 },
+        ])
+    );
+
+    let balance = wallet.call("blockchain.scripthash.get_balance", satoshi.clone());
+    assert_eq!(
+        balance,
+        json!({ "confirmed": 4_000_003_000_u64, "unconfirmed": 0 })
+    );
+
+    // the spent coinbase output is left out
+    let unspent = wallet.call("blockchain.scripthash.listunspent", satoshi);
+    assert_eq!(
+        unspent,
+        json!([
+            { "tx_hash": SECOND_100, "tx_pos": 0, "height": 100, "value": 2000 },
+            { "tx_hash": FIRST_100, "tx_pos": 0, "height": 100, "value": 1000 },
+            { "tx_hash": TX_SPEND_170, "tx_pos": 1, "height": 170, "value": 4_000_000_000_u64 },
+        ])
+    );
+
+    let hal = json!([scripthash(HAL)]);
+    let history = wallet.call("blockchain.scripthash.get_history", hal.clone());
+    assert_eq!(history, json!([{ "tx_hash": TX_SPEND_170, "height": 170 }]));
+
+    // the script that was never paid to
+    let unknown = json!([scripthash("51")]);
+    assert_eq!(
+        wallet.call("blockchain.scripthash.get_history", unknown.clone()),
+        json!([])
+    );
+    assert_eq!(
+        wallet.call("blockchain.scripthash.get_balance", unknown.clone()),
+        json!({ "confirmed": 0, "unconfirmed": 0 })
+    );
+    assert_eq!(
+        wallet.call("blockchain.scripthash.subscribe", unknown),
+        Value::Null
+    );
+    let invalid = wallet.call("blockchain.scripthash.get_history", json!(["nope"]));
+    assert_eq!(invalid["error"]["code"], -32602);
+}
+
+#[test]
+fn subscribed_scripthash_is_notified_of_new_block() {
+    let db = match TestDb::create() {
+        Some(x) => x,
+        None => return,
+    };
+    seed(&db);
+    let node = Node::start();
+    let mut wallet = Wallet::connect(&node, Some(&db));
+    let mut history = vec![
+        (COINBASE_9, 9),
+        (FIRST_100, 100),
+        (SECOND_100, 100),
+        (TX_SPEND_170, 170),
+    ];
+    let satoshi = scripthash(SATOSHI);
+    let reply = wallet.call("blockchain.scripthash.subscribe", json!([satoshi]));
+    assert_eq!(reply, json!(status(&history)));
+
+    index_block(&db, 172, &[NEW_172], &[(NEW_172, 0, SATOSHI, 500)]);
+    history.push((NEW_172, 172));
+    let params = wallet.notification("blockchain.scripthash.subscribe");
+    assert_eq!(params, json!([satoshi, status(&history)]));
+    let balance = wallet.call("blockchain.scripthash.get_balance", json!([satoshi]));
+    assert_eq!(balance["confirmed"], 4_000_003_500_u64);
+
+    // no more notifications once unsubscribed
+    let reply = wallet.call("blockchain.scripthash.unsubscribe", json!([satoshi]));
+    assert_eq!(reply, true);
+}
+
+/// raw header of the block, the hash stands in for it
+fn raw_header(hash: &str) -> String {
+    format!("{}{}", hash, "00".repeat(48))
+}
+
+#[test]
+fn headers_subscribe_is_notified_of_new_tip() {
+    let node = Node::start();
+    node.on("getblockheader", |params| {
+        Ok(json!(raw_header(params[0].as_str().unwrap_or_default())))
+    });
+    let tip = Arc::new(AtomicU64::new(TIP));
+    let chain = tip.clone();
+    node.on("getblockchaininfo", move |params| {
+        let mut out = common::default_handler("getblockchaininfo", params)?;
+        let height = chain.load(Ordering::SeqCst);
+        out["blocks"] = json!(height);
+        out["headers"] = json!(height);
+        out["bestblockhash"] = json!(common::block_hash(height));
+        Ok(out)
+    });
+    let mut wallet = Wallet::connect(&node, None);
+    let reply = wallet.call("blockchain.headers.subscribe", json!([]));
+    assert_eq!(
+        reply,
+        json!({ "height": TIP, "hex": raw_header(&common::block_hash(TIP)) })
+    );
+
+    // the session learns the tip it starts from before it moves
+    let started = Instant::now();
+    while node.calls("getblockchaininfo") < 2 {
+        assert!(started.elapsed() < NOTIFY_WITHIN, "the tip is not polled");
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    tip.store(TIP + 1, Ordering::SeqCst);
+    let params = wallet.notification("blockchain.headers.subscribe");
+    assert_eq!(
+        params,
+        json!([{ "height": TIP + 1, "hex": raw_header(&common::block_hash(TIP + 1)) }])
+    );
+}
+
+#[test]
+fn transaction_get() {
+    let node = Node::start();
+    let mut wallet = Wallet::connect(&node, None);
+    let raw = common::fixture("getrawtransaction_f4184f.json");
+    let hex = wallet.call("blockchain.transaction.get", json!([TX_SPEND_170]));
+    assert_eq!(hex, raw["hex"]);
+    let verbose = wallet.call("blockchain.transaction.get", json!([TX_SPEND_170, true]));
+    assert_eq!(verbose["txid"], TX_SPEND_170);
+    assert_eq!(verbose["hex"], raw["hex"]);
+    let missing = wallet.call("blockchain.transaction.get", json!([COINBASE_9]));
+    assert_eq!(missing["error"]["code"], 2);
+}
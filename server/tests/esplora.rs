@@ -0,0 +1,291 @@
+//! Esplora compatibility layer, errors as plain text with the status
+
+mod common;
+mod db;
+
+use common::{block_hash, Server, BLOCK_170, TIP, TX_COINBASE_170, TX_SPEND_170};
+use db::TestDb;
+use serde_json::{json, Value};
+
+// pay-to-pubkey output of the coinbase of block 9 spent by the transaction
+const SPENT_SCRIPT: &str = "410411db93e1dcdb8a016b49840f8c53bc1eb68a382e97b1482ecad7b148a6909a5cb2e0eaddfb84ccf9744464f82e160bfa9b8b64f9d4c03f999b8643f656b412a3ac";
+const COINBASE_9: &str = "0437cd7f8525ceed2324359c2d0ba26006d92d856a9c20fa0241106ee5a597c9";
+// address the outputs of both transactions of block 170 are indexed with
+const ADDRESS: &str = "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa";
+const BLOCK_TIME_170: u64 = 1231731025;
+
+/// `Cache-Control` of the reply
+fn cache_control(server: &Server, path: &str) -> String {
+    let res = ureq::get(&format!("{}{}", server.url, path))
+        .call()
+        .unwrap();
+    res.header("Cache-Control").unwrap_or_default().to_string()
+}
+
+/// keys of the object, sorted
+fn keys(value: &Value) -> Vec<&str> {
+    let mut out: Vec<&str> = value
+        .as_object()
+        .unwrap_or_else(|| panic!("not an object: {}", value))
+        .keys()
+        .map(String::as_str)
+        .collect();
+    out.sort_unstable();
+    out
+}
+
+/// index with both transactions of block 170, the output spent by the second one
+/// is stored with its script and without address as pay-to-pubkey outputs are
+fn seed(db: &TestDb) {
+    db.seed(&format!(
+        "INSERT INTO final_tx (txhash, txindex, blockheight) VALUES \
+            (decode('{coinbase}', 'hex'), 0, 170), (decode('{spend}', 'hex'), 1, 170); \
+        INSERT INTO final_outputs (txhash, vout, blockheight, txindex, script, address, value) \
+            VALUES (decode('{spent}', 'hex'), 0, 9, 0, decode('{script}', 'hex'), NULL, 5000000000), \
+            (decode('{coinbase}', 'hex'), 0, 170, 0, NULL, '{address}', 5000000000), \
+            (decode('{spend}', 'hex'), 0, 170, 1, NULL, '{address}', 1000000000)",
+        coinbase = TX_COINBASE_170,
+        spend = TX_SPEND_170,
+        spent = COINBASE_9,
+        script = SPENT_SCRIPT,
+        address = ADDRESS,
+    ));
+}
+
+/// server whose node has the tip far above block 170, so its transactions are deep
+fn deep_server(extra: &[&str]) -> Server {
+    let server = Server::start_with(extra);
+    server.node.on("getblockchaininfo", |params| {
+        let mut out = common::default_handler("getblockchaininfo", params)?;
+        out["blocks"] = json!(TIP + 1000);
+        Ok(out)
+    });
+    server
+}
+
+#[test]
+fn block_shape() {
+    let server = Server::start();
+    let (status, block) = server.get(&format!("/esplora/block/{}", BLOCK_170));
+    assert_eq!(status, 200, "{}", block);
+    assert_eq!(
+        keys(&block),
+        [
+            "bits",
+            "difficulty",
+            "height",
+            "id",
+            "mediantime",
+            "merkle_root",
+            "nonce",
+            "previousblockhash",
+            "size",
+            "timestamp",
+            "tx_count",
+            "version",
+            "weight",
+        ]
+    );
+    assert_eq!(block["id"], BLOCK_170);
+    assert_eq!(block["height"], 170);
+    assert_eq!(block["timestamp"], BLOCK_TIME_170);
+    assert_eq!(block["tx_count"], 2);
+    assert_eq!(block["bits"], 0x1d00ffff);
+    assert_eq!(block["previousblockhash"], block_hash(169));
+    assert_eq!(
+        block["merkle_root"],
+        "7dac2c5666815c17a3b36427de37bb9d2e2c5ccec3f8633eb91a4205cb4c10ff"
+    );
+}
+
+#[test]
+fn tx_shape() {
+    let server = Server::start();
+    let (status, tx) = server.get(&format!("/esplora/tx/{}", TX_SPEND_170));
+    assert_eq!(status, 200, "{}", tx);
+    assert_eq!(
+        keys(&tx),
+        ["locktime", "size", "status", "txid", "version", "vin", "vout", "weight"]
+    );
+    assert_eq!(tx["txid"], TX_SPEND_170);
+    assert_eq!(tx["weight"], tx["size"].as_u64().unwrap() * 4);
+    let vin = &tx["vin"][0];
+    assert_eq!(vin["txid"], COINBASE_9);
+    assert_eq!(vin["vout"], 0);
+    assert_eq!(vin["is_coinbase"], false);
+    assert_eq!(vin["sequence"], 0xffff_ffff_u32);
+    assert!(vin["scriptsig_asm"].as_str().unwrap().ends_with("[ALL]"));
+    // the index is down
+    assert_eq!(vin["prevout"], Value::Null);
+    let vout = &tx["vout"][1];
+    assert_eq!(vout["value"], 4_000_000_000_u64);
+    assert_eq!(vout["scriptpubkey"], SPENT_SCRIPT);
+    assert_eq!(vout["scriptpubkey_type"], "p2pk");
+    assert!(vout["scriptpubkey_asm"]
+        .as_str()
+        .unwrap()
+        .ends_with("OP_CHECKSIG"));
+    assert_eq!(
+        tx["status"],
+        json!({
+            "confirmed": true,
+            "block_height": 170,
+            "block_hash": BLOCK_170,
+            "block_time": BLOCK_TIME_170,
+        })
+    );
+
+    let (status, coinbase) = server.get(&format!("/esplora/tx/{}", TX_COINBASE_170));
+    assert_eq!(status, 200, "{}", coinbase);
+    assert_eq!(coinbase["vin"][0]["is_coinbase"], true);
+    assert_eq!(coinbase["vin"][0]["txid"], "0".repeat(64));
+    assert_eq!(coinbase["fee"], 0);
+}
+
+#[test]
+fn tx_status() {
+    let server = Server::start();
+    let (status, body) = server.get(&format!("/esplora/tx/{}/status", TX_SPEND_170));
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(
+        body,
+        json!({
+            "confirmed": true,
+            "block_height": 170,
+            "block_hash": BLOCK_170,
+            "block_time": BLOCK_TIME_170,
+        })
+    );
+    let (status, body) = server.get(&format!("/esplora/tx/{}/status", COINBASE_9));
+    assert_eq!(status, 404, "{}", body);
+}
+
+#[test]
+fn tx_without_prevouts_is_cached_as_the_tip() {
+    let server = deep_server(&[]);
+    let path = format!("/esplora/tx/{}", TX_SPEND_170);
+    assert_eq!(cache_control(&server, &path), "public, max-age=10");
+    // nothing is missing in the coinbase
+    let path = format!("/esplora/tx/{}", TX_COINBASE_170);
+    assert_eq!(cache_control(&server, &path), "public, max-age=86400");
+}
+
+#[test]
+fn tx_with_indexed_prevouts() {
+    let db = match TestDb::create() {
+        Some(x) => x,
+        None => return,
+    };
+    seed(&db);
+    let server = deep_server(&["--database-url", &db.url]);
+    let path = format!("/esplora/tx/{}", TX_SPEND_170);
+    let (status, tx) = server.get(&path);
+    assert_eq!(status, 200, "{}", tx);
+    // the script of the pay-to-pubkey output is stored without address
+    let prevout = &tx["vin"][0]["prevout"];
+    assert_eq!(prevout["scriptpubkey"], SPENT_SCRIPT);
+    assert_eq!(prevout["scriptpubkey_type"], "p2pk");
+    assert_eq!(
+        prevout["scriptpubkey_asm"],
+        tx["vout"][1]["scriptpubkey_asm"]
+    );
+    assert_eq!(prevout["scriptpubkey_address"], Value::Null);
+    assert_eq!(prevout["value"], 5_000_000_000_u64);
+    // both outputs add up to the spent one
+    assert_eq!(tx["fee"], 0);
+    assert_eq!(cache_control(&server, &path), "public, max-age=86400");
+}
+
+#[test]
+fn address_history_pages() {
+    let db = match TestDb::create() {
+        Some(x) => x,
+        None => return,
+    };
+    seed(&db);
+    let server = Server::start_with(&["--database-url", &db.url]);
+    let (status, txs) = server.get(&format!("/esplora/address/{}/txs", ADDRESS));
+    assert_eq!(status, 200, "{}", txs);
+    // newest first
+    let txids: Vec<&Value> = txs.as_array().unwrap().iter().map(|x| &x["txid"]).collect();
+    assert_eq!(txids, [TX_SPEND_170, TX_COINBASE_170]);
+    assert_eq!(txs[0]["status"]["block_height"], 170);
+    // the prevout of the index has the script of its address
+    let prevout = &txs[0]["vin"][0]["prevout"];
+    assert_eq!(prevout["scriptpubkey"], SPENT_SCRIPT);
+
+    let path = format!("/esplora/address/{}/txs/chain/{}", ADDRESS, TX_SPEND_170);
+    let (status, txs) = server.get(&path);
+    assert_eq!(status, 200, "{}", txs);
+    let txids: Vec<&Value> = txs.as_array().unwrap().iter().map(|x| &x["txid"]).collect();
+    assert_eq!(txids, [TX_COINBASE_170]);
+
+    let path = format!("/esplora/address/{}/txs/chain/{}", ADDRESS, TX_COINBASE_170);
+    assert_eq!(server.get(&path), (200, json!([])));
+    let path = format!("/esplora/address/{}/txs/chain/{}", ADDRESS, COINBASE_9);
+    assert_eq!(server.get(&path).0, 404);
+    let (status, _) = server.get("/esplora/address/nope/txs");
+    assert_eq!(status, 400);
+}
+
+#[test]
+fn tip_height_and_hash() {
+    let server = Server::start();
+    // plain text, the height reads as a number
+    assert_eq!(server.get("/esplora/blocks/tip/height"), (200, json!(TIP)));
+    assert_eq!(
+        server.get("/esplora/blocks/tip/hash"),
+        (200, json!(block_hash(TIP)))
+    );
+}
+
+#[test]
+fn fee_estimates_in_sat_per_vbyte() {
+    let server = Server::start();
+    server
+        .node
+        .on("estimatesmartfee", |params| match params[0].as_u64() {
+            // BTC per kvB
+            Some(x) if x < 144 => Ok(json!({ "feerate": 0.00012, "blocks": x })),
+            // no estimate for the long targets yet
+            x => Ok(json!({ "errors": ["Insufficient data or no feerate found"], "blocks": x })),
+        });
+    let (status, body) = server.get("/esplora/fee-estimates");
+    assert_eq!(status, 200, "{}", body);
+    let rates = body.as_object().unwrap();
+    assert_eq!(rates.len(), 25);
+    for target in 1..=25 {
+        let rate = rates[&target.to_string()].as_f64().unwrap();
+        assert!((rate - 12.0).abs() < 1e-9, "{}: {}", target, rate);
+    }
+    assert!(rates.get("144").is_none());
+}
+
+#[test]
+fn block_height_out_of_range() {
+    let server = Server::start();
+    let (status, body) = server.get(&format!("/esplora/block-height/{}", TIP + 1));
+    assert_eq!(status, 404);
+    assert_eq!(body, "Block not found");
+}
+
+#[test]
+fn block_height_node_failure() {
+    let server = Server::start();
+    server.node.on("getblockhash", |_| {
+        Err((-28, "Loading block index...".to_string()))
+    });
+    let (status, _) = server.get("/esplora/block-height/1");
+    assert!(status >= 500, "status {}", status);
+}
+
+#[test]
+fn script_type_names() {
+    let server = Server::start();
+    let (status, tx) = server.get(&format!("/api/tx/{}", TX_SPEND_170));
+    assert_eq!(status, 200, "{}", tx);
+    assert_eq!(tx["tx"]["vout"][0]["scriptPubKey"]["type"], "pubkey");
+    let (status, tx) = server.get(&format!("/esplora/tx/{}", TX_SPEND_170));
+    assert_eq!(status, 200, "{}", tx);
+    assert_eq!(tx["vout"][0]["scriptpubkey_type"], "p2pk");
+}
@@ -0,0 +1,46 @@
+//! Liveness and readiness probes
+
+mod common;
+
+use common::{Node, Server};
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[test]
+fn healthz_does_not_touch_dependencies() {
+    let server = Server::start();
+    let start = Instant::now();
+    let (status, report) = server.get("/healthz");
+    assert_eq!(status, 200);
+    assert_eq!(report["status"], "ok");
+    assert_eq!(report["draining"], false);
+    assert!(report["database"].is_null());
+    assert!(start.elapsed() < Duration::from_secs(1));
+    assert_eq!(server.node.calls("getblockchaininfo"), 0);
+}
+
+#[test]
+fn readyz_asks_once_and_briefly() {
+    // the node accepts connections and never replies
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let accepted = Arc::new(AtomicUsize::new(0));
+    let counter = accepted.clone();
+    std::thread::spawn(move || {
+        let mut open = vec![];
+        for stream in listener.incoming() {
+            counter.fetch_add(1, Ordering::SeqCst);
+            open.push(stream);
+        }
+    });
+    let server = Server::start_on(Node::at(&url), &[]);
+    let start = Instant::now();
+    let (status, report) = server.get("/readyz");
+    assert_eq!(status, 503);
+    assert_eq!(report["node"]["status"], "fail");
+    assert_eq!(report["database"]["status"], "fail");
+    assert!(start.elapsed() < Duration::from_secs(4));
+    assert_eq!(accepted.load(Ordering::SeqCst), 1);
+}
@@ -0,0 +1,67 @@
+//! `ETag` and `Cache-Control` set by the HTTP cache middleware
+
+mod common;
+
+use common::{block_hash, Server, BLOCK_170};
+
+/// status, `ETag` and `Cache-Control` of the reply
+fn headers(server: &Server, path: &str, if_none_match: Option<&str>) -> (u16, String, String) {
+    let url = format!("{}{}", server.url, path);
+    let mut req = ureq::get(&url);
+    if let Some(tag) = if_none_match {
+        req = req.set("If-None-Match", tag);
+    }
+    let res = req.call().unwrap();
+    let header = |name| res.header(name).unwrap_or_default().to_string();
+    (res.status(), header("ETag"), header("Cache-Control"))
+}
+
+#[test]
+fn etag_is_content_digest() {
+    let server = Server::start();
+    let path = format!("/esplora/block/{}", BLOCK_170);
+    let (status, etag, _) = headers(&server, &path, None);
+    assert_eq!(status, 200);
+    // weak tag of the hex SHA-256 of the body
+    assert_eq!(etag.len(), "W/\"\"".len() + 64);
+    assert!(etag.starts_with("W/\""));
+
+    // the same body gets the same tag from another instance
+    let other = Server::start();
+    assert_eq!(headers(&other, &path, None).1, etag);
+
+    let (status, _, _) = headers(&server, &path, Some(&etag));
+    assert_eq!(status, 304);
+}
+
+#[test]
+fn deep_blocks_are_cached_for_long() {
+    let server = Server::start();
+    let deep = block_hash(1);
+    let (_, _, control) = headers(&server, &format!("/esplora/block/{}", deep), None);
+    assert_eq!(control, "public, max-age=86400");
+    let (_, _, control) = headers(&server, &format!("/api/blocks/{}", deep), None);
+    assert_eq!(control, "public, max-age=86400");
+    let page = format!("/api/blocks?from={}&limit=2", block_hash(100));
+    let (_, _, control) = headers(&server, &page, None);
+    assert_eq!(control, "public, max-age=86400");
+    // the tip and the page from the tip change with the next block
+    let (_, _, control) = headers(&server, &format!("/api/blocks/{}", block_hash(171)), None);
+    assert_eq!(control, "public, max-age=10");
+    let (_, _, control) = headers(&server, "/api/blocks?limit=2", None);
+    assert_eq!(control, "public, max-age=10");
+}
+
+#[test]
+fn bodies_have_no_confirmations() {
+    let server = Server::start();
+    let (_, block) = server.get(&format!("/api/blocks/{}", BLOCK_170));
+    assert!(
+        block["block"]["block"]["confirmations"].is_null(),
+        "{}",
+        block
+    );
+    assert_eq!(block["block"]["block"]["height"], 170);
+    let (_, blocks) = server.get("/api/blocks?limit=2");
+    assert!(blocks["blocks"]["list"][0]["header"]["confirmations"].is_null());
+}
@@ -0,0 +1,190 @@
+//! Replies of the handlers checked against the schemas of `/api/openapi.json`
+
+mod common;
+
+use common::{Server, BLOCK_170, TX_SPEND_170};
+use serde_json::{json, Value};
+
+/// checks the value against the subset of OpenAPI schema keywords the spec uses
+fn validate(spec: &Value, schema: &Value, value: &Value, path: &str) -> Result<(), String> {
+    if let Some(target) = schema["$ref"].as_str() {
+        let name = target.trim_start_matches("#/components/schemas/");
+        let resolved = &spec["components"]["schemas"][name];
+        if resolved.is_null() {
+            return Err(format!("{}: no schema {}", path, name));
+        }
+        return validate(spec, resolved, value, path);
+    }
+    if value.is_null() && schema["nullable"] == json!(true) {
+        return Ok(());
+    }
+    if let Some(all) = schema["allOf"].as_array() {
+        for x in all {
+            validate(spec, x, value, path)?;
+        }
+    }
+    if let Some(one) = schema["oneOf"].as_array() {
+        let errors: Vec<String> = one
+            .iter()
+            .filter_map(|x| validate(spec, x, value, path).err())
+            .collect();
+        if errors.len() + 1 != one.len() {
+            return Err(format!(
+                "{}: matches {} of oneOf {:?}",
+                path,
+                one.len() - errors.len(),
+                errors
+            ));
+        }
+    }
+    if let Some(options) = schema["enum"].as_array() {
+        if !options.contains(value) {
+            return Err(format!("{}: {} is not in {:?}", path, value, options));
+        }
+    }
+    let typed = match schema["type"].as_str() {
+        None => true,
+        Some("object") => value.is_object(),
+        Some("array") => value.is_array(),
+        Some("string") => value.is_string(),
+        Some("boolean") => value.is_boolean(),
+        Some("number") => value.is_number(),
+        Some("integer") => value.is_i64() || value.is_u64(),
+        Some(x) => return Err(format!("{}: unknown type {}", path, x)),
+    };
+    if !typed {
+        return Err(format!("{}: {} is not {}", path, value, schema["type"]));
+    }
+    if let (Some(min), Some(x)) = (schema["minimum"].as_f64(), value.as_f64()) {
+        if x < min {
+            return Err(format!("{}: {} is below {}", path, x, min));
+        }
+    }
+    if let Some(fields) = value.as_object() {
+        if let Some(required) = schema["required"].as_array() {
+            for key in required.iter().filter_map(Value::as_str) {
+                if !fields.contains_key(key) {
+                    return Err(format!("{}.{} is missing", path, key));
+                }
+            }
+        }
+        for (key, inner) in fields {
+            let inner_path = format!("{}.{}", path, key);
+            match schema["properties"].get(key) {
+                Some(x) => validate(spec, x, inner, &inner_path)?,
+                None if schema["additionalProperties"].is_object() => {
+                    validate(spec, &schema["additionalProperties"], inner, &inner_path)?
+                }
+                None => {}
+            }
+        }
+    }
+    if let Some(items) = value.as_array() {
+        if schema["items"].is_object() {
+            for (i, x) in items.iter().enumerate() {
+                validate(spec, &schema["items"], x, &format!("{}[{}]", path, i))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// checks the reply of the endpoint against the schema of its status in the spec
+fn check(server: &Server, spec: &Value, method: &str, path: &str, route: &str, body: &str) {
+    let (status, reply) = match method {
+        "get" => server.get(path),
+        _ => server.post(path, body),
+    };
+    let responses = &spec["paths"][route][method]["responses"];
+    let schema = &responses[status.to_string()]["content"]["application/json"]["schema"];
+    assert!(
+        schema.is_object(),
+        "{} {}: status {} is not in the spec",
+        method,
+        path,
+        status
+    );
+    if let Err(e) = validate(spec, schema, &reply, "reply") {
+        panic!("{} {} ({}): {}\n{}", method, path, status, e, reply);
+    }
+}
+
+#[test]
+fn replies_match_spec() {
+    let server = Server::start();
+    server.node.on("testmempoolaccept", |_| {
+        Ok(json!([{
+            "txid": TX_SPEND_170,
+            "allowed": true,
+            "vsize": 275,
+            "fees": { "base": 0.0 },
+        }]))
+    });
+    server
+        .node
+        .on("sendrawtransaction", |_| Ok(json!(TX_SPEND_170)));
+    let (status, spec) = server.get("/api/openapi.json");
+    assert_eq!(status, 200);
+    let hex = common::fixture("getrawtransaction_f4184f.json")["hex"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let unknown = format!("/api/tx/{}", "00".repeat(32));
+    let block = format!("/api/blocks/{}", BLOCK_170);
+    let tx = format!("/api/tx/{}", TX_SPEND_170);
+
+    check(&server, &spec, "get", "/", "/", "");
+    check(
+        &server,
+        &spec,
+        "get",
+        "/api/blocks?limit=3",
+        "/api/blocks",
+        "",
+    );
+    check(&server, &spec, "get", &block, "/api/blocks/{block}", "");
+    check(&server, &spec, "get", &tx, "/api/tx/{tx}", "");
+    check(&server, &spec, "get", &unknown, "/api/tx/{tx}", "");
+    check(&server, &spec, "post", "/api/decode", "/api/decode", &hex);
+    check(&server, &spec, "post", "/api/tx", "/api/tx", &hex);
+    check(&server, &spec, "post", "/api/tx", "/api/tx", "not hex");
+}
+
+#[test]
+fn schemas_are_derived() {
+    let spec = bitcoin_explorer::openapi::document();
+    let schemas = &spec["components"]["schemas"];
+    for name in &[
+        "BlockHeader",
+        "BlockSummary",
+        "RawTransaction",
+        "TxDetail",
+        "TxDetailVin",
+        "TxDetailVout",
+        "BlockStatsInfo",
+        "PagerOutput",
+        "Error",
+    ] {
+        assert!(schemas[*name].is_object(), "{} is not in the spec", name);
+    }
+    let header = &schemas["BlockHeader"];
+    assert!(header["required"]
+        .as_array()
+        .unwrap()
+        .contains(&json!("nTx")));
+    assert_eq!(
+        header["properties"]["previousblockhash"]["nullable"],
+        json!(true)
+    );
+    // the values of the detail are in satoshi, unlike the ones of the node
+    let vout = &schemas["TxDetailVout"]["properties"];
+    assert!(vout["value"].is_null());
+    let described = vout["valueSat"]["description"].as_str().unwrap_or_default();
+    assert!(described.contains("satoshi"), "{}", vout);
+    // every `$ref` points to a schema of the document
+    let text = spec.to_string();
+    for part in text.split("\"#/components/schemas/").skip(1) {
+        let name = &part[..part.find('"').unwrap()];
+        assert!(schemas[name].is_object(), "dangling reference {}", name);
+    }
+}
@@ -0,0 +1,137 @@
+//! Throttling of the API requests by client address and API key
+
+mod common;
+mod db;
+
+use bitcoin::hashes::hex::ToHex;
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin_explorer::ratelimit::{Limiter, MAX_BUCKETS};
+use bitcoincore_rpc_json::bitcoin;
+use common::Server;
+use db::TestDb;
+use std::time::{Duration, Instant};
+
+/// status and `Retry-After`, `X-RateLimit-Limit`, `X-RateLimit-Remaining` of the reply
+fn limited(server: &Server, path: &str, key: Option<&str>) -> (u16, [Option<String>; 3]) {
+    let url = format!("{}{}", server.url, path);
+    let mut req = ureq::get(&url);
+    if let Some(key) = key {
+        req = req.set("X-API-Key", key);
+    }
+    let res = match req.call() {
+        Ok(x) => x,
+        Err(ureq::Error::Status(_, x)) => x,
+        Err(e) => panic!("{}: {}", path, e),
+    };
+    let header = |name| res.header(name).map(str::to_string);
+    let headers = [
+        header("Retry-After"),
+        header("X-RateLimit-Limit"),
+        header("X-RateLimit-Remaining"),
+    ];
+    (res.status(), headers)
+}
+
+/// status of the request with the API key in the header
+fn with_key(server: &Server, path: &str, key: &str) -> u16 {
+    limited(server, path, Some(key)).0
+}
+
+fn some(x: &str) -> Option<String> {
+    Some(x.to_string())
+}
+
+#[test]
+fn throttled_reply_tells_when_to_retry() {
+    let server = Server::start_with(&["--rate-limit", "0.5/2"]);
+    let (status, headers) = limited(&server, "/api/blocks?limit=1", None);
+    assert_eq!(status, 200);
+    assert_eq!(headers, [None, some("2"), some("1")]);
+    let (status, headers) = limited(&server, "/api/blocks?limit=1", None);
+    assert_eq!(status, 200);
+    assert_eq!(headers, [None, some("2"), some("0")]);
+    let (status, headers) = limited(&server, "/api/blocks?limit=1", None);
+    assert_eq!(status, 429);
+    // a token in 2 seconds at the rate of 0.5 per second
+    assert_eq!(headers, [some("2"), some("2"), some("0")]);
+}
+
+#[test]
+fn key_tier_lifts_anonymous_limit() {
+    let db = match TestDb::create() {
+        Some(x) => x,
+        None => return,
+    };
+    let digest = sha256::Hash::hash(b"pro-key").to_hex();
+    db.seed(&format!(
+        "INSERT INTO api_keys (key_hash, owner, tier) VALUES ('{}', 'test', 'pro')",
+        digest
+    ));
+    let server = Server::start_with(&[
+        "--rate-limit",
+        "0.01/1",
+        "--rate-limit-tiers",
+        "pro=0.01/50",
+        "--database-url",
+        &db.url,
+    ]);
+    // the lookup of the key takes the only token of the address
+    let (status, headers) = limited(&server, "/api/blocks?limit=1", Some("pro-key"));
+    assert_eq!(status, 200);
+    assert_eq!(headers, [None, some("50"), some("49")]);
+    assert_eq!(limited(&server, "/api/blocks?limit=1", None).0, 429);
+    // the known key is limited by its tier only
+    for remaining in (44..49).rev() {
+        let (status, headers) = limited(&server, "/api/blocks?limit=1", Some("pro-key"));
+        assert_eq!(status, 200);
+        assert_eq!(headers, [None, some("50"), Some(remaining.to_string())]);
+    }
+}
+
+#[test]
+fn flood_of_addresses_evicts_least_recent() {
+    let node = common::Node::at("http://127.0.0.1:9");
+    let args = common::args(&node, &["--rate-limit", "0.01/1"]);
+    let pool = sqlx::PgPool::connect_lazy(common::DATABASE_URL).unwrap();
+    let limiter = Limiter::from_args(&args, pool).unwrap();
+    assert!(limiter.take_anonymous("first", false).is_ok());
+    assert!(limiter.take_anonymous("first", false).is_err());
+    let start = Instant::now();
+    for i in 0..3 * MAX_BUCKETS {
+        let _ = limiter.take_anonymous(&format!("client-{}", i), false);
+    }
+    assert!(
+        start.elapsed() < Duration::from_secs(30),
+        "{:?}",
+        start.elapsed()
+    );
+    // the first client was dropped and comes back with a full bucket,
+    // the recent ones are still throttled
+    assert!(limiter.take_anonymous("first", false).is_ok());
+    let last = format!("client-{}", 3 * MAX_BUCKETS - 1);
+    assert!(limiter.take_anonymous(&last, false).is_err());
+}
+
+#[test]
+fn key_lookup_is_charged_to_address() {
+    let server = Server::start_with(&["--rate-limit", "0.01/1"]);
+    // the key can't be read from the unreachable database,
+    // so the request is limited by the address
+    assert_eq!(with_key(&server, "/api/blocks?limit=1", "first"), 200);
+    // the address has no tokens left, the next key is not even read
+    let start = Instant::now();
+    assert_eq!(with_key(&server, "/api/blocks?limit=1", "second"), 429);
+    assert!(start.elapsed() < Duration::from_secs(1));
+}
+
+#[test]
+fn key_in_query_is_ignored() {
+    let server = Server::start_with(&["--rate-limit", "0.01/1"]);
+    let start = Instant::now();
+    let (status, _) = server.get("/api/blocks?limit=1&api_key=first");
+    assert_eq!(status, 200);
+    // no lookup of the key in the unreachable database
+    assert!(start.elapsed() < Duration::from_secs(1));
+    let (status, _) = server.get("/api/blocks?limit=1&api_key=second");
+    assert_eq!(status, 429);
+}
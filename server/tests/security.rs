@@ -0,0 +1,153 @@
+//! Security headers of the responses and CORS
+
+mod common;
+
+use common::{Node, Server};
+
+const ORIGIN: &str = "https://app.example";
+
+/// reply to the request with the headers, whatever its status is
+fn call(server: &Server, method: &str, path: &str, headers: &[(&str, &str)]) -> ureq::Response {
+    let mut req = ureq::request(method, &format!("{}{}", server.url, path));
+    for (name, value) in headers {
+        req = req.set(name, value);
+    }
+    match req.call() {
+        Ok(x) => x,
+        Err(ureq::Error::Status(_, x)) => x,
+        Err(e) => panic!("{} {}: {}", method, path, e),
+    }
+}
+
+/// `Strict-Transport-Security` of the reply, with the request headers
+fn hsts(server: &Server, headers: &[(&str, &str)]) -> Option<String> {
+    let res = call(server, "GET", "/api/openapi.json", headers);
+    res.header("Strict-Transport-Security").map(str::to_string)
+}
+
+#[test]
+fn security_headers() {
+    let server = Server::start();
+    let res = call(&server, "GET", "/api/openapi.json", &[]);
+    assert_eq!(res.header("X-Content-Type-Options"), Some("nosniff"));
+    assert_eq!(res.header("X-Frame-Options"), Some("DENY"));
+    assert_eq!(
+        res.header("Referrer-Policy"),
+        Some("strict-origin-when-cross-origin")
+    );
+    let csp = res.header("Content-Security-Policy").unwrap_or_default();
+    assert!(csp.starts_with("default-src 'self'"), "{}", csp);
+    assert!(csp.contains("frame-ancestors 'none'"), "{}", csp);
+}
+
+#[test]
+fn csp_is_configurable() {
+    let server = Server::start_with(&["--csp", "default-src 'none'"]);
+    let res = call(&server, "GET", "/api/openapi.json", &[]);
+    assert_eq!(
+        res.header("Content-Security-Policy"),
+        Some("default-src 'none'")
+    );
+    let server = Server::start_with(&["--csp", ""]);
+    let res = call(&server, "GET", "/api/openapi.json", &[]);
+    assert_eq!(res.header("Content-Security-Policy"), None);
+    assert_eq!(res.header("X-Content-Type-Options"), Some("nosniff"));
+}
+
+#[test]
+fn cors_allows_listed_origin() {
+    let server = Server::start_with(&["--cors-origins", ORIGIN]);
+    let res = call(&server, "GET", "/api/openapi.json", &[("Origin", ORIGIN)]);
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.header("Access-Control-Allow-Origin"), Some(ORIGIN));
+    let exposed = res
+        .header("Access-Control-Expose-Headers")
+        .unwrap_or_default();
+    for name in &[
+        "ETag",
+        "Retry-After",
+        "X-RateLimit-Limit",
+        "X-RateLimit-Remaining",
+    ] {
+        assert!(exposed.contains(name), "{} is not in {}", name, exposed);
+    }
+}
+
+#[test]
+fn cors_denies_other_origin() {
+    let server = Server::start_with(&["--cors-origins", ORIGIN]);
+    let res = call(
+        &server,
+        "GET",
+        "/api/openapi.json",
+        &[("Origin", "https://other.example")],
+    );
+    assert_eq!(res.status(), 401);
+    assert_eq!(res.header("Access-Control-Allow-Origin"), None);
+}
+
+#[test]
+fn cors_preflight() {
+    let server = Server::start_with(&["--cors-origins", ORIGIN, "--cors-max-age", "600"]);
+    let res = call(
+        &server,
+        "OPTIONS",
+        "/api/tx",
+        &[
+            ("Origin", ORIGIN),
+            ("Access-Control-Request-Method", "POST"),
+            ("Access-Control-Request-Headers", "X-API-Key"),
+        ],
+    );
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.header("Access-Control-Allow-Origin"), Some(ORIGIN));
+    assert_eq!(
+        res.header("Access-Control-Allow-Methods"),
+        Some("GET, POST, OPTIONS")
+    );
+    assert_eq!(res.header("Access-Control-Max-Age"), Some("600"));
+    let allowed = res
+        .header("Access-Control-Allow-Headers")
+        .unwrap_or_default();
+    assert!(allowed.contains("X-API-Key"), "{}", allowed);
+}
+
+#[test]
+fn cors_is_off_without_origins() {
+    let server = Server::start();
+    let res = call(&server, "GET", "/api/openapi.json", &[("Origin", ORIGIN)]);
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.header("Access-Control-Allow-Origin"), None);
+}
+
+#[test]
+fn hsts_only_over_https() {
+    let server = Server::start_with(&["--hsts-max-age", "3600", "--trust-proxy", "true"]);
+    assert_eq!(hsts(&server, &[]), None);
+    assert_eq!(hsts(&server, &[("X-Forwarded-Proto", "http")]), None);
+    assert_eq!(
+        hsts(&server, &[("X-Forwarded-Proto", "https")]).as_deref(),
+        Some("max-age=3600; includeSubDomains")
+    );
+    assert!(hsts(&server, &[("Forwarded", "for=192.0.2.43;proto=https")]).is_some());
+}
+
+#[test]
+fn hsts_ignores_untrusted_proxy() {
+    let server = Server::start_with(&["--hsts-max-age", "3600"]);
+    assert_eq!(hsts(&server, &[("X-Forwarded-Proto", "https")]), None);
+}
+
+#[test]
+fn hsts_requires_https() {
+    let node = Node::at("http://127.0.0.1:9");
+    let plain = common::args(&node, &["--hsts-max-age", "3600"]);
+    let e = plain.validate().unwrap_err().to_string();
+    assert!(
+        e.contains("HSTS_MAX_AGE requires TLS_CERT or TRUST_PROXY"),
+        "{}",
+        e
+    );
+    let proxied = common::args(&node, &["--hsts-max-age", "3600", "--trust-proxy", "true"]);
+    proxied.validate().unwrap();
+}
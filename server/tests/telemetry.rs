@@ -0,0 +1,112 @@
+//! Spans of the request and of the node calls it makes, exported in-memory
+
+mod common;
+
+use common::{Node, Server, BLOCK_170};
+use opentelemetry::sdk::export::trace::{ExportResult, SpanData, SpanExporter};
+use opentelemetry::sdk::propagation::TraceContextPropagator;
+use opentelemetry::sdk::trace::TracerProvider;
+use opentelemetry::trace::TracerProvider as _;
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+
+const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+const PARENT_ID: &str = "00f067aa0ba902b7";
+
+/// keeps the finished spans
+#[derive(Clone, Debug, Default)]
+struct Memory(Arc<Mutex<Vec<SpanData>>>);
+
+#[async_trait::async_trait]
+impl SpanExporter for Memory {
+    async fn export(&mut self, batch: Vec<SpanData>) -> ExportResult {
+        self.0.lock().unwrap().extend(batch);
+        Ok(())
+    }
+}
+
+impl Memory {
+    /// waits for the span with the name to finish
+    fn find(&self, name: &str) -> SpanData {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(5) {
+            let spans = self.0.lock().unwrap();
+            if let Some(x) = spans.iter().find(|s| s.name == name) {
+                return x.clone();
+            }
+            drop(spans);
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        panic!("no span {}", name);
+    }
+}
+
+/// node recording the `traceparent` of the calls
+fn node(traceparents: Arc<Mutex<Vec<String>>>) -> Node {
+    let mut app = tide::with_state(traceparents);
+    app.at("/").post(
+        |mut req: tide::Request<Arc<Mutex<Vec<String>>>>| async move {
+            if let Some(x) = req.header("traceparent") {
+                req.state().lock().unwrap().push(x.as_str().to_string());
+            }
+            let call: Value = req.body_json().await?;
+            let method = call["method"].as_str().unwrap_or_default();
+            let reply = match common::default_handler(method, &call["params"]) {
+                Ok(result) => json!({ "result": result, "error": null, "id": call["id"] }),
+                Err((code, message)) => json!({
+                    "result": null,
+                    "error": { "code": code, "message": message },
+                    "id": call["id"],
+                }),
+            };
+            tide::Body::from_json(&reply)
+        },
+    );
+    Node::at(&common::listen(app))
+}
+
+#[test]
+fn spans_nest_and_continue_the_trace() {
+    let memory = Memory::default();
+    let provider = TracerProvider::builder()
+        .with_simple_exporter(memory.clone())
+        .build();
+    let tracer = provider.get_tracer("test", None);
+    opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+    tracing_subscriber::registry()
+        .with(tracing_opentelemetry::layer().with_tracer(tracer))
+        .try_init()
+        .unwrap();
+
+    let traceparents = Arc::new(Mutex::new(vec![]));
+    let server = Server::start_on(node(traceparents.clone()), &[]);
+    let path = format!("/api/blocks/{}", BLOCK_170);
+    let res = ureq::get(&format!("{}{}", server.url, path))
+        .set("traceparent", &format!("00-{}-{}-01", TRACE_ID, PARENT_ID))
+        .call()
+        .unwrap();
+    assert_eq!(res.status(), 200);
+
+    // the request continues the trace of the caller
+    let request = memory.find(&format!("GET {}", path));
+    let trace_id = request.span_context.trace_id();
+    assert_eq!(format!("{:032x}", trace_id.to_u128()), TRACE_ID);
+    assert_eq!(
+        format!("{:016x}", request.parent_span_id.to_u64()),
+        PARENT_ID
+    );
+
+    // the node call is the child of the request
+    let rpc = memory.find("rpc getblock");
+    assert_eq!(rpc.span_context.trace_id(), trace_id);
+    assert_eq!(rpc.parent_span_id, request.span_context.span_id());
+
+    // and the node is told about it
+    let rpc_id = format!("{:016x}", rpc.span_context.span_id().to_u64());
+    let sent = traceparents.lock().unwrap().clone();
+    let expected = format!("00-{}-{}-01", TRACE_ID, rpc_id);
+    assert!(sent.contains(&expected), "{:?} has no {}", sent, expected);
+}
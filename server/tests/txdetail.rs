@@ -0,0 +1,151 @@
+//! `/api/tx/:tx`, the node reply joined with the outputs index
+
+mod common;
+mod db;
+
+use common::{Server, TIP, TX_COINBASE_170, TX_SPEND_170};
+use db::TestDb;
+use explorer_types::TxDetail;
+use serde_json::{json, Value};
+
+const COINBASE_9: &str = "0437cd7f8525ceed2324359c2d0ba26006d92d856a9c20fa0241106ee5a597c9";
+// synthetic transaction spending the first output of the transaction
+const SPENDING: &str = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+// address the spent output is indexed with
+const ADDRESS: &str = "12cbQLTFMXRnSzktFkuoG3eHoMeFtpTu3S";
+// the spent output is indexed with more than the transaction sends
+const FEE: u64 = 10_000;
+
+/// index with the transaction, its spent output and the spending of its first output
+fn seed(db: &TestDb) {
+    db.seed(&format!(
+        "INSERT INTO final_tx (txhash, txindex, blockheight) VALUES \
+            (decode('{spend}', 'hex'), 1, 170); \
+        INSERT INTO final_outputs (txhash, vout, blockheight, txindex, address, value, \
+                spent_txhash, spent_vin, spent_height) VALUES \
+            (decode('{spent}', 'hex'), 0, 9, 0, '{address}', {spent_value}, \
+                decode('{spend}', 'hex'), 0, 170), \
+            (decode('{spend}', 'hex'), 0, 170, 1, NULL, 1000000000, \
+                decode('{spending}', 'hex'), 3, 180), \
+            (decode('{spend}', 'hex'), 1, 170, 1, NULL, 4000000000, NULL, NULL, NULL)",
+        spend = TX_SPEND_170,
+        spent = COINBASE_9,
+        spending = SPENDING,
+        address = ADDRESS,
+        spent_value = 5_000_000_000 + FEE,
+    ));
+}
+
+/// `Cache-Control` of the reply
+fn cache_control(server: &Server, path: &str) -> String {
+    let res = ureq::get(&format!("{}{}", server.url, path))
+        .call()
+        .unwrap();
+    res.header("Cache-Control").unwrap_or_default().to_string()
+}
+
+#[test]
+fn enriched_from_the_index() {
+    let db = match TestDb::create() {
+        Some(x) => x,
+        None => return,
+    };
+    seed(&db);
+    let server = Server::start_with(&["--database-url", &db.url]);
+    let (status, reply) = server.get(&format!("/api/tx/{}", TX_SPEND_170));
+    assert_eq!(status, 200, "{}", reply);
+    let tx = &reply["tx"];
+
+    let vin = &tx["vin"][0];
+    assert_eq!(vin["txid"], COINBASE_9);
+    assert_eq!(vin["address"], ADDRESS);
+    assert_eq!(vin["valueSat"], 5_000_000_000 + FEE);
+
+    // in satoshi, the BTC value of the node is left out
+    assert_eq!(tx["vout"][0]["valueSat"], 1_000_000_000_u64);
+    assert_eq!(tx["vout"][1]["valueSat"], 4_000_000_000_u64);
+    assert!(tx["vout"][0].get("value").is_none());
+
+    assert_eq!(tx["vout"][0]["spent"], true);
+    assert_eq!(
+        tx["vout"][0]["spending"],
+        json!({ "txid": SPENDING, "vin": 3, "height": 180 })
+    );
+    assert_eq!(tx["vout"][1]["spent"], false);
+    assert_eq!(tx["vout"][1]["spending"], Value::Null);
+
+    assert_eq!(tx["fee"], FEE);
+    let vsize = tx["vsize"].as_f64().unwrap();
+    let feerate = (FEE as f64 / vsize * 1000.0).round() / 1000.0;
+    assert_eq!(tx["feerate"], feerate);
+
+    assert_eq!(tx["blockheight"], 170);
+    assert_eq!(tx["txindex"], 1);
+
+    // the unspent output can still change
+    let path = format!("/api/tx/{}", TX_SPEND_170);
+    assert_eq!(cache_control(&server, &path), "public, max-age=10");
+}
+
+#[test]
+fn without_the_index() {
+    let server = Server::start();
+    let (status, reply) = server.get(&format!("/api/tx/{}", TX_SPEND_170));
+    assert_eq!(status, 200, "{}", reply);
+    let tx = &reply["tx"];
+    assert_eq!(tx["vin"][0]["address"], Value::Null);
+    assert_eq!(tx["vin"][0]["valueSat"], Value::Null);
+    assert_eq!(tx["vout"][0]["valueSat"], 1_000_000_000_u64);
+    assert_eq!(tx["vout"][0]["spent"], Value::Null);
+    assert_eq!(tx["fee"], Value::Null);
+    assert_eq!(tx["feerate"], Value::Null);
+    // the height comes from the node, the position in the block from the index only
+    assert_eq!(tx["blockheight"], 170);
+    assert_eq!(tx["txindex"], Value::Null);
+
+    let (status, reply) = server.get(&format!("/api/tx/{}", TX_COINBASE_170));
+    assert_eq!(status, 200, "{}", reply);
+    assert_eq!(reply["tx"]["fee"], Value::Null);
+    assert!(reply["tx"]["vin"][0]["coinbase"].is_string());
+}
+
+#[test]
+fn unspendable_outputs_do_not_wait_for_spending() {
+    let server = Server::start();
+    let (_, reply) = server.get(&format!("/api/tx/{}", TX_SPEND_170));
+    let mut tx: TxDetail = serde_json::from_value(reply["tx"].clone()).unwrap();
+    assert!(!tx.is_spent());
+    tx.vout[0].spent = Some(true);
+    tx.vout[1].spent = Some(false);
+    assert!(!tx.is_spent());
+    // `OP_RETURN` data
+    tx.vout[1].script_pub_key.hex = "6a0b68656c6c6f20776f726c64".to_string();
+    assert!(tx.vout[1].is_unspendable());
+    assert!(tx.is_spent());
+    // not indexed yet
+    tx.vout[0].spent = None;
+    assert!(!tx.is_spent());
+    assert!(!tx.vout[0].is_unspendable());
+}
+
+#[test]
+fn spent_transaction_is_cached_for_long() {
+    let db = match TestDb::create() {
+        Some(x) => x,
+        None => return,
+    };
+    seed(&db);
+    db.seed(&format!(
+        "UPDATE final_outputs SET spent_txhash = decode('{}', 'hex'), spent_vin = 0, \
+            spent_height = 180 WHERE txhash = decode('{}', 'hex') AND vout = 1",
+        SPENDING, TX_SPEND_170
+    ));
+    let server = Server::start_with(&["--database-url", &db.url]);
+    server.node.on("getblockchaininfo", |params| {
+        let mut out = common::default_handler("getblockchaininfo", params)?;
+        out["blocks"] = json!(TIP + 1000);
+        Ok(out)
+    });
+    let path = format!("/api/tx/{}", TX_SPEND_170);
+    assert_eq!(cache_control(&server, &path), "public, max-age=86400");
+}
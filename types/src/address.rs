@@ -0,0 +1,19 @@
+use crate::pager;
+use serde::{Deserialize, Serialize};
+
+/// single record of the address history
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AddressTx {
+    pub txid: String,
+    pub blockheight: u32,
+    pub txindex: u32,
+    // total amount of BTC transferred in this transaction, negative for spending
+    pub sent_btc: i64,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct AddressTxList {
+    pub address: String,
+    pub list: Vec<AddressTx>,
+    pub pager: Option<pager::Output>,
+}
@@ -0,0 +1,116 @@
+use crate::tx::BlockTransaction;
+use serde::{Deserialize, Serialize};
+
+/// result of `getblockstats` RPC call
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct BlockStatsInfo {
+    pub avgfee: u32,
+    pub avgfeerate: u32,
+    pub avgtxsize: u64,
+    pub blockhash: String,
+    pub feerate_percentiles: Vec<u32>,
+    pub height: u32,
+    pub ins: u32,
+    pub maxfee: u64,
+    pub maxfeerate: u64,
+    pub maxtxsize: u32,
+    pub medianfee: u32,
+    pub mediantime: u64,
+    pub mediantxsize: u32,
+    pub minfee: u64,
+    pub minfeerate: u64,
+    pub mintxsize: u32,
+    pub outs: u32,
+    pub subsidy: u64,
+    pub swtotal_size: u32,
+    pub swtotal_weight: u64,
+    pub swtxs: u32,
+    pub time: u64,
+    pub total_out: u64,
+    pub total_size: u64,
+    pub total_weight: u64,
+    pub totalfee: u64,
+    pub txs: u32,
+    pub utxo_increase: i32,
+    pub utxo_size_inc: i32,
+}
+
+/// result of `getblockheader` RPC call with verbosity
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct BlockHeader {
+    pub hash: String,
+    /// as of the node reply, left out of the API responses where it changes with every block:
+    /// it is the tip height minus `height` plus one, -1 for the blocks out of the best chain
+    #[serde(default)]
+    pub confirmations: i32,
+    pub height: u32,
+    pub version: i32,
+    pub version_hex: Option<String>,
+    pub merkleroot: String,
+    pub time: u64,
+    pub mediantime: Option<u64>,
+    pub nonce: u32,
+    pub bits: String,
+    pub difficulty: f64,
+    pub chainwork: String,
+    pub n_tx: u32,
+    pub previousblockhash: Option<String>,
+    pub nextblockhash: Option<String>,
+}
+
+/// result of `getblock` RPC call with verbosity 1, transactions are given by txid
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct BlockSummary {
+    pub hash: String,
+    /// as of the node reply, left out of the API responses where it changes with every block:
+    /// it is the tip height minus `height` plus one, -1 for the blocks out of the best chain
+    #[serde(default)]
+    pub confirmations: i32,
+    pub size: u64,
+    pub strippedsize: Option<u64>,
+    pub weight: u64,
+    pub height: u32,
+    pub version: i32,
+    pub version_hex: Option<String>,
+    pub merkleroot: String,
+    pub tx: Vec<String>,
+    pub time: u64,
+    pub mediantime: Option<u64>,
+    pub nonce: u32,
+    pub bits: String,
+    pub difficulty: f64,
+    pub chainwork: String,
+    pub n_tx: u32,
+    pub previousblockhash: Option<String>,
+    pub nextblockhash: Option<String>,
+}
+
+/// result of `getblock` RPC call with verbosity 2
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockInfo {
+    pub hash: String,
+    pub confirmations: i32,
+    pub strippedsize: u64,
+    pub size: i64,
+    pub weight: u64,
+    pub height: u32,
+    pub version: u64,
+    pub version_hex: String,
+    pub merkleroot: String,
+    pub tx: Vec<BlockTransaction>,
+    pub time: i64,
+    pub mediantime: i64,
+    pub nonce: u64,
+    pub bits: String,
+    pub difficulty: f64,
+    pub chainwork: String,
+    pub n_tx: u32,
+    pub previousblockhash: String,
+    pub nextblockhash: Option<String>,
+}
@@ -0,0 +1,43 @@
+use serde::{Deserialize, Serialize};
+
+/// Transaction accepted by the node and relayed to its peers
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Broadcast {
+    pub txid: String,
+    pub vsize: Option<u64>,
+    /// fee in satoshi
+    pub fee: Option<u64>,
+}
+
+/// Reason the node refuses the transaction, i.e. `min relay fee not met`
+/// or `bad-txns-inputs-missingorspent`
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Rejection {
+    pub txid: Option<String>,
+    pub reason: String,
+    /// explanation of the reason, when the node gives one
+    pub details: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MempoolAcceptFees {
+    /// fee in BTC
+    pub base: f64,
+}
+
+/// result of `testmempoolaccept` RPC call for one transaction
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MempoolAccept {
+    pub txid: String,
+    pub wtxid: Option<String>,
+    #[serde(default)]
+    pub allowed: bool,
+    pub vsize: Option<u64>,
+    pub fees: Option<MempoolAcceptFees>,
+    #[serde(rename = "reject-reason")]
+    pub reject_reason: Option<String>,
+    #[serde(rename = "reject-details")]
+    pub reject_details: Option<String>,
+}
@@ -0,0 +1,17 @@
+use serde::{Deserialize, Serialize};
+
+/// result of `getblockchaininfo` RPC call
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ChainInfo {
+    pub chain: String,
+    pub blocks: u32,
+    pub headers: u32,
+    pub bestblockhash: String,
+    pub difficulty: f64,
+    pub mediantime: u64,
+    pub verificationprogress: f64,
+    pub initialblockdownload: bool,
+    pub chainwork: String,
+    pub size_on_disk: u64,
+    pub pruned: bool,
+}
@@ -0,0 +1,107 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// machine readable kind of the API error
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    NotFound,
+    InvalidParam,
+    UpstreamUnavailable,
+    NotIndexed,
+    RateLimited,
+    Unauthorized,
+    InternalError,
+}
+
+impl ErrorCode {
+    /// HTTP status that is sent with this error
+    pub fn status(self) -> u16 {
+        match self {
+            Self::NotFound => 404,
+            Self::InvalidParam => 400,
+            Self::UpstreamUnavailable => 502,
+            Self::NotIndexed => 503,
+            Self::RateLimited => 429,
+            Self::Unauthorized => 401,
+            Self::InternalError => 500,
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::NotFound => "not_found",
+            Self::InvalidParam => "invalid_param",
+            Self::UpstreamUnavailable => "upstream_unavailable",
+            Self::NotIndexed => "not_indexed",
+            Self::RateLimited => "rate_limited",
+            Self::Unauthorized => "unauthorized",
+            Self::InternalError => "internal_error",
+        };
+        f.write_str(s)
+    }
+}
+
+/// error of the explorer API
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ApiError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl ApiError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::NotFound, message)
+    }
+    pub fn invalid_param(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::InvalidParam, message)
+    }
+    pub fn upstream_unavailable(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::UpstreamUnavailable, message)
+    }
+    pub fn not_indexed(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::NotIndexed, message)
+    }
+    pub fn rate_limited(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::RateLimited, message)
+    }
+    pub fn unauthorized(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::Unauthorized, message)
+    }
+    pub fn internal_error(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::InternalError, message)
+    }
+    pub fn status(&self) -> u16 {
+        self.code.status()
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+/// error envelope returned by every endpoint of the explorer API,
+/// `{"error": {"code": "not_found", "message": "..."}}`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(
+    feature = "schema",
+    derive(schemars::JsonSchema),
+    schemars(rename = "Error")
+)]
+pub struct ErrorResponse {
+    pub error: ApiError,
+}
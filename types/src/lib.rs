@@ -0,0 +1,27 @@
+//! Serde types shared by the server, the indexer and the SDK.
+//!
+//! Anything that crosses a process boundary (bitcoind JSON-RPC replies,
+//! explorer API responses) should be declared here once, so the crates
+//! cannot drift apart. `tests/fixtures` keeps the bitcoind replies they are checked against.
+
+pub mod address;
+pub mod block;
+pub mod broadcast;
+pub mod chain;
+pub mod error;
+pub mod network;
+pub mod pager;
+pub mod rpc;
+pub mod tx;
+
+pub use address::{AddressTx, AddressTxList};
+pub use block::{BlockHeader, BlockInfo, BlockStatsInfo, BlockSummary};
+pub use broadcast::{Broadcast, MempoolAccept, Rejection};
+pub use chain::ChainInfo;
+pub use error::{ApiError, ErrorCode, ErrorResponse};
+pub use network::Network;
+pub use rpc::RpcResponse;
+pub use tx::{
+    BlockTransaction, BlockTxVin, BlockTxVout, RawTransaction, TxDetail, TxDetailVin, TxDetailVout,
+    TxScriptPubKey, TxScriptSig, TxSpending,
+};
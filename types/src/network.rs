@@ -0,0 +1,80 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Bitcoin network served by the explorer
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Network {
+    Mainnet,
+    Testnet,
+    Signet,
+    Regtest,
+}
+
+impl Network {
+    pub const ALL: [Network; 4] = [
+        Network::Mainnet,
+        Network::Testnet,
+        Network::Signet,
+        Network::Regtest,
+    ];
+
+    /// name in the configuration and in `/api/{network}/` paths
+    pub fn name(&self) -> &'static str {
+        match self {
+            Network::Mainnet => "mainnet",
+            Network::Testnet => "testnet",
+            Network::Signet => "signet",
+            Network::Regtest => "regtest",
+        }
+    }
+
+    /// `chain` of the network in `getblockchaininfo` reply
+    pub fn chain(&self) -> &'static str {
+        match self {
+            Network::Mainnet => "main",
+            Network::Testnet => "test",
+            Network::Signet => "signet",
+            Network::Regtest => "regtest",
+        }
+    }
+
+    /// default JSON-RPC port of Bitcoin Core
+    pub fn rpc_port(&self) -> u16 {
+        match self {
+            Network::Mainnet => 8332,
+            Network::Testnet => 18332,
+            Network::Signet => 38332,
+            Network::Regtest => 18443,
+        }
+    }
+
+    /// database schema of the index of the network.
+    /// Mainnet stays in the default schema of the existing databases
+    pub fn schema(&self) -> &'static str {
+        match self {
+            Network::Mainnet => "public",
+            x => x.name(),
+        }
+    }
+}
+
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Network {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        Network::ALL
+            .iter()
+            .find(|n| n.name() == s || n.chain() == s)
+            .copied()
+            .ok_or_else(|| format!("unknown network {}", s))
+    }
+}
@@ -0,0 +1,7 @@
+use serde::{Deserialize, Serialize};
+
+/// JSON-RPC envelope of the bitcoind reply
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RpcResponse<T> {
+    pub result: T,
+}
@@ -0,0 +1,188 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct TxScriptSig {
+    pub asm: String,
+    pub hex: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct TxScriptPubKey {
+    pub asm: String,
+    pub hex: String,
+    pub req_sigs: Option<u32>,
+    #[serde(rename = "type")]
+    pub script_type: String, // witness_v0_keyhash, witness_v0_scripthash, pubkeyhash, nulldata
+    pub addresses: Option<Vec<String>>,
+    // replaces `addresses` since Bitcoin Core 22
+    pub address: Option<String>,
+}
+
+impl TxScriptPubKey {
+    /// address of the standard script
+    pub fn address(&self) -> Option<&str> {
+        match &self.address {
+            Some(x) => Some(x.as_str()),
+            None => self.addresses.as_ref()?.first().map(String::as_str),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct BlockTxVin {
+    pub txid: Option<String>,
+    pub vout: Option<u32>,
+    pub coinbase: Option<String>,
+    pub script_sig: Option<TxScriptSig>,
+    pub txinwitness: Option<Vec<String>>,
+    pub sequence: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct BlockTxVout {
+    pub value: f64,
+    pub n: u32,
+    pub script_pub_key: TxScriptPubKey,
+}
+
+impl BlockTxVout {
+    /// value in satoshi
+    pub fn sats(&self) -> i64 {
+        (self.value * 100_000_000.0).round() as i64
+    }
+}
+
+/// transaction as it is returned inside of `getblock` with verbosity 2
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockTransaction {
+    pub txid: Option<String>,
+    pub hash: String,
+    pub version: u32,
+    pub size: u32,
+    pub vsize: u32,
+    pub weight: u32,
+    pub locktime: u32,
+    pub vin: Vec<BlockTxVin>,
+    pub vout: Vec<BlockTxVout>,
+    pub hex: String,
+}
+
+/// result of `getrawtransaction` RPC call with verbosity
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct RawTransaction {
+    pub txid: String,
+    pub hash: String,
+    pub version: u32,
+    pub size: u32,
+    pub vsize: u32,
+    pub locktime: u32,
+    pub vin: Vec<BlockTxVin>,
+    pub vout: Vec<BlockTxVout>,
+    pub hex: String,
+    // none while the transaction is in the mempool
+    pub blockhash: Option<String>,
+    pub confirmations: Option<u32>,
+    pub time: Option<u64>,
+    pub blocktime: Option<u64>,
+}
+
+/// input of the transaction with the output it spends
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct TxDetailVin {
+    pub txid: Option<String>,
+    pub vout: Option<u32>,
+    pub coinbase: Option<String>,
+    pub script_sig: Option<TxScriptSig>,
+    pub txinwitness: Option<Vec<String>>,
+    pub sequence: u64,
+    // address of the spent output, none for non-standard scripts
+    pub address: Option<String>,
+    /// value of the spent output in satoshi, none while it is not indexed
+    pub value_sat: Option<u64>,
+}
+
+/// input that spends the output
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct TxSpending {
+    pub txid: String,
+    pub vin: u32,
+    pub height: Option<u32>,
+}
+
+/// output of the transaction with its spending
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct TxDetailVout {
+    /// value in satoshi, unlike `value` in BTC of the node reply which is left out
+    pub value_sat: u64,
+    pub n: u32,
+    pub script_pub_key: TxScriptPubKey,
+    // none while the output is not indexed
+    pub spent: Option<bool>,
+    pub spending: Option<TxSpending>,
+}
+
+/// transaction with the outputs it spends, the spending of its outputs and its fee,
+/// as it is returned by `/api/tx/:tx` and `/api/decode`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct TxDetail {
+    pub txid: String,
+    pub hash: String,
+    pub version: u32,
+    pub size: u32,
+    pub vsize: u32,
+    pub weight: u32,
+    pub locktime: u32,
+    pub vin: Vec<TxDetailVin>,
+    pub vout: Vec<TxDetailVout>,
+    // fee in satoshi, known when all the spent outputs are
+    pub fee: Option<u64>,
+    // sat/vB
+    pub feerate: Option<f64>,
+    // confirmations are left out, they are the tip height minus `blockheight` plus one
+    pub blockhash: Option<String>,
+    pub blockheight: Option<u32>,
+    // index of the transaction in the block
+    pub txindex: Option<u32>,
+    pub blocktime: Option<u64>,
+    pub hex: String,
+}
+
+// longest script that can be spent, as of the consensus rules
+const MAX_SCRIPT_SIZE: usize = 10_000;
+
+impl TxDetailVout {
+    /// whether the output can never be spent, i.e. `OP_RETURN` data
+    pub fn is_unspendable(&self) -> bool {
+        let hex = &self.script_pub_key.hex;
+        hex.starts_with("6a") || hex.len() > MAX_SCRIPT_SIZE * 2
+    }
+}
+
+impl TxDetail {
+    /// whether all the outputs that can be spent are known to be spent,
+    /// so the detail does not change anymore
+    pub fn is_spent(&self) -> bool {
+        self.vout
+            .iter()
+            .all(|x| x.spent == Some(true) || x.is_unspendable())
+    }
+}
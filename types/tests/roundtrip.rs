@@ -0,0 +1,152 @@
+//! Round trips of the shared types through the bitcoind replies in `tests/fixtures`.
+//!
+//! The fixtures are mainnet block 170 and its transaction spending the first coins,
+//! in the shape of Bitcoin Core 25 replies. The hashes and the hex are the chain data,
+//! the tip-relative counters such as `confirmations` are not.
+
+use explorer_types::{
+    BlockInfo, BlockStatsInfo, BlockTransaction, BlockTxVout, RawTransaction, RpcResponse,
+};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+fn fixture(name: &str) -> Value {
+    let path = format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+    let text = std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {}", path, e));
+    serde_json::from_str(&text).unwrap()
+}
+
+/// every field of the typed value is in the reply with the same value,
+/// fields the type does not declare are left out
+fn assert_subset(reply: &Value, typed: &Value, path: &str) {
+    match (reply, typed) {
+        (_, Value::Null) => {}
+        (Value::Object(r), Value::Object(t)) => {
+            for (key, value) in t {
+                let inner = format!("{}.{}", path, key);
+                match r.get(key) {
+                    Some(x) => assert_subset(x, value, &inner),
+                    None => assert!(value.is_null(), "{} is not in the reply", inner),
+                }
+            }
+        }
+        (Value::Array(r), Value::Array(t)) => {
+            assert_eq!(r.len(), t.len(), "{} length", path);
+            for (i, (x, y)) in r.iter().zip(t).enumerate() {
+                assert_subset(x, y, &format!("{}[{}]", path, i));
+            }
+        }
+        (Value::Number(r), Value::Number(t)) => {
+            assert_eq!(r.as_f64(), t.as_f64(), "{}", path);
+        }
+        _ => assert_eq!(reply, typed, "{}", path),
+    }
+}
+
+/// decodes the reply, encodes it back and decodes again
+fn round_trip<T: DeserializeOwned + Serialize>(name: &str) -> T {
+    let reply = fixture(name);
+    let decoded: RpcResponse<T> = serde_json::from_value(reply.clone()).unwrap();
+    let encoded = serde_json::to_value(&decoded.result).unwrap();
+    assert_subset(&reply["result"], &encoded, "result");
+    let again: T = serde_json::from_value(encoded.clone()).unwrap();
+    assert_eq!(serde_json::to_value(&again).unwrap(), encoded);
+    decoded.result
+}
+
+fn vout(value: &str) -> BlockTxVout {
+    let text = format!(
+        r#"{{"value": {}, "n": 0, "scriptPubKey": {{"asm": "", "hex": "", "type": "nonstandard"}}}}"#,
+        value
+    );
+    serde_json::from_str(&text).unwrap()
+}
+
+#[test]
+fn getblock() {
+    let block: BlockInfo = round_trip("getblock_170.json");
+    assert_eq!(block.height, 170);
+    assert_eq!(block.n_tx, 2);
+    assert_eq!(block.tx.len(), 2);
+    assert_eq!(block.size, 490);
+
+    let coinbase = &block.tx[0];
+    assert_eq!(coinbase.vin[0].txid, None);
+    assert_eq!(coinbase.vin[0].coinbase.as_deref(), Some("04ffff001d0102"));
+    assert_eq!(coinbase.vout[0].sats(), 5_000_000_000);
+    // P2PK has no address since Bitcoin Core 22
+    assert_eq!(coinbase.vout[0].script_pub_key.address(), None);
+    assert_eq!(coinbase.vout[0].script_pub_key.script_type, "pubkey");
+
+    let spend = &block.tx[1];
+    let sats: Vec<i64> = spend.vout.iter().map(BlockTxVout::sats).collect();
+    assert_eq!(sats, vec![1_000_000_000, 4_000_000_000]);
+}
+
+#[test]
+fn getrawtransaction() {
+    let tx: BlockTransaction = round_trip("getrawtransaction_f4184f.json");
+    assert_eq!(
+        tx.txid.as_deref(),
+        Some("f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16")
+    );
+    assert_eq!((tx.size, tx.vsize, tx.weight), (275, 275, 1100));
+    assert_eq!(tx.hex.len(), 2 * 275);
+    let vin = &tx.vin[0];
+    assert_eq!(vin.vout, Some(0));
+    assert!(vin.script_sig.as_ref().unwrap().asm.ends_with("[ALL]"));
+    assert_eq!(vin.sequence, 0xffff_ffff);
+}
+
+#[test]
+fn getrawtransaction_verbose() {
+    let tx: RawTransaction = round_trip("getrawtransaction_f4184f.json");
+    assert_eq!(
+        tx.blockhash.as_deref(),
+        Some("00000000d1145790a8694403d4063f323d499e655c83426834d4ce2f8dd4a2ee")
+    );
+    assert_eq!(tx.blocktime, Some(1231731025));
+    assert_eq!(tx.vout.len(), 2);
+}
+
+#[test]
+fn getblockstats() {
+    let stats: BlockStatsInfo = round_trip("getblockstats_170.json");
+    assert_eq!(stats.height, 170);
+    assert_eq!(stats.txs, 2);
+    assert_eq!(stats.subsidy, 5_000_000_000);
+    assert_eq!(stats.feerate_percentiles.len(), 5);
+}
+
+#[test]
+fn vout_sats_rounding() {
+    // none of these is exact in binary floating point
+    assert_eq!(vout("0.29").sats(), 29_000_000);
+    assert_eq!(vout("0.57000000").sats(), 57_000_000);
+    assert_eq!(vout("1.1").sats(), 110_000_000);
+    assert_eq!(vout("0.00000001").sats(), 1);
+    assert_eq!(vout("0.00000000").sats(), 0);
+    assert_eq!(vout("20999999.97690000").sats(), 2_099_999_997_690_000);
+}
+
+#[test]
+fn missing_optional_fields() {
+    // the tip block has no `nextblockhash`
+    let mut reply = fixture("getblock_170.json");
+    reply["result"]
+        .as_object_mut()
+        .unwrap()
+        .remove("nextblockhash");
+    let decoded: RpcResponse<BlockInfo> = serde_json::from_value(reply).unwrap();
+    assert_eq!(decoded.result.nextblockhash, None);
+
+    let out: BlockTxVout = serde_json::from_value(json!({
+        "value": 0.1,
+        "n": 1,
+        "scriptPubKey": {"asm": "", "hex": "", "type": "nulldata"},
+    }))
+    .unwrap();
+    assert_eq!(out.script_pub_key.address(), None);
+    assert_eq!(out.sats(), 10_000_000);
+}